@@ -0,0 +1,31 @@
+//! Authentication errors.
+
+use denc::RadosError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CephXError {
+    #[error("bad key: {0}")]
+    BadKey(String),
+
+    #[error("decrypt failed (wrong key or corrupt payload)")]
+    DecryptFailed,
+
+    #[error("ticket expired")]
+    TicketExpired,
+
+    #[error("access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("keyring parse error: {0}")]
+    KeyringParse(String),
+
+    #[error("duplicate keyring entry for {0}")]
+    DuplicateEntity(String),
+
+    #[error(transparent)]
+    Encoding(#[from] RadosError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
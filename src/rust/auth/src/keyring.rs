@@ -0,0 +1,229 @@
+//! Keyring files (`/etc/ceph/ceph.client.admin.keyring` and friends).
+//!
+//! Keyrings use the same INI-like syntax as ceph.conf: one section per
+//! entity, with `key` and optional `caps <service>` entries.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::CephXError;
+use crate::types::{CryptoKey, EntityName};
+
+/// One keyring section: the entity's secret plus its capability strings,
+/// keyed by service name (`mon`, `osd`, ...).
+#[derive(Debug, Clone)]
+pub struct KeyringEntry {
+    pub name: EntityName,
+    pub key: CryptoKey,
+    pub caps: BTreeMap<String, String>,
+}
+
+/// What [`Keyring::merge`] does when both keyrings name the same entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the entry already in `self`.
+    KeepSelf,
+    /// Take the entry from `other`.
+    KeepOther,
+    /// Fail with [`CephXError::DuplicateEntity`].
+    Error,
+}
+
+/// A parsed keyring: entity name → entry.
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    entries: BTreeMap<String, KeyringEntry>,
+}
+
+impl Keyring {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, CephXError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::from_str_contents(&text)
+    }
+
+    pub fn from_str_contents(text: &str) -> Result<Self, CephXError> {
+        let mut entries = BTreeMap::new();
+        let mut current: Option<(EntityName, Option<CryptoKey>, BTreeMap<String, String>)> = None;
+
+        let flush = |current: &mut Option<(EntityName, Option<CryptoKey>, BTreeMap<String, String>)>,
+                         entries: &mut BTreeMap<String, KeyringEntry>|
+         -> Result<(), CephXError> {
+            if let Some((name, key, caps)) = current.take() {
+                let key = key.ok_or_else(|| {
+                    CephXError::KeyringParse(format!("section [{name}] has no key"))
+                })?;
+                entries.insert(name.to_string(), KeyringEntry { name, key, caps });
+            }
+            Ok(())
+        };
+
+        for (lineno, raw_line) in text.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                flush(&mut current, &mut entries)?;
+                let name = section.trim().parse()?;
+                current = Some((name, None, BTreeMap::new()));
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                CephXError::KeyringParse(format!("line {}: expected key = value", lineno + 1))
+            })?;
+            let (key, value) = (key.trim(), value.trim().trim_matches('"'));
+            let Some((_, secret, caps)) = current.as_mut() else {
+                return Err(CephXError::KeyringParse(format!(
+                    "line {}: entry outside a section",
+                    lineno + 1
+                )));
+            };
+            if key == "key" {
+                *secret = Some(CryptoKey::from_base64(value)?);
+            } else if let Some(service) = key.strip_prefix("caps ") {
+                caps.insert(service.trim().to_string(), value.to_string());
+            }
+            // Unknown attributes are ignored, like the C++ parser does.
+        }
+        flush(&mut current, &mut entries)?;
+        Ok(Keyring { entries })
+    }
+
+    /// Loads each file in order and merges them, earlier files winning on
+    /// conflicts — mirroring how the C++ client walks its `keyring` search
+    /// path.
+    pub fn from_multiple_files<P: AsRef<Path>>(paths: &[P]) -> Result<Self, CephXError> {
+        let mut merged = Keyring::default();
+        for path in paths {
+            merged = merged.merge(Self::from_file(path)?, ConflictPolicy::KeepSelf)?;
+        }
+        Ok(merged)
+    }
+
+    /// Combines two keyrings; `on_conflict` decides which entry survives
+    /// when both name the same entity.
+    pub fn merge(mut self, other: Keyring, on_conflict: ConflictPolicy) -> Result<Self, CephXError> {
+        for (name, entry) in other.entries {
+            match self.entries.entry(name) {
+                std::collections::btree_map::Entry::Vacant(slot) => {
+                    slot.insert(entry);
+                }
+                std::collections::btree_map::Entry::Occupied(mut slot) => match on_conflict {
+                    ConflictPolicy::KeepSelf => {}
+                    ConflictPolicy::KeepOther => {
+                        slot.insert(entry);
+                    }
+                    ConflictPolicy::Error => {
+                        return Err(CephXError::DuplicateEntity(slot.key().clone()));
+                    }
+                },
+            }
+        }
+        Ok(self)
+    }
+
+    /// The entity names in this keyring, in sorted order.
+    pub fn entity_names(&self) -> Vec<&str> {
+        self.entries.keys().map(String::as_str).collect()
+    }
+
+    pub fn get(&self, entity: &str) -> Option<&KeyringEntry> {
+        self.entries.get(entity)
+    }
+
+    pub fn get_key(&self, entity: &str) -> Option<&CryptoKey> {
+        self.entries.get(entity).map(|e| &e.key)
+    }
+
+    pub fn insert(&mut self, entry: KeyringEntry) {
+        self.entries.insert(entry.name.to_string(), entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn sample_keyring_text() -> String {
+        let key = CryptoKey::new_aes(Bytes::from_static(&[9u8; 16])).unwrap();
+        format!(
+            "[client.admin]\n\tkey = {}\n\tcaps mon = \"allow *\"\n\tcaps osd = \"allow *\"\n",
+            key.to_base64()
+        )
+    }
+
+    #[test]
+    fn parse_keyring() {
+        let keyring = Keyring::from_str_contents(&sample_keyring_text()).unwrap();
+        assert_eq!(keyring.len(), 1);
+        let entry = keyring.get("client.admin").unwrap();
+        assert_eq!(entry.caps["mon"], "allow *");
+        assert!(keyring.get_key("client.admin").is_some());
+        assert!(keyring.get("client.other").is_none());
+    }
+
+    #[test]
+    fn section_without_key_is_an_error() {
+        let err = Keyring::from_str_contents("[client.x]\ncaps mon = \"allow r\"\n").unwrap_err();
+        assert!(matches!(err, CephXError::KeyringParse(_)));
+    }
+
+    fn keyring_for(entity: &str, byte: u8) -> Keyring {
+        let key = CryptoKey::new_aes(Bytes::from_static(&[9u8; 16])).unwrap();
+        let text = format!("[{entity}]\nkey = {}\ncaps mon = \"allow {byte}\"\n", key.to_base64());
+        Keyring::from_str_contents(&text).unwrap()
+    }
+
+    #[test]
+    fn merge_resolves_conflicts_by_policy() {
+        let ours = keyring_for("client.admin", 1);
+        let theirs = keyring_for("client.admin", 2).merge(keyring_for("client.rgw", 3), ConflictPolicy::Error).unwrap();
+
+        let kept = ours.clone().merge(theirs.clone(), ConflictPolicy::KeepSelf).unwrap();
+        assert_eq!(kept.get("client.admin").unwrap().caps["mon"], "allow 1");
+        assert_eq!(kept.entity_names(), ["client.admin", "client.rgw"]);
+
+        let taken = ours.clone().merge(theirs.clone(), ConflictPolicy::KeepOther).unwrap();
+        assert_eq!(taken.get("client.admin").unwrap().caps["mon"], "allow 2");
+
+        let err = ours.merge(theirs, ConflictPolicy::Error).unwrap_err();
+        assert!(matches!(err, CephXError::DuplicateEntity(name) if name == "client.admin"));
+    }
+
+    #[test]
+    fn from_multiple_files_keeps_the_first_entry() {
+        let dir = std::env::temp_dir().join(format!("keyring-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = CryptoKey::new_aes(Bytes::from_static(&[9u8; 16])).unwrap();
+        let first = dir.join("first.keyring");
+        let second = dir.join("second.keyring");
+        std::fs::write(
+            &first,
+            format!("[client.admin]\nkey = {}\ncaps mon = \"allow *\"\n", key.to_base64()),
+        )
+        .unwrap();
+        std::fs::write(
+            &second,
+            format!(
+                "[client.admin]\nkey = {}\n[osd.0]\nkey = {}\n",
+                key.to_base64(),
+                key.to_base64()
+            ),
+        )
+        .unwrap();
+
+        let merged = Keyring::from_multiple_files(&[&first, &second]).unwrap();
+        assert_eq!(merged.entity_names(), ["client.admin", "osd.0"]);
+        assert_eq!(merged.get("client.admin").unwrap().caps["mon"], "allow *");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
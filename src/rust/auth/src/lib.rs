@@ -0,0 +1,19 @@
+//! CephX authentication.
+//!
+//! This crate mirrors the C++ `src/auth` tree: key material and entity
+//! naming ([`types`]), the wire structures of the cephx protocol
+//! ([`protocol`]), keyring file handling ([`keyring`]), the pluggable
+//! provider interface used by the messenger ([`provider`]) and the
+//! client/server negotiation handlers ([`cephx`]).
+
+pub mod cephx;
+pub mod error;
+pub mod keyring;
+pub mod protocol;
+pub mod provider;
+pub mod types;
+
+pub use cephx::{CachedServiceTicket, CephXClientHandler, ServiceId};
+pub use error::CephXError;
+pub use keyring::Keyring;
+pub use types::{CryptoKey, EntityName};
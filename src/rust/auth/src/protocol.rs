@@ -0,0 +1,213 @@
+//! Wire structures of the cephx protocol.
+
+use bytes::{Bytes, BytesMut};
+use denc::types::UTime;
+use denc::{Denc, RadosError};
+
+use crate::error::CephXError;
+use crate::types::{AuthTicket, CryptoKey, EntityName};
+
+/// Magic value embedded in every cephx-encrypted payload so the receiver
+/// can tell a wrong key from corrupt data (`AUTH_ENC_MAGIC` in C++).
+pub const CEPHX_ENC_MAGIC: u64 = 0xff00_9cad_8826_aa55;
+
+/// The opaque ticket a client presents to a service.  Only the service (and
+/// the monitors) hold the key that decrypts `blob`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CephXTicketBlob {
+    pub secret_id: u64,
+    pub blob: Bytes,
+}
+
+impl Denc for CephXTicketBlob {
+    fn encode(&self, buf: &mut BytesMut) {
+        1u8.encode(buf); // struct_v
+        self.secret_id.encode(buf);
+        self.blob.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let struct_v = u8::decode(buf)?;
+        if struct_v != 1 {
+            return Err(RadosError::UnsupportedVersion {
+                version: struct_v,
+                max_supported: 1,
+            });
+        }
+        Ok(CephXTicketBlob {
+            secret_id: u64::decode(buf)?,
+            blob: Bytes::decode(buf)?,
+        })
+    }
+}
+
+/// The service-key-encrypted contents of a ticket blob
+/// (`CephXServiceTicketInfo`): who the client is and the session key both
+/// sides will share.
+#[derive(Debug, Clone)]
+pub struct CephXServiceTicketInfo {
+    pub entity: EntityName,
+    pub global_id: u64,
+    pub session_key: CryptoKey,
+    pub expires: UTime,
+    pub caps: Bytes,
+}
+
+impl CephXServiceTicketInfo {
+    /// True once `now` has reached the expiry.  The boundary counts as
+    /// expired: a ticket presented at exactly `expires` is refused.
+    pub fn is_expired(&self, now: UTime) -> bool {
+        now >= self.expires
+    }
+
+    /// How much validity remains at `now`; `None` once expired.
+    pub fn ttl_remaining(&self, now: UTime) -> Option<std::time::Duration> {
+        if self.is_expired(now) {
+            return None;
+        }
+        Some(self.expires.elapsed_since(&now))
+    }
+}
+
+impl Denc for CephXServiceTicketInfo {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.entity.entity_type.encode(buf);
+        self.entity.id.encode(buf);
+        self.global_id.encode(buf);
+        self.session_key.encode(buf);
+        self.expires.encode(buf);
+        self.caps.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let entity_type = u32::decode(buf)?;
+        let id = String::decode(buf)?;
+        Ok(CephXServiceTicketInfo {
+            entity: EntityName::new(entity_type, id),
+            global_id: u64::decode(buf)?,
+            session_key: CryptoKey::decode(buf)?,
+            expires: UTime::decode(buf)?,
+            caps: Bytes::decode(buf)?,
+        })
+    }
+}
+
+/// The authorizer a client sends when opening a connection to a service:
+/// its ticket blob plus a nonce encrypted with the session key, proving it
+/// actually holds that key.
+#[derive(Debug, Clone)]
+pub struct CephXAuthorizer {
+    pub global_id: u64,
+    pub service_id: u32,
+    pub ticket: CephXTicketBlob,
+    /// `nonce` encrypted with the session key.
+    pub encrypted_nonce: Bytes,
+}
+
+impl CephXAuthorizer {
+    /// Builds an authorizer for `ticket`, encrypting `nonce` with the
+    /// ticket's session key.
+    pub fn build(
+        ticket: &AuthTicket,
+        ticket_blob: CephXTicketBlob,
+        service_id: u32,
+        nonce: u64,
+    ) -> Result<Self, CephXError> {
+        let mut plain = BytesMut::new();
+        CEPHX_ENC_MAGIC.encode(&mut plain);
+        nonce.encode(&mut plain);
+        let encrypted_nonce = ticket.session_key.encrypt(&plain)?;
+        Ok(CephXAuthorizer {
+            global_id: ticket.global_id,
+            service_id,
+            ticket: ticket_blob,
+            encrypted_nonce,
+        })
+    }
+}
+
+impl Denc for CephXAuthorizer {
+    fn encode(&self, buf: &mut BytesMut) {
+        1u8.encode(buf); // struct_v
+        self.global_id.encode(buf);
+        self.service_id.encode(buf);
+        self.ticket.encode(buf);
+        self.encrypted_nonce.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let struct_v = u8::decode(buf)?;
+        if struct_v != 1 {
+            return Err(RadosError::UnsupportedVersion {
+                version: struct_v,
+                max_supported: 1,
+            });
+        }
+        Ok(CephXAuthorizer {
+            global_id: u64::decode(buf)?,
+            service_id: u32::decode(buf)?,
+            ticket: CephXTicketBlob::decode(buf)?,
+            encrypted_nonce: Bytes::decode(buf)?,
+        })
+    }
+}
+
+/// Decrypts and validates a cephx-encrypted payload, checking the magic.
+pub fn decrypt_enc_payload(key: &CryptoKey, ciphertext: &[u8]) -> Result<Bytes, CephXError> {
+    let mut plain = key.decrypt(ciphertext)?;
+    let magic = u64::decode(&mut plain).map_err(|_| CephXError::DecryptFailed)?;
+    if magic != CEPHX_ENC_MAGIC {
+        return Err(CephXError::DecryptFailed);
+    }
+    Ok(plain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticket_blob_round_trip() {
+        let blob = CephXTicketBlob {
+            secret_id: 42,
+            blob: Bytes::from_static(b"opaque"),
+        };
+        let mut raw = denc::encode_to_bytes(&blob);
+        assert_eq!(CephXTicketBlob::decode(&mut raw).unwrap(), blob);
+    }
+
+    #[test]
+    fn ticket_expiry_edge_cases() {
+        let info = CephXServiceTicketInfo {
+            entity: "client.admin".parse().unwrap(),
+            global_id: 1,
+            session_key: CryptoKey::new_aes(Bytes::from_static(&[1u8; 16])).unwrap(),
+            expires: UTime::new(1000, 0),
+            caps: Bytes::new(),
+        };
+        assert!(!info.is_expired(UTime::new(999, 0)));
+        assert_eq!(
+            info.ttl_remaining(UTime::new(999, 0)),
+            Some(std::time::Duration::from_secs(1))
+        );
+        // Exactly at expiry counts as expired.
+        assert!(info.is_expired(UTime::new(1000, 0)));
+        assert_eq!(info.ttl_remaining(UTime::new(1000, 0)), None);
+        assert!(info.is_expired(UTime::new(1001, 0)));
+        assert_eq!(info.ttl_remaining(UTime::new(1001, 0)), None);
+    }
+
+    #[test]
+    fn enc_payload_magic_check() {
+        let key = CryptoKey::new_aes(Bytes::from_static(&[3u8; 16])).unwrap();
+        let mut plain = BytesMut::new();
+        CEPHX_ENC_MAGIC.encode(&mut plain);
+        0xdead_beefu64.encode(&mut plain);
+        let ciphertext = key.encrypt(&plain).unwrap();
+        let mut decrypted = decrypt_enc_payload(&key, &ciphertext).unwrap();
+        assert_eq!(u64::decode(&mut decrypted).unwrap(), 0xdead_beef);
+
+        let wrong = CryptoKey::new_aes(Bytes::from_static(&[4u8; 16])).unwrap();
+        assert!(decrypt_enc_payload(&wrong, &ciphertext).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! The pluggable authentication interface consumed by the messenger.
+//!
+//! The msgr2 state machine drives authentication through an
+//! [`AuthProvider`]: it asks for the initial `AUTH_REQUEST` payload, feeds
+//! back each server reply, and is told when the exchange is complete.
+
+use bytes::{Bytes, BytesMut};
+use denc::Denc;
+
+use crate::cephx::{CephXClientHandler, CephXServerHandler};
+use crate::error::CephXError;
+use crate::protocol::CephXAuthorizer;
+use crate::types::{AuthCapsInfo, CryptoKey, EntityName};
+
+/// Authentication method ids (`CEPH_AUTH_*` in C++).
+pub const CEPH_AUTH_NONE: u32 = 1;
+pub const CEPH_AUTH_CEPHX: u32 = 2;
+
+/// The outcome of feeding one server reply to the provider.
+pub enum AuthStep {
+    /// Send this payload as `AUTH_REQUEST_MORE` and keep going.
+    Continue(Bytes),
+    /// Authentication finished; `connection_secret` keys the session
+    /// encryption when the connection runs in secure mode.
+    Done {
+        global_id: u64,
+        connection_secret: Bytes,
+    },
+}
+
+/// Client-side authentication strategy for one cluster.
+///
+/// Implementations must be usable from several connections concurrently;
+/// per-exchange state belongs behind interior mutability.
+pub trait AuthProvider: Send + Sync {
+    fn entity_name(&self) -> &EntityName;
+
+    /// The `method` advertised in the `AUTH_REQUEST` frame.
+    fn method(&self) -> u32;
+
+    /// The initial `AUTH_REQUEST` payload.
+    fn build_initial_request(&self) -> Result<Bytes, CephXError>;
+
+    /// Handles an `AUTH_REPLY_MORE` or `AUTH_DONE` payload.
+    fn handle_reply(&self, reply: &mut Bytes) -> Result<AuthStep, CephXError>;
+}
+
+/// `auth_supported = none`: authentication is a formality.
+pub struct NoneAuthProvider {
+    entity: EntityName,
+}
+
+impl NoneAuthProvider {
+    pub fn new(entity: EntityName) -> Self {
+        NoneAuthProvider { entity }
+    }
+}
+
+impl AuthProvider for NoneAuthProvider {
+    fn entity_name(&self) -> &EntityName {
+        &self.entity
+    }
+
+    fn method(&self) -> u32 {
+        CEPH_AUTH_NONE
+    }
+
+    fn build_initial_request(&self) -> Result<Bytes, CephXError> {
+        let mut buf = BytesMut::new();
+        self.entity.entity_type.encode(&mut buf);
+        self.entity.id.encode(&mut buf);
+        Ok(buf.freeze())
+    }
+
+    fn handle_reply(&self, _reply: &mut Bytes) -> Result<AuthStep, CephXError> {
+        Ok(AuthStep::Done {
+            global_id: 0,
+            connection_secret: Bytes::new(),
+        })
+    }
+}
+
+/// Client-side cephx against the monitors: sends the entity name, answers
+/// the server challenge with a proof encrypted under the permanent key.
+/// Use this when *opening* a connection to the cluster; services
+/// *accepting* connections want [`ServiceAuthProvider`] instead, and code
+/// that needs the raw negotiation (e.g. the monitors themselves) uses
+/// [`CephXServerHandler`] directly.
+pub struct MonitorAuthProvider {
+    handler: CephXClientHandler,
+}
+
+impl MonitorAuthProvider {
+    pub fn new(entity: EntityName, secret: CryptoKey) -> Self {
+        MonitorAuthProvider {
+            handler: CephXClientHandler::new(entity, secret),
+        }
+    }
+
+    pub fn handler(&self) -> &CephXClientHandler {
+        &self.handler
+    }
+}
+
+impl AuthProvider for MonitorAuthProvider {
+    fn entity_name(&self) -> &EntityName {
+        self.handler.entity_name()
+    }
+
+    fn method(&self) -> u32 {
+        CEPH_AUTH_CEPHX
+    }
+
+    fn build_initial_request(&self) -> Result<Bytes, CephXError> {
+        let mut buf = BytesMut::new();
+        let entity = self.handler.entity_name();
+        entity.entity_type.encode(&mut buf);
+        entity.id.encode(&mut buf);
+        Ok(buf.freeze())
+    }
+
+    fn handle_reply(&self, reply: &mut Bytes) -> Result<AuthStep, CephXError> {
+        // The server either sends a challenge (continue) or the final
+        // AUTH_DONE payload carrying our global_id and connection secret.
+        let done = bool::decode(reply)?;
+        if done {
+            let global_id = u64::decode(reply)?;
+            let connection_secret = Bytes::decode(reply)?;
+            return Ok(AuthStep::Done {
+                global_id,
+                connection_secret,
+            });
+        }
+        let server_challenge = u64::decode(reply)?;
+        let mut proof_plain = BytesMut::new();
+        crate::protocol::CEPHX_ENC_MAGIC.encode(&mut proof_plain);
+        server_challenge.encode(&mut proof_plain);
+        let proof = self.handler.secret().encrypt(&proof_plain)?;
+        let mut out = BytesMut::new();
+        proof.encode(&mut out);
+        Ok(AuthStep::Continue(out.freeze()))
+    }
+}
+
+/// Server-side cephx for a service daemon (OSD, MDS): verifies the
+/// authorizer blob a connecting client presents and derives what the
+/// client may do.  Unlike [`MonitorAuthProvider`] it never initiates an
+/// exchange, and unlike [`CephXServerHandler`] it hides the reply-blob
+/// mechanics behind the [`AuthProvider`] interface.
+pub struct ServiceAuthProvider {
+    entity: EntityName,
+    handler: CephXServerHandler,
+}
+
+impl ServiceAuthProvider {
+    pub fn new(entity: EntityName, service_key: CryptoKey) -> Self {
+        ServiceAuthProvider {
+            entity,
+            handler: CephXServerHandler::new(service_key),
+        }
+    }
+
+    /// Decodes and verifies a raw authorizer blob against `service_key`,
+    /// returning the capabilities granted by the embedded ticket.
+    pub fn verify_authorizer(
+        raw: &[u8],
+        service_key: &CryptoKey,
+    ) -> Result<AuthCapsInfo, CephXError> {
+        let mut buf = Bytes::copy_from_slice(raw);
+        let authorizer = CephXAuthorizer::decode(&mut buf)?;
+        let handler = CephXServerHandler::new(service_key.clone());
+        let (info, _reply) = handler.verify_authorizer(&authorizer)?;
+        Ok(AuthCapsInfo {
+            allow_all: info.caps.as_ref() == b"allow *",
+            caps: info.caps,
+        })
+    }
+}
+
+impl AuthProvider for ServiceAuthProvider {
+    fn entity_name(&self) -> &EntityName {
+        &self.entity
+    }
+
+    fn method(&self) -> u32 {
+        CEPH_AUTH_CEPHX
+    }
+
+    /// Servers never initiate; the exchange starts with the client's
+    /// authorizer arriving via [`AuthProvider::handle_reply`].
+    fn build_initial_request(&self) -> Result<Bytes, CephXError> {
+        Ok(Bytes::new())
+    }
+
+    fn handle_reply(&self, reply: &mut Bytes) -> Result<AuthStep, CephXError> {
+        let authorizer = CephXAuthorizer::decode(reply)?;
+        // The proof reply is dropped here; connections that must echo it
+        // back to the client use [`CephXServerHandler`] directly.
+        let (info, _proof) = self.handler.verify_authorizer(&authorizer)?;
+        Ok(AuthStep::Done {
+            global_id: info.global_id,
+            connection_secret: Bytes::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn none_provider_is_immediately_done() {
+        let provider = NoneAuthProvider::new("client.admin".parse().unwrap());
+        assert_eq!(provider.method(), CEPH_AUTH_NONE);
+        let mut reply = Bytes::new();
+        assert!(matches!(
+            provider.handle_reply(&mut reply).unwrap(),
+            AuthStep::Done { .. }
+        ));
+    }
+
+    #[test]
+    fn service_provider_verifies_client_authorizer() {
+        use crate::cephx::{make_ticket_blob, CephXClientHandler, ServiceTicket};
+        use crate::protocol::CephXServiceTicketInfo;
+        use crate::types::AuthTicket;
+        use denc::types::UTime;
+
+        let service_key = CryptoKey::new_aes(Bytes::from_static(&[9u8; 16])).unwrap();
+        let session_key = CryptoKey::new_aes(Bytes::from_static(&[10u8; 16])).unwrap();
+        let entity: EntityName = "client.admin".parse().unwrap();
+
+        let info = CephXServiceTicketInfo {
+            entity: entity.clone(),
+            global_id: 7,
+            session_key: session_key.clone(),
+            expires: UTime::new(u32::MAX, 0),
+            caps: Bytes::from_static(b"allow *"),
+        };
+        let blob = make_ticket_blob(&service_key, 1, &info).unwrap();
+        let client = CephXClientHandler::new(entity.clone(), session_key.clone());
+        client.set_ticket(
+            4, // osd
+            ServiceTicket {
+                ticket: AuthTicket {
+                    entity: entity.clone(),
+                    global_id: 7,
+                    session_key,
+                    expires: UTime::new(u32::MAX, 0),
+                    caps: Bytes::from_static(b"allow *"),
+                },
+                blob,
+            },
+        );
+        let raw = denc::encode_to_bytes(&client.build_authorizer(4).unwrap());
+
+        let caps = ServiceAuthProvider::verify_authorizer(&raw, &service_key).unwrap();
+        assert!(caps.allow_all);
+
+        let wrong_key = CryptoKey::new_aes(Bytes::from_static(&[11u8; 16])).unwrap();
+        assert!(ServiceAuthProvider::verify_authorizer(&raw, &wrong_key).is_err());
+
+        let provider = ServiceAuthProvider::new("osd.0".parse().unwrap(), service_key);
+        let mut reply = raw.clone();
+        match provider.handle_reply(&mut reply).unwrap() {
+            AuthStep::Done { global_id, .. } => assert_eq!(global_id, 7),
+            AuthStep::Continue(_) => panic!("expected verification to finish"),
+        }
+    }
+
+    #[test]
+    fn cephx_provider_answers_challenge() {
+        let key = CryptoKey::new_aes(Bytes::from_static(&[8u8; 16])).unwrap();
+        let provider = MonitorAuthProvider::new("client.admin".parse().unwrap(), key);
+        let mut reply = BytesMut::new();
+        false.encode(&mut reply); // not done: challenge follows
+        0x1234_5678u64.encode(&mut reply);
+        let mut reply = reply.freeze();
+        match provider.handle_reply(&mut reply).unwrap() {
+            AuthStep::Continue(payload) => assert!(!payload.is_empty()),
+            AuthStep::Done { .. } => panic!("expected a challenge response"),
+        }
+    }
+}
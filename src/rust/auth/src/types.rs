@@ -0,0 +1,292 @@
+//! Key material, entity names and session state.
+
+use std::fmt;
+use std::str::FromStr;
+
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
+use denc::types::UTime;
+use denc::{Denc, RadosError};
+
+use crate::error::CephXError;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// Entity type bits, matching `CEPH_ENTITY_TYPE_*` in `include/msgr.h`.
+pub const CEPH_ENTITY_TYPE_MON: u32 = 0x01;
+pub const CEPH_ENTITY_TYPE_MDS: u32 = 0x02;
+pub const CEPH_ENTITY_TYPE_OSD: u32 = 0x04;
+pub const CEPH_ENTITY_TYPE_CLIENT: u32 = 0x08;
+pub const CEPH_ENTITY_TYPE_MGR: u32 = 0x10;
+pub const CEPH_ENTITY_TYPE_AUTH: u32 = 0x20;
+
+/// Key type discriminator; AES is the only type modern clusters use.
+pub const CEPH_CRYPTO_AES: u16 = 1;
+
+/// The fixed cephx CBC initialization vector (`CEPH_AES_IV` in C++).
+const CEPH_AES_IV: &[u8; 16] = b"cephsageyudagreg";
+
+/// A named Ceph entity, e.g. `client.admin` or `osd.3`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntityName {
+    pub entity_type: u32,
+    pub id: String,
+}
+
+impl EntityName {
+    pub fn new(entity_type: u32, id: impl Into<String>) -> Self {
+        EntityName {
+            entity_type,
+            id: id.into(),
+        }
+    }
+
+    pub fn client(id: impl Into<String>) -> Self {
+        EntityName::new(CEPH_ENTITY_TYPE_CLIENT, id)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.entity_type {
+            CEPH_ENTITY_TYPE_MON => "mon",
+            CEPH_ENTITY_TYPE_MDS => "mds",
+            CEPH_ENTITY_TYPE_OSD => "osd",
+            CEPH_ENTITY_TYPE_CLIENT => "client",
+            CEPH_ENTITY_TYPE_MGR => "mgr",
+            CEPH_ENTITY_TYPE_AUTH => "auth",
+            _ => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for EntityName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.type_name(), self.id)
+    }
+}
+
+impl FromStr for EntityName {
+    type Err = CephXError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (type_str, id) = s
+            .split_once('.')
+            .ok_or_else(|| CephXError::BadKey(format!("malformed entity name {s:?}")))?;
+        let entity_type = match type_str {
+            "mon" => CEPH_ENTITY_TYPE_MON,
+            "mds" => CEPH_ENTITY_TYPE_MDS,
+            "osd" => CEPH_ENTITY_TYPE_OSD,
+            "client" => CEPH_ENTITY_TYPE_CLIENT,
+            "mgr" => CEPH_ENTITY_TYPE_MGR,
+            "auth" => CEPH_ENTITY_TYPE_AUTH,
+            other => {
+                return Err(CephXError::BadKey(format!("unknown entity type {other:?}")))
+            }
+        };
+        Ok(EntityName::new(entity_type, id))
+    }
+}
+
+/// A secret key (`CryptoKey` in C++): type tag, creation time and raw
+/// secret.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CryptoKey {
+    pub key_type: u16,
+    pub created: UTime,
+    pub secret: Bytes,
+}
+
+impl fmt::Debug for CryptoKey {
+    // Never log key material.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CryptoKey(type={}, {} bytes)",
+            self.key_type,
+            self.secret.len()
+        )
+    }
+}
+
+impl CryptoKey {
+    pub fn new_aes(secret: impl Into<Bytes>) -> Result<Self, CephXError> {
+        let secret = secret.into();
+        if secret.len() != 16 {
+            return Err(CephXError::BadKey(format!(
+                "AES key must be 16 bytes, got {}",
+                secret.len()
+            )));
+        }
+        Ok(CryptoKey {
+            key_type: CEPH_CRYPTO_AES,
+            created: UTime::default(),
+            secret,
+        })
+    }
+
+    /// Parses the base64 `key = ...` value found in keyrings.  The decoded
+    /// blob is the `CryptoKey` wire encoding: type, created, secret.
+    pub fn from_base64(encoded: &str) -> Result<Self, CephXError> {
+        let raw = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| CephXError::BadKey(format!("invalid base64: {e}")))?;
+        let mut buf = Bytes::from(raw);
+        Ok(Self::decode(&mut buf)?)
+    }
+
+    pub fn to_base64(&self) -> String {
+        let mut buf = BytesMut::new();
+        self.encode(&mut buf);
+        base64::engine::general_purpose::STANDARD.encode(buf)
+    }
+
+    /// AES-128-CBC encryption with the fixed cephx IV and PKCS#7 padding.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Bytes, CephXError> {
+        let cipher = Aes128CbcEnc::new_from_slices(&self.secret, CEPH_AES_IV)
+            .map_err(|e| CephXError::BadKey(e.to_string()))?;
+        Ok(Bytes::from(
+            cipher.encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+        ))
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Bytes, CephXError> {
+        let cipher = Aes128CbcDec::new_from_slices(&self.secret, CEPH_AES_IV)
+            .map_err(|e| CephXError::BadKey(e.to_string()))?;
+        cipher
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map(Bytes::from)
+            .map_err(|_| CephXError::DecryptFailed)
+    }
+}
+
+impl Denc for CryptoKey {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.key_type.encode(buf);
+        self.created.encode(buf);
+        (self.secret.len() as u16).encode(buf);
+        buf.extend_from_slice(&self.secret);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let key_type = u16::decode(buf)?;
+        let created = UTime::decode(buf)?;
+        let len = u16::decode(buf)? as usize;
+        denc::need(buf, len)?;
+        use bytes::Buf;
+        let secret = buf.copy_to_bytes(len);
+        Ok(CryptoKey {
+            key_type,
+            created,
+            secret,
+        })
+    }
+}
+
+/// Per-connection authentication session state.
+#[derive(Debug, Clone)]
+pub struct CephXSession {
+    pub session_key: CryptoKey,
+    pub global_id: u64,
+}
+
+impl CephXSession {
+    /// Derives a 128-bit subkey from the session key via HKDF-SHA256,
+    /// with `domain` as the info string.  Subprotocols that need their
+    /// own encryption key use a distinct domain separator instead of
+    /// sharing the root session key.
+    pub fn derive_key(&self, domain: &[u8]) -> Result<Bytes, CephXError> {
+        let hkdf = hkdf::Hkdf::<sha2::Sha256>::new(None, &self.session_key.secret);
+        let mut okm = [0u8; 16];
+        hkdf.expand(domain, &mut okm)
+            .map_err(|e| CephXError::BadKey(e.to_string()))?;
+        Ok(Bytes::copy_from_slice(&okm))
+    }
+}
+
+/// A granted ticket for one service: session key, validity window and the
+/// opaque (service-key-encrypted) ticket blob to present.
+#[derive(Debug, Clone)]
+pub struct AuthTicket {
+    pub entity: EntityName,
+    pub global_id: u64,
+    pub session_key: CryptoKey,
+    pub expires: UTime,
+    pub caps: Bytes,
+}
+
+impl AuthTicket {
+    /// True while the expiry lies in the future.
+    pub fn is_valid(&self) -> bool {
+        UTime::now() < self.expires
+    }
+}
+
+/// What an authenticated peer is allowed to do, as decoded from its ticket.
+#[derive(Debug, Clone, Default)]
+pub struct AuthCapsInfo {
+    pub allow_all: bool,
+    pub caps: Bytes,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_name_parse_and_display() {
+        let name: EntityName = "client.admin".parse().unwrap();
+        assert_eq!(name.entity_type, CEPH_ENTITY_TYPE_CLIENT);
+        assert_eq!(name.to_string(), "client.admin");
+        assert!("monadmin".parse::<EntityName>().is_err());
+    }
+
+    #[test]
+    fn auth_ticket_validity() {
+        let mut ticket = AuthTicket {
+            entity: EntityName::client("admin"),
+            global_id: 1,
+            session_key: CryptoKey::new_aes(Bytes::from_static(&[1u8; 16])).unwrap(),
+            expires: UTime::new(u32::MAX, 0),
+            caps: Bytes::new(),
+        };
+        assert!(ticket.is_valid());
+        ticket.expires = UTime::new(1, 0);
+        assert!(!ticket.is_valid());
+    }
+
+    #[test]
+    fn derived_keys_are_domain_separated() {
+        let session = CephXSession {
+            session_key: CryptoKey::new_aes(Bytes::from_static(&[5u8; 16])).unwrap(),
+            global_id: 42,
+        };
+        let msgr = session.derive_key(b"msgr2.1 payload").unwrap();
+        let rbd = session.derive_key(b"rbd mirror").unwrap();
+        assert_eq!(msgr.len(), 16);
+        assert_ne!(msgr, rbd);
+        // Derivation is deterministic per domain.
+        assert_eq!(msgr, session.derive_key(b"msgr2.1 payload").unwrap());
+        // The subkey never equals the root key itself.
+        assert_ne!(msgr, session.session_key.secret);
+    }
+
+    #[test]
+    fn crypto_key_base64_round_trip() {
+        let key = CryptoKey::new_aes(Bytes::from_static(&[7u8; 16])).unwrap();
+        let encoded = key.to_base64();
+        let decoded = CryptoKey::from_base64(&encoded).unwrap();
+        assert_eq!(decoded, key);
+    }
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let key = CryptoKey::new_aes(Bytes::from_static(&[1u8; 16])).unwrap();
+        let ciphertext = key.encrypt(b"the quick brown fox").unwrap();
+        assert_ne!(&ciphertext[..], b"the quick brown fox");
+        assert_eq!(&key.decrypt(&ciphertext).unwrap()[..], b"the quick brown fox");
+
+        let wrong = CryptoKey::new_aes(Bytes::from_static(&[2u8; 16])).unwrap();
+        assert!(wrong.decrypt(&ciphertext).is_err());
+    }
+}
@@ -0,0 +1,527 @@
+//! ceph.conf parsing.
+//!
+//! A small INI reader covering the subset of `src/common/ConfUtils`
+//! behavior the client stack needs: sections, `key = value` lines,
+//! comments (`#` and `;`), and normalization of spaces to underscores in
+//! option names.
+
+#[cfg(feature = "watch")]
+pub mod watcher;
+#[cfg(feature = "watch")]
+pub use watcher::ConfigWatcher;
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("cannot read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("line {line}: {reason}")]
+    Parse { line: usize, reason: String },
+
+    #[error("option {option:?} has invalid value {value:?}")]
+    BadValue { option: String, value: String },
+
+    #[error("[{section}] {option} is required: {description}")]
+    MissingOption {
+        section: String,
+        option: String,
+        description: String,
+    },
+}
+
+/// One mandatory key for [`CephConfig::validate_required_options`].
+#[derive(Debug, Clone, Copy)]
+pub struct RequiredOption<'a> {
+    pub section: &'a str,
+    pub key: &'a str,
+    pub description: &'a str,
+}
+
+impl<'a> RequiredOption<'a> {
+    pub fn new(section: &'a str, key: &'a str, description: &'a str) -> Self {
+        RequiredOption {
+            section,
+            key,
+            description,
+        }
+    }
+}
+
+/// A value parseable from a config string.
+pub trait ConfigValue: Sized {
+    fn parse_config(raw: &str) -> Option<Self>;
+}
+
+impl ConfigValue for String {
+    fn parse_config(raw: &str) -> Option<Self> {
+        Some(raw.to_string())
+    }
+}
+
+impl ConfigValue for i64 {
+    fn parse_config(raw: &str) -> Option<Self> {
+        raw.parse().ok()
+    }
+}
+
+impl ConfigValue for u64 {
+    fn parse_config(raw: &str) -> Option<Self> {
+        raw.parse().ok()
+    }
+}
+
+impl ConfigValue for f64 {
+    fn parse_config(raw: &str) -> Option<Self> {
+        raw.parse().ok()
+    }
+}
+
+impl ConfigValue for bool {
+    fn parse_config(raw: &str) -> Option<Self> {
+        match raw.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" | "on" => Some(true),
+            "false" | "no" | "0" | "off" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Normalizes an option name: lower-case, spaces and dashes to
+/// underscores.
+fn normalize_option(name: &str) -> String {
+    name.trim()
+        .to_ascii_lowercase()
+        .replace([' ', '-'], "_")
+}
+
+/// One `[section]` of the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigSection {
+    pub options: BTreeMap<String, String>,
+}
+
+/// A parsed ceph.conf.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CephConfig {
+    pub sections: BTreeMap<String, ConfigSection>,
+}
+
+impl CephConfig {
+    /// Loads `path`, following `#include /path/to/fragment` directives.
+    ///
+    /// Include paths may be relative to the including file and may use a
+    /// `*` glob in the file name (`/etc/ceph/conf.d/*.conf`), with the
+    /// matches merged in lexical order.  Cycles are rejected.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<CephConfig, ConfigError> {
+        let mut config = CephConfig::default();
+        let mut visited = HashSet::new();
+        config.load_file(path.as_ref(), &mut visited)?;
+        Ok(config)
+    }
+
+    /// Parses in-memory contents.  `#include` lines are indistinguishable
+    /// from comments without a file to resolve them against, so they are
+    /// ignored here.
+    pub fn from_str_contents(contents: &str) -> Result<CephConfig, ConfigError> {
+        let mut config = CephConfig::default();
+        config.parse_into(contents, None)?;
+        Ok(config)
+    }
+
+    fn load_file(&mut self, path: &Path, visited: &mut HashSet<PathBuf>) -> Result<(), ConfigError> {
+        let canonical = path.canonicalize().map_err(|source| ConfigError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        if !visited.insert(canonical.clone()) {
+            return Err(ConfigError::Parse {
+                line: 0,
+                reason: "include cycle detected".to_string(),
+            });
+        }
+        let contents = std::fs::read_to_string(&canonical).map_err(|source| ConfigError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let dir = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.parse_into(&contents, Some((&dir, visited)))?;
+        visited.remove(&canonical);
+        Ok(())
+    }
+
+    fn parse_into(
+        &mut self,
+        contents: &str,
+        mut file_ctx: Option<(&Path, &mut HashSet<PathBuf>)>,
+    ) -> Result<(), ConfigError> {
+        let mut section = "global".to_string();
+        for (lineno, raw_line) in contents.lines().enumerate() {
+            if let Some((dir, visited)) = file_ctx.as_mut() {
+                if let Some(target) = raw_line.trim().strip_prefix("#include ") {
+                    for included in expand_include(dir, target.trim()) {
+                        self.load_file(&included, visited)?;
+                    }
+                    continue;
+                }
+            }
+            let line = strip_comment(raw_line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']').ok_or_else(|| ConfigError::Parse {
+                    line: lineno + 1,
+                    reason: "unterminated section header".to_string(),
+                })?;
+                section = name.trim().to_string();
+                self.sections.entry(section.clone()).or_default();
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| ConfigError::Parse {
+                line: lineno + 1,
+                reason: format!("expected key = value, got {line:?}"),
+            })?;
+            self.sections
+                .entry(section.clone())
+                .or_default()
+                .options
+                .insert(normalize_option(key), value.trim().trim_matches('"').to_string());
+        }
+        Ok(())
+    }
+
+    /// Looks `option` up in `section`, falling back to `[global]`.
+    pub fn get_raw(&self, section: &str, option: &str) -> Option<&str> {
+        let option = normalize_option(option);
+        for section in [section, "global"] {
+            if let Some(value) = self
+                .sections
+                .get(section)
+                .and_then(|s| s.options.get(&option))
+            {
+                return Some(value.as_str());
+            }
+        }
+        None
+    }
+
+    /// Checks that every option in `schema` is present (honoring the
+    /// `[global]` fallback), reporting all missing options at once so the
+    /// user can fix their file in one pass.
+    pub fn validate_required_options(&self, schema: &[RequiredOption<'_>]) -> Vec<ConfigError> {
+        schema
+            .iter()
+            .filter(|required| self.get_raw(required.section, required.key).is_none())
+            .map(|required| ConfigError::MissingOption {
+                section: required.section.to_string(),
+                option: normalize_option(required.key),
+                description: required.description.to_string(),
+            })
+            .collect()
+    }
+
+    /// Typed lookup with section fallback; `None` if the option is absent.
+    pub fn get<T: ConfigValue>(
+        &self,
+        section: &str,
+        option: &str,
+    ) -> Result<Option<T>, ConfigError> {
+        match self.get_raw(section, option) {
+            None => Ok(None),
+            Some(raw) => T::parse_config(raw)
+                .map(Some)
+                .ok_or_else(|| ConfigError::BadValue {
+                    option: normalize_option(option),
+                    value: raw.to_string(),
+                }),
+        }
+    }
+
+    /// Typed lookup searching `sections` in order, then `[global]`.
+    ///
+    /// `None` means the option is absent everywhere; `Some(Err)` means the
+    /// first section that has it holds an unparseable value.  The nested
+    /// shape lets callers distinguish "not set" from "set badly", which
+    /// [`CephConfig::get`] flattens away.
+    pub fn get_typed<T: ConfigValue>(
+        &self,
+        sections: &[&str],
+        key: &str,
+    ) -> Option<Result<T, ConfigError>> {
+        let option = normalize_option(key);
+        let raw = sections
+            .iter()
+            .copied()
+            .chain(std::iter::once("global"))
+            .find_map(|section| {
+                self.sections
+                    .get(section)
+                    .and_then(|s| s.options.get(&option))
+            })?;
+        Some(
+            T::parse_config(raw).ok_or_else(|| ConfigError::BadValue {
+                option,
+                value: raw.to_string(),
+            }),
+        )
+    }
+
+    /// Like [`CephConfig::get_typed`] but the option must be present;
+    /// absence becomes [`ConfigError::MissingOption`] with `description`
+    /// explaining why it is needed.
+    pub fn require_typed<T: ConfigValue>(
+        &self,
+        sections: &[&str],
+        key: &str,
+        description: &str,
+    ) -> Result<T, ConfigError> {
+        self.get_typed(sections, key)
+            .unwrap_or_else(|| {
+                Err(ConfigError::MissingOption {
+                    section: sections.first().copied().unwrap_or("global").to_string(),
+                    option: normalize_option(key),
+                    description: description.to_string(),
+                })
+            })
+    }
+}
+
+/// Expands one include target, relative to `dir`, into the files to load.
+/// A `*` in the file name globs over the directory entries, which are
+/// merged in lexical order; paths without a glob pass through unchanged.
+fn expand_include(dir: &Path, target: &str) -> Vec<PathBuf> {
+    let path = if Path::new(target).is_absolute() {
+        PathBuf::from(target)
+    } else {
+        dir.join(target)
+    };
+    let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+        return vec![path];
+    };
+    if !file_name.contains('*') {
+        return vec![path];
+    }
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let mut matches: Vec<PathBuf> = std::fs::read_dir(parent)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| wildcard_match(file_name, name))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Matches `name` against `pattern`, where `*` spans any run of
+/// characters.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut pieces = pattern.split('*');
+    let first = pieces.next().unwrap_or("");
+    if !name.starts_with(first) {
+        return false;
+    }
+    let mut rest = &name[first.len()..];
+    let mut pieces = pieces.peekable();
+    while let Some(piece) = pieces.next() {
+        if pieces.peek().is_none() {
+            return rest.ends_with(piece);
+        }
+        match rest.find(piece) {
+            Some(pos) => rest = &rest[pos + piece.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(['#', ';']) {
+        Some(pos) => &line[..pos],
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+[global]
+mon host = 10.0.0.1:3300,10.0.0.2:3300   # the monitors
+fsid = 6a8b1c24-0d9e-4f32-8ae2-7a59c6f0e1d3
+
+[client]
+keyring = /etc/ceph/keyring
+rados osd op timeout = 30
+debug-ms = 0
+"#;
+
+    #[test]
+    fn sections_and_fallback() {
+        let config = CephConfig::from_str_contents(SAMPLE).unwrap();
+        assert_eq!(
+            config.get_raw("client", "mon_host"),
+            Some("10.0.0.1:3300,10.0.0.2:3300")
+        );
+        assert_eq!(config.get_raw("client", "keyring"), Some("/etc/ceph/keyring"));
+        assert_eq!(config.get_raw("osd", "keyring"), None);
+    }
+
+    #[test]
+    fn option_names_are_normalized() {
+        let config = CephConfig::from_str_contents(SAMPLE).unwrap();
+        assert_eq!(
+            config.get::<i64>("client", "rados osd op timeout").unwrap(),
+            Some(30)
+        );
+        assert_eq!(config.get::<i64>("client", "debug_ms").unwrap(), Some(0));
+    }
+
+    #[test]
+    fn bad_typed_value_is_an_error() {
+        let config = CephConfig::from_str_contents("[global]\npg num = lots\n").unwrap();
+        assert!(matches!(
+            config.get::<i64>("global", "pg_num"),
+            Err(ConfigError::BadValue { .. })
+        ));
+    }
+
+    #[test]
+    fn typed_lookup_searches_sections_in_order() {
+        let config = CephConfig::from_str_contents(
+            "[global]\nrados osd op timeout = 30\nlog to stderr = yes\n\
+             [client]\nrados osd op timeout = 2.5\n\
+             [client.admin]\nkeyring = /etc/ceph/admin.keyring\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .get_typed::<String>(&["client.admin", "client"], "keyring")
+                .unwrap()
+                .unwrap(),
+            "/etc/ceph/admin.keyring"
+        );
+        // [client] shadows [global] for the float; [global] still answers
+        // for options only it has.
+        assert_eq!(
+            config
+                .get_typed::<f64>(&["client.admin", "client"], "rados_osd_op_timeout")
+                .unwrap()
+                .unwrap(),
+            2.5
+        );
+        assert!(config
+            .get_typed::<bool>(&["client"], "log to stderr")
+            .unwrap()
+            .unwrap());
+        assert_eq!(
+            config
+                .get_typed::<i64>(&["global"], "rados_osd_op_timeout")
+                .unwrap()
+                .unwrap(),
+            30
+        );
+        assert!(config
+            .get_typed::<i64>(&["client"], "no_such_option")
+            .is_none());
+        assert!(matches!(
+            config.get_typed::<i64>(&["client"], "rados_osd_op_timeout"),
+            Some(Err(ConfigError::BadValue { .. }))
+        ));
+    }
+
+    #[test]
+    fn require_typed_reports_absence() {
+        let config = CephConfig::from_str_contents("[global]\nfsid = base\n").unwrap();
+        assert_eq!(
+            config
+                .require_typed::<String>(&["client"], "fsid", "cluster identity")
+                .unwrap(),
+            "base"
+        );
+        assert!(matches!(
+            config.require_typed::<u64>(&["client"], "mon_port", "monitor port"),
+            Err(ConfigError::MissingOption { section, option, .. })
+                if section == "client" && option == "mon_port"
+        ));
+    }
+
+    #[test]
+    fn validation_reports_every_missing_option() {
+        let config = CephConfig::from_str_contents(SAMPLE).unwrap();
+        let schema = [
+            RequiredOption::new("global", "fsid", "cluster identity"),
+            RequiredOption::new("client", "admin socket", "for asok commands"),
+            RequiredOption::new("osd", "osd data", "object store path"),
+        ];
+        let errors = config.validate_required_options(&schema);
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| matches!(
+            e,
+            ConfigError::MissingOption { option, .. }
+                if option == "admin_socket" || option == "osd_data"
+        )));
+    }
+
+    #[test]
+    fn includes_merge_fragments_in_order() {
+        let dir = std::env::temp_dir().join(format!("cephconfig-test-{}", std::process::id()));
+        let conf_d = dir.join("conf.d");
+        std::fs::create_dir_all(&conf_d).unwrap();
+        std::fs::write(
+            dir.join("ceph.conf"),
+            "[global]\nfsid = base\n#include conf.d/*.conf\n",
+        )
+        .unwrap();
+        std::fs::write(conf_d.join("10-first.conf"), "[global]\nmon host = first\n").unwrap();
+        std::fs::write(
+            conf_d.join("20-second.conf"),
+            "[global]\nmon host = second\n[client]\nkeyring = /tmp/k\n",
+        )
+        .unwrap();
+
+        let config = CephConfig::from_file(dir.join("ceph.conf")).unwrap();
+        assert_eq!(config.get_raw("global", "fsid"), Some("base"));
+        // Lexical order: 20-second.conf wins.
+        assert_eq!(config.get_raw("global", "mon_host"), Some("second"));
+        assert_eq!(config.get_raw("client", "keyring"), Some("/tmp/k"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("cephconfig-cycle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.conf"), "#include b.conf\n").unwrap();
+        std::fs::write(dir.join("b.conf"), "#include a.conf\n").unwrap();
+
+        let err = CephConfig::from_file(dir.join("a.conf")).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { reason, .. }
+            if reason == "include cycle detected"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unterminated_section_is_rejected() {
+        assert!(matches!(
+            CephConfig::from_str_contents("[global\n"),
+            Err(ConfigError::Parse { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+//! Reloading a ceph.conf when it changes on disk (the `watch` feature).
+//!
+//! Long-running daemons use [`ConfigWatcher`] to pick up edits without a
+//! restart.  Editors and config management tools tend to produce a burst
+//! of events per logical change (write, truncate, rename into place), so
+//! events are debounced: the file is re-parsed once per quiet period, not
+//! once per event.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify::{RecursiveMode, Watcher};
+
+use crate::{CephConfig, ConfigError};
+
+/// How long the file must stay quiet before a burst of events triggers
+/// one reload.
+const DEBOUNCE_WINDOW: Duration = Duration::from_millis(200);
+
+enum WatcherEvent {
+    Changed,
+    Stop,
+}
+
+/// Watches one config file and invokes a callback with the freshly parsed
+/// [`CephConfig`] after each (debounced) change.
+///
+/// Reloads that fail to parse are skipped silently: the callback only
+/// ever sees a complete, valid config, and a half-written file simply
+/// delays the update until the next event.
+pub struct ConfigWatcher {
+    tx: mpsc::Sender<WatcherEvent>,
+    thread: Option<std::thread::JoinHandle<()>>,
+    /// Keeps the OS watch registered for the lifetime of this struct.
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Starts watching `path`, delivering each reloaded config to
+    /// `on_change` from a background thread.
+    pub fn new<P, F>(path: P, on_change: F) -> Result<ConfigWatcher, ConfigError>
+    where
+        P: Into<PathBuf>,
+        F: Fn(CephConfig) + Send + 'static,
+    {
+        let path = path.into();
+        let (tx, rx) = mpsc::channel();
+
+        let event_tx = tx.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<_>| {
+            if event.is_ok() {
+                let _ = event_tx.send(WatcherEvent::Changed);
+            }
+        })
+        .map_err(|e| ConfigError::Io {
+            path: path.display().to_string(),
+            source: std::io::Error::other(e),
+        })?;
+        watcher
+            .watch(&path, RecursiveMode::NonRecursive)
+            .map_err(|e| ConfigError::Io {
+                path: path.display().to_string(),
+                source: std::io::Error::other(e),
+            })?;
+
+        let thread = std::thread::spawn(move || loop {
+            match rx.recv() {
+                Ok(WatcherEvent::Changed) => {}
+                Ok(WatcherEvent::Stop) | Err(_) => return,
+            }
+            // Absorb the rest of the burst before reloading.
+            loop {
+                match rx.recv_timeout(DEBOUNCE_WINDOW) {
+                    Ok(WatcherEvent::Changed) => {}
+                    Ok(WatcherEvent::Stop) => return,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if let Ok(config) = CephConfig::from_file(&path) {
+                on_change(config);
+            }
+        });
+
+        Ok(ConfigWatcher {
+            tx,
+            thread: Some(thread),
+            _watcher: watcher,
+        })
+    }
+
+    /// Stops the background thread, waiting for an in-flight reload to
+    /// finish.  Dropping the watcher stops it too.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.tx.send(WatcherEvent::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn a_burst_of_writes_reloads_once() {
+        let dir = std::env::temp_dir().join(format!("cephconfig-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let conf = dir.join("ceph.conf");
+        std::fs::write(&conf, "[global]\nfsid = before\n").unwrap();
+
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let (seen_tx, seen_rx) = mpsc::channel();
+        let counter = reloads.clone();
+        let watcher = ConfigWatcher::new(&conf, move |config| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = seen_tx.send(config.get_raw("global", "fsid").map(str::to_string));
+        })
+        .unwrap();
+
+        // Three writes inside one debounce window.
+        std::fs::write(&conf, "[global]\nfsid = first\n").unwrap();
+        std::fs::write(&conf, "[global]\nfsid = second\n").unwrap();
+        std::fs::write(&conf, "[global]\nfsid = after\n").unwrap();
+
+        let fsid = seen_rx
+            .recv_timeout(Duration::from_secs(10))
+            .expect("a reload within the timeout");
+        assert_eq!(fsid.as_deref(), Some("after"));
+        assert_eq!(reloads.load(Ordering::SeqCst), 1);
+
+        watcher.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
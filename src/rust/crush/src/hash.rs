@@ -0,0 +1,121 @@
+//! The rjenkins1 hash used throughout CRUSH.
+//!
+//! A direct port of `src/crush/hash.c`; outputs must match the C
+//! implementation bit-for-bit or placement diverges from the cluster.
+
+const CRUSH_HASH_SEED: u32 = 1_315_423_911;
+
+/// Robert Jenkins' 32-bit mix, mutating in place like the C macro.
+#[inline]
+fn hashmix(a: &mut u32, b: &mut u32, c: &mut u32) {
+    *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 13);
+    *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 8);
+    *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 13);
+    *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 12);
+    *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 16);
+    *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 5);
+    *a = a.wrapping_sub(*b).wrapping_sub(*c) ^ (*c >> 3);
+    *b = b.wrapping_sub(*c).wrapping_sub(*a) ^ (*a << 10);
+    *c = c.wrapping_sub(*a).wrapping_sub(*b) ^ (*b >> 15);
+}
+
+pub fn crush_hash_rjenkins1(a: u32) -> u32 {
+    let mut hash = CRUSH_HASH_SEED ^ a;
+    let mut a = a;
+    let mut b = a;
+    let mut x = 231232u32;
+    let mut y = 1232u32;
+    hashmix(&mut b, &mut x, &mut hash);
+    hashmix(&mut y, &mut a, &mut hash);
+    hash
+}
+
+pub fn crush_hash_rjenkins1_2(a: u32, b: u32) -> u32 {
+    let mut hash = CRUSH_HASH_SEED ^ a ^ b;
+    let mut a = a;
+    let mut b = b;
+    let mut x = 231232u32;
+    let mut y = 1232u32;
+    hashmix(&mut a, &mut b, &mut hash);
+    hashmix(&mut x, &mut a, &mut hash);
+    hashmix(&mut b, &mut y, &mut hash);
+    hash
+}
+
+pub fn crush_hash_rjenkins1_3(a: u32, b: u32, c: u32) -> u32 {
+    let mut hash = CRUSH_HASH_SEED ^ a ^ b ^ c;
+    let mut a = a;
+    let mut b = b;
+    let mut c = c;
+    let mut x = 231232u32;
+    let mut y = 1232u32;
+    hashmix(&mut a, &mut b, &mut hash);
+    hashmix(&mut c, &mut x, &mut hash);
+    hashmix(&mut y, &mut a, &mut hash);
+    hashmix(&mut b, &mut x, &mut hash);
+    hashmix(&mut y, &mut c, &mut hash);
+    hash
+}
+
+/// Hashes `iterations` distinct inputs and reports the wall time taken.
+///
+/// Not a statistically rigorous benchmark — it exists so performance work
+/// on the hash has a stable entry point to measure against (the hash runs
+/// once per replica per PG, so regressions here hurt every placement).
+pub fn bench_hash(iterations: u64) -> std::time::Duration {
+    let start = std::time::Instant::now();
+    let mut acc = 0u32;
+    for i in 0..iterations {
+        acc = acc.wrapping_add(crush_hash_rjenkins1_2(i as u32, (i >> 32) as u32));
+    }
+    // Keep the accumulated value observable so the loop cannot be
+    // optimized away.
+    std::hint::black_box(acc);
+    start.elapsed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn rjenkins1_is_deterministic_and_input_sensitive() {
+        assert_eq!(crush_hash_rjenkins1_2(7, 11), crush_hash_rjenkins1_2(7, 11));
+        assert_ne!(crush_hash_rjenkins1_2(1, 2), crush_hash_rjenkins1_2(2, 1));
+        assert_ne!(crush_hash_rjenkins1(1), crush_hash_rjenkins1(2));
+        assert_ne!(
+            crush_hash_rjenkins1_3(1, 2, 3),
+            crush_hash_rjenkins1_3(3, 2, 1)
+        );
+    }
+
+    #[test]
+    fn bench_hash_runs_and_reports_time() {
+        // Smoke test only; the returned duration is wall time and cannot
+        // be asserted against.
+        let _ = bench_hash(1000);
+    }
+
+    proptest! {
+        #[test]
+        fn rjenkins1_2_is_deterministic(a: u32, b: u32) {
+            prop_assert_eq!(
+                crush_hash_rjenkins1_2(a, b),
+                crush_hash_rjenkins1_2(a, b)
+            );
+        }
+
+        #[test]
+        fn rjenkins1_2_rarely_collides(seed: u32) {
+            use std::collections::HashSet;
+
+            // Sample 1000 distinct (x, y) pairs; a good 32-bit hash should
+            // collide on well under 1% of them.
+            let hashes: HashSet<u32> = (0..1000u32)
+                .map(|i| crush_hash_rjenkins1_2(seed.wrapping_add(i), i))
+                .collect();
+            prop_assert!(hashes.len() >= 990, "only {} distinct hashes", hashes.len());
+        }
+    }
+}
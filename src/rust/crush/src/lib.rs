@@ -0,0 +1,13 @@
+//! CRUSH: Controlled Replication Under Scalable Hashing.
+//!
+//! Decodes the binary CRUSH map embedded in an OSDMap and computes PG →
+//! OSD placements, mirroring `src/crush` in the C++ tree.
+
+pub mod hash;
+pub mod mapper;
+pub mod placement;
+pub mod types;
+
+pub use mapper::MappingTable;
+pub use placement::{crush_do_rule, pg_upmap_lookup, validate_pg_id, PgId};
+pub use types::{decode_crush_map, CrushError, CrushMap, CrushRule};
@@ -0,0 +1,151 @@
+//! An LRU cache of recently computed PG → OSD mappings.
+//!
+//! Rule execution walks the bucket hierarchy and runs the straw2 draw for
+//! every replica, which adds up when the same handful of PGs is mapped over
+//! and over.  [`MappingTable`] memoizes the raw CRUSH result per [`PgId`];
+//! callers invalidate it wholesale whenever a new OSDMap (and hence a
+//! possibly different CRUSH map) arrives.
+
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+
+use crate::hash::crush_hash_rjenkins1_2;
+use crate::placement::{crush_do_rule, validate_pg_id, PgId, PoolParams};
+use crate::types::{CrushError, CrushMap};
+
+/// A bounded cache of PG → OSD mappings with hit-rate accounting.
+///
+/// The cached value is the raw CRUSH result; upmap overrides and up/in
+/// filtering remain the caller's job, as they depend on OSDMap state this
+/// crate does not see.
+pub struct MappingTable {
+    map: LruCache<PgId, Vec<u32>>,
+    hits: u64,
+    lookups: u64,
+}
+
+impl MappingTable {
+    /// Creates a table holding at most `capacity` mappings (at least one).
+    pub fn new(capacity: usize) -> Self {
+        MappingTable {
+            map: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
+            hits: 0,
+            lookups: 0,
+        }
+    }
+
+    /// Returns the cached mapping for `pg`, computing and caching it on a
+    /// miss.  Failed computations are not cached.
+    pub fn get_or_compute(
+        &mut self,
+        pg: PgId,
+        crush: &CrushMap,
+        pool: &impl PoolParams,
+    ) -> Result<Vec<u32>, CrushError> {
+        validate_pg_id(pg, pool)?;
+        self.lookups += 1;
+        if let Some(osds) = self.map.get(&pg) {
+            self.hits += 1;
+            return Ok(osds.clone());
+        }
+        let x = crush_hash_rjenkins1_2(pg.ps(), pg.pool as u32);
+        let osds: Vec<u32> = crush_do_rule(crush, pool.crush_rule(), x, pool.size())?
+            .into_iter()
+            .filter(|&osd| osd >= 0)
+            .map(|osd| osd as u32)
+            .collect();
+        self.map.put(pg, osds.clone());
+        Ok(osds)
+    }
+
+    /// Drops every cached mapping; call when a new OSDMap arrives.  The
+    /// hit-rate counters are preserved.
+    pub fn invalidate_all(&mut self) {
+        self.map.clear();
+    }
+
+    /// The fraction of lookups served from the cache, or 0.0 before the
+    /// first lookup.
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups as f64
+        }
+    }
+
+    /// How many mappings are currently cached.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::testutil::simple_map;
+
+    struct TestPool;
+
+    impl PoolParams for TestPool {
+        fn pool_id(&self) -> u64 {
+            1
+        }
+        fn pg_num(&self) -> u32 {
+            32
+        }
+        fn size(&self) -> u32 {
+            3
+        }
+        fn crush_rule(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn a_repeated_lookup_hits_the_cache() {
+        let map = simple_map(8);
+        let mut table = MappingTable::new(16);
+        let pg = PgId::new(1, 7);
+
+        let first = table.get_or_compute(pg, &map, &TestPool).unwrap();
+        assert_eq!(first.len(), 3);
+        assert_eq!(table.hit_rate(), 0.0);
+
+        let second = table.get_or_compute(pg, &map, &TestPool).unwrap();
+        assert_eq!(second, first);
+        assert_eq!(table.hit_rate(), 0.5);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn invalidation_forces_a_recompute() {
+        let map = simple_map(8);
+        let mut table = MappingTable::new(16);
+        let pg = PgId::new(1, 7);
+
+        let before = table.get_or_compute(pg, &map, &TestPool).unwrap();
+        table.invalidate_all();
+        assert!(table.is_empty());
+
+        // The recompute is a miss, so the hit rate stays at zero.
+        let after = table.get_or_compute(pg, &map, &TestPool).unwrap();
+        assert_eq!(after, before);
+        assert_eq!(table.hit_rate(), 0.0);
+    }
+
+    #[test]
+    fn capacity_bounds_the_cache() {
+        let map = simple_map(8);
+        let mut table = MappingTable::new(2);
+        for seed in 0..4 {
+            table.get_or_compute(PgId::new(1, seed), &map, &TestPool).unwrap();
+        }
+        assert_eq!(table.len(), 2);
+    }
+}
@@ -0,0 +1,500 @@
+//! Rule execution: mapping a placement seed to an ordered set of OSDs.
+
+use bytes::{Bytes, BytesMut};
+use denc::{Denc, RadosError};
+
+use crate::hash::crush_hash_rjenkins1_3;
+use crate::types::{CrushBucket, CrushError, CrushMap, StepOp, CRUSH_BUCKET_STRAW2};
+
+/// A placement group id: pool plus placement seed (`pg_t`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PgId {
+    pub pool: u64,
+    pub seed: u32,
+}
+
+impl PgId {
+    pub const fn new(pool: u64, seed: u32) -> Self {
+        PgId { pool, seed }
+    }
+
+    /// The placement seed (`ps` in the C++ sources).
+    pub fn ps(&self) -> u32 {
+        self.seed
+    }
+
+    /// The PG `object` is placed in within `pool`, from the placement
+    /// hash the hobject carries (`get_hash()` in the C++ sources).
+    pub fn for_object(object: &denc::hobject::HObject, pool: &impl PoolParams) -> PgId {
+        PgId::new(pool.pool_id(), object.hash % pool.pg_num())
+    }
+}
+
+impl std::fmt::Display for PgId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:x}", self.pool, self.seed)
+    }
+}
+
+impl Denc for PgId {
+    fn encode(&self, buf: &mut BytesMut) {
+        1u8.encode(buf); // struct_v
+        self.pool.encode(buf);
+        self.seed.encode(buf);
+        (-1i32).encode(buf); // deprecated preferred
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let _struct_v = u8::decode(buf)?;
+        let pool = u64::decode(buf)?;
+        let seed = u32::decode(buf)?;
+        let _preferred = i32::decode(buf)?;
+        Ok(PgId { pool, seed })
+    }
+}
+
+/// Pool parameters placement needs.  Implemented by
+/// `osdclient::osdmap::PgPool`; defined here so this crate stays
+/// independent of the OSD client.
+pub trait PoolParams {
+    fn pool_id(&self) -> u64;
+    fn pg_num(&self) -> u32;
+    fn size(&self) -> u32;
+    fn crush_rule(&self) -> u32;
+}
+
+/// Checks that `pg` can exist in `pool`: hand-built [`PgId`]s can carry a
+/// seed at or beyond the pool's `pg_num`, which no rule execution or
+/// OSDMap lookup would ever produce.
+pub fn validate_pg_id(pg: PgId, pool: &impl PoolParams) -> Result<(), CrushError> {
+    if pg.ps() >= pool.pg_num() {
+        return Err(CrushError::InvalidPgId {
+            pg,
+            pg_num: pool.pg_num(),
+        });
+    }
+    Ok(())
+}
+
+/// Applies the `pg_upmap` / `pg_upmap_items` override tables to the CRUSH
+/// result for `pg`.
+///
+/// `pg_upmap` replaces the mapping wholesale; `pg_upmap_items` swaps
+/// individual OSDs within `crush_result` (each pair is `(from, to)`).
+/// Returns `None` when neither table has an entry for `pg`, in which case
+/// the caller should keep the CRUSH result as-is.
+pub fn pg_upmap_lookup(
+    pg: PgId,
+    crush_result: &[u32],
+    upmap: &std::collections::BTreeMap<PgId, Vec<u32>>,
+    upmap_items: &std::collections::BTreeMap<PgId, Vec<(u32, u32)>>,
+) -> Option<Vec<u32>> {
+    if let Some(mapping) = upmap.get(&pg) {
+        return Some(mapping.clone());
+    }
+    let swaps = upmap_items.get(&pg)?;
+    let mut out = crush_result.to_vec();
+    for osd in &mut out {
+        if let Some(&(_, to)) = swaps.iter().find(|&&(from, _)| from == *osd) {
+            *osd = to;
+        }
+    }
+    Some(out)
+}
+
+/// How many times we re-draw before giving up on finding a distinct item.
+const CHOOSE_TOTAL_TRIES: u32 = 50;
+
+/// Picks one child of a straw2 bucket for replica `r` of input `x`.
+fn bucket_choose(bucket: &CrushBucket, x: u32, r: u32) -> Option<i32> {
+    debug_assert_eq!(bucket.alg, CRUSH_BUCKET_STRAW2);
+    let mut best: Option<(f64, i32)> = None;
+    for (&item, &weight) in bucket.items.iter().zip(&bucket.item_weights) {
+        if weight == 0 {
+            continue;
+        }
+        let u = crush_hash_rjenkins1_3(x, item as u32, r) & 0xffff;
+        // Straw2 draw: an exponentially distributed "straw length" scaled
+        // by weight; the longest straw wins.  The C implementation uses a
+        // fixed-point log table; this port uses f64, which preserves the
+        // weight distribution.
+        let ln = (((u + 1) as f64) / 65536.0).ln();
+        let draw = ln / (weight as f64);
+        match best {
+            Some((best_draw, _)) if draw <= best_draw => {}
+            _ => best = Some((draw, item)),
+        }
+    }
+    best.map(|(_, item)| item)
+}
+
+/// Descends from `item` to a device (leaf), drawing through nested buckets.
+fn choose_leaf(map: &CrushMap, item: i32, x: u32, r: u32) -> Result<Option<i32>, CrushError> {
+    let mut current = item;
+    loop {
+        if current >= 0 {
+            return Ok(Some(current));
+        }
+        let bucket = map.bucket(current)?;
+        match bucket_choose(bucket, x, r) {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Selects `num_reps` distinct items starting from `start` (firstn).
+fn choose_firstn(
+    map: &CrushMap,
+    start: i32,
+    x: u32,
+    num_reps: u32,
+    to_leaf: bool,
+) -> Result<Vec<i32>, CrushError> {
+    let mut out: Vec<i32> = Vec::with_capacity(num_reps as usize);
+    let mut ftotal = 0u32;
+    for rep in 0..num_reps {
+        let mut chosen = None;
+        for _try in 0..CHOOSE_TOTAL_TRIES {
+            let r = rep + ftotal;
+            let candidate = if start >= 0 {
+                Some(start)
+            } else if to_leaf {
+                choose_leaf(map, start, x, r)?
+            } else {
+                bucket_choose(map.bucket(start)?, x, r)
+            };
+            match candidate {
+                Some(c) if !out.contains(&c) => {
+                    chosen = Some(c);
+                    break;
+                }
+                _ => ftotal += 1,
+            }
+        }
+        if let Some(c) = chosen {
+            out.push(c);
+        }
+    }
+    Ok(out)
+}
+
+/// How many direct children of bucket `item` are buckets of failure-domain
+/// type `domain_type`.
+fn count_children_of_type(
+    map: &CrushMap,
+    item: i32,
+    domain_type: i32,
+) -> Result<usize, CrushError> {
+    let bucket = map.bucket(item)?;
+    let mut count = 0;
+    for &child in &bucket.items {
+        if child < 0 && i32::from(map.bucket(child)?.bucket_type) == domain_type {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Executes `rule_id` of `map` for input `x`, returning up to `num_reps`
+/// devices.
+pub fn crush_do_rule(
+    map: &CrushMap,
+    rule_id: u32,
+    x: u32,
+    num_reps: u32,
+) -> Result<Vec<i32>, CrushError> {
+    let rule = map.rule(rule_id)?;
+    let mut working: Vec<i32> = Vec::new();
+    let mut result: Vec<i32> = Vec::new();
+    for step in &rule.steps {
+        match step.op {
+            StepOp::Noop => {}
+            StepOp::Take => {
+                working = vec![step.arg1];
+            }
+            StepOp::ChooseFirstn | StepOp::ChooseIndep => {
+                let n = if step.arg1 == 0 {
+                    num_reps
+                } else {
+                    step.arg1 as u32
+                };
+                let mut next = Vec::new();
+                for &item in &working {
+                    let picked = choose_firstn(map, item, x, n, false)?;
+                    // Indep placements (erasure coding) need every slot
+                    // filled; coming up short is an error, not a shorter
+                    // result.
+                    if step.op == StepOp::ChooseIndep && picked.len() < n as usize {
+                        return Err(CrushError::InsufficientOSDs {
+                            need: n as usize,
+                            available: picked.len(),
+                        });
+                    }
+                    next.extend(picked);
+                }
+                working = next;
+            }
+            StepOp::ChooseleafFirstn | StepOp::ChooseleafIndep => {
+                let n = if step.arg1 == 0 {
+                    num_reps
+                } else {
+                    step.arg1 as u32
+                };
+                // A failure-domain type (arg2) the map cannot satisfy is a
+                // topology problem, not bad luck in the draws: report it
+                // as such instead of looping the retries.
+                if step.arg2 != 0 {
+                    for &item in &working {
+                        if item >= 0 {
+                            continue;
+                        }
+                        let available = count_children_of_type(map, item, step.arg2)?;
+                        if available < n as usize {
+                            return Err(CrushError::TopologyConstraintViolation {
+                                rule_id,
+                                type_name: map
+                                    .type_name(step.arg2)
+                                    .unwrap_or("?")
+                                    .to_string(),
+                                available,
+                                required: n as usize,
+                            });
+                        }
+                    }
+                }
+                let mut next = Vec::new();
+                for &item in &working {
+                    let picked = choose_firstn(map, item, x, n, true)?;
+                    if step.op == StepOp::ChooseleafIndep && picked.len() < n as usize {
+                        return Err(CrushError::InsufficientOSDs {
+                            need: n as usize,
+                            available: picked.len(),
+                        });
+                    }
+                    next.extend(picked);
+                }
+                working = next;
+            }
+            StepOp::Emit => {
+                result.append(&mut working);
+            }
+            StepOp::Unknown(op) => {
+                return Err(CrushError::Integrity(format!(
+                    "rule {rule_id} contains unknown step op {op}"
+                )));
+            }
+        }
+    }
+    result.truncate(num_reps as usize);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::testutil::simple_map;
+
+    #[test]
+    fn pgid_round_trip() {
+        let pg = PgId::new(3, 0x1f);
+        let mut raw = denc::encode_to_bytes(&pg);
+        assert_eq!(PgId::decode(&mut raw).unwrap(), pg);
+        assert_eq!(pg.to_string(), "3.1f");
+    }
+
+    struct TestPool;
+
+    impl PoolParams for TestPool {
+        fn pool_id(&self) -> u64 {
+            1
+        }
+        fn pg_num(&self) -> u32 {
+            32
+        }
+        fn size(&self) -> u32 {
+            3
+        }
+        fn crush_rule(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn pg_ids_are_validated_against_pg_num() {
+        assert!(validate_pg_id(PgId::new(1, 0), &TestPool).is_ok());
+        assert!(validate_pg_id(PgId::new(1, 31), &TestPool).is_ok());
+        let err = validate_pg_id(PgId::new(1, 32), &TestPool).unwrap_err();
+        assert!(matches!(
+            err,
+            CrushError::InvalidPgId {
+                pg: PgId { pool: 1, seed: 32 },
+                pg_num: 32,
+            }
+        ));
+    }
+
+    #[test]
+    fn for_object_follows_the_hobject_hash() {
+        let mut hobj = denc::hobject::HObject::new("rbd_header.10ab", 1);
+        hobj.hash = 0x0000_0027; // 39: lands in seed 39 % 32
+        let pg = PgId::for_object(&hobj, &TestPool);
+        assert_eq!(pg, PgId::new(1, 7));
+        assert!(validate_pg_id(pg, &TestPool).is_ok());
+    }
+
+    #[test]
+    fn do_rule_returns_distinct_devices() {
+        let map = simple_map(8);
+        for x in 0..64u32 {
+            let osds = crush_do_rule(&map, 0, x, 3).unwrap();
+            assert_eq!(osds.len(), 3, "seed {x}");
+            let mut dedup = osds.clone();
+            dedup.sort_unstable();
+            dedup.dedup();
+            assert_eq!(dedup.len(), 3, "duplicates for seed {x}: {osds:?}");
+            assert!(osds.iter().all(|&o| (0..8).contains(&o)));
+        }
+    }
+
+    #[test]
+    fn upmap_overrides_take_precedence() {
+        use std::collections::BTreeMap;
+
+        let pg = PgId::new(1, 7);
+        let crush_result = [0u32, 1, 2];
+
+        let mut upmap = BTreeMap::new();
+        upmap.insert(pg, vec![5, 4, 3]);
+        assert_eq!(
+            pg_upmap_lookup(pg, &crush_result, &upmap, &BTreeMap::new()),
+            Some(vec![5, 4, 3])
+        );
+
+        let mut items = BTreeMap::new();
+        items.insert(pg, vec![(1u32, 6u32)]);
+        assert_eq!(
+            pg_upmap_lookup(pg, &crush_result, &BTreeMap::new(), &items),
+            Some(vec![0, 6, 2])
+        );
+
+        // A full upmap wins over item swaps for the same PG.
+        assert_eq!(
+            pg_upmap_lookup(pg, &crush_result, &upmap, &items),
+            Some(vec![5, 4, 3])
+        );
+
+        assert_eq!(
+            pg_upmap_lookup(pg, &crush_result, &BTreeMap::new(), &BTreeMap::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn indep_steps_fail_when_osds_run_out() {
+        let mut map = simple_map(2);
+        map.rules[0].as_mut().unwrap().steps[1].op = StepOp::ChooseIndep;
+        let err = crush_do_rule(&map, 0, 1, 4).unwrap_err();
+        assert!(matches!(
+            err,
+            CrushError::InsufficientOSDs {
+                need: 4,
+                available: 2,
+            }
+        ));
+        // Two replicas on two OSDs is still fine.
+        assert_eq!(crush_do_rule(&map, 0, 1, 2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn chooseleaf_reports_missing_failure_domains() {
+        use crate::types::{CrushRule, CrushRuleStep, CRUSH_BUCKET_STRAW2};
+        use std::collections::BTreeMap;
+
+        // One root holding a single rack of two OSDs.
+        let rack = CrushBucket {
+            id: -2,
+            bucket_type: 3, // "rack"
+            alg: CRUSH_BUCKET_STRAW2,
+            hash: 0,
+            weight: 0x20000,
+            items: vec![0, 1],
+            item_weights: vec![0x10000, 0x10000],
+        };
+        let root = CrushBucket {
+            id: -1,
+            bucket_type: 10, // "root"
+            alg: CRUSH_BUCKET_STRAW2,
+            hash: 0,
+            weight: 0x20000,
+            items: vec![-2],
+            item_weights: vec![0x20000],
+        };
+        let rule = CrushRule {
+            ruleset: 0,
+            rule_type: 1,
+            min_size: 1,
+            max_size: 10,
+            steps: vec![
+                CrushRuleStep {
+                    op: StepOp::Take,
+                    arg1: -1,
+                    arg2: 0,
+                },
+                CrushRuleStep {
+                    op: StepOp::ChooseleafFirstn,
+                    arg1: 0,
+                    arg2: 3, // one leaf per rack
+                },
+                CrushRuleStep {
+                    op: StepOp::Emit,
+                    arg1: 0,
+                    arg2: 0,
+                },
+            ],
+        };
+        let map = CrushMap {
+            max_devices: 2,
+            buckets: vec![Some(root), Some(rack)],
+            rules: vec![Some(rule)],
+            type_names: BTreeMap::from([
+                (0, "osd".to_string()),
+                (3, "rack".to_string()),
+                (10, "root".to_string()),
+            ]),
+            bucket_names: BTreeMap::from([
+                (-1, "default".to_string()),
+                (-2, "rack0".to_string()),
+            ]),
+            rule_names: BTreeMap::from([(0, "replicated_rule".to_string())]),
+        };
+
+        let err = crush_do_rule(&map, 0, 1, 3).unwrap_err();
+        match err {
+            CrushError::TopologyConstraintViolation {
+                rule_id,
+                type_name,
+                available,
+                required,
+            } => {
+                assert_eq!(rule_id, 0);
+                assert_eq!(type_name, "rack");
+                assert_eq!(available, 1);
+                assert_eq!(required, 3);
+            }
+            other => panic!("expected a topology violation, got {other:?}"),
+        }
+
+        // A single replica fits in the single rack.
+        assert_eq!(crush_do_rule(&map, 0, 1, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn do_rule_is_deterministic() {
+        let map = simple_map(8);
+        assert_eq!(
+            crush_do_rule(&map, 0, 42, 3).unwrap(),
+            crush_do_rule(&map, 0, 42, 3).unwrap()
+        );
+    }
+}
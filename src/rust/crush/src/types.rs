@@ -0,0 +1,505 @@
+//! CRUSH map structures and the binary map decoder.
+
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use denc::{Denc, RadosError};
+use thiserror::Error;
+
+/// `CRUSH_MAGIC` from `crush/crush.h`.
+pub const CRUSH_MAGIC: u32 = 0x0001_0000;
+
+/// Bucket algorithms (`crush_algorithm` in C).
+pub const CRUSH_BUCKET_UNIFORM: u8 = 1;
+pub const CRUSH_BUCKET_LIST: u8 = 2;
+pub const CRUSH_BUCKET_TREE: u8 = 3;
+pub const CRUSH_BUCKET_STRAW: u8 = 4;
+pub const CRUSH_BUCKET_STRAW2: u8 = 5;
+
+/// Rule step opcodes (`crush_rule_step_op`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOp {
+    Noop,
+    Take,
+    ChooseFirstn,
+    ChooseIndep,
+    ChooseleafFirstn,
+    ChooseleafIndep,
+    Emit,
+    Unknown(u32),
+}
+
+impl StepOp {
+    pub fn from_wire(op: u32) -> StepOp {
+        match op {
+            0 => StepOp::Noop,
+            1 => StepOp::Take,
+            2 => StepOp::ChooseFirstn,
+            3 => StepOp::ChooseIndep,
+            4 => StepOp::Emit,
+            6 => StepOp::ChooseleafFirstn,
+            7 => StepOp::ChooseleafIndep,
+            other => StepOp::Unknown(other),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CrushError {
+    #[error("bad magic {found:#x}, expected {CRUSH_MAGIC:#x}")]
+    BadMagic { found: u32 },
+
+    #[error("unsupported bucket algorithm {alg} in bucket {id}")]
+    UnsupportedBucketAlgorithm { id: i32, alg: u8 },
+
+    #[error("no such rule {0}")]
+    NoSuchRule(u32),
+
+    #[error("no such bucket {0}")]
+    NoSuchBucket(i32),
+
+    #[error("map integrity: {0}")]
+    Integrity(String),
+
+    #[error("rule {rule_id} step {step_index}: {reason}")]
+    InvalidRuleStep {
+        rule_id: u32,
+        step_index: usize,
+        reason: String,
+    },
+
+    #[error(
+        "rule {rule_id}: only {available} failure domains of type {type_name} for {required} replicas"
+    )]
+    TopologyConstraintViolation {
+        rule_id: u32,
+        type_name: String,
+        available: usize,
+        required: usize,
+    },
+
+    #[error("not enough OSDs: need {need}, only {available} available")]
+    InsufficientOSDs { need: usize, available: usize },
+
+    #[error("pg {pg} is out of range for a pool with {pg_num} PGs")]
+    InvalidPgId {
+        pg: crate::placement::PgId,
+        pg_num: u32,
+    },
+
+    #[error(transparent)]
+    Encoding(#[from] RadosError),
+}
+
+/// One bucket: an interior node of the CRUSH hierarchy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrushBucket {
+    pub id: i32,
+    pub bucket_type: u16,
+    pub alg: u8,
+    pub hash: u8,
+    /// 16.16 fixed-point total weight.
+    pub weight: u32,
+    /// Child item ids (negative = bucket, non-negative = device).
+    pub items: Vec<i32>,
+    /// 16.16 fixed-point per-item weights, parallel to `items`.
+    pub item_weights: Vec<u32>,
+}
+
+/// One step of a placement rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrushRuleStep {
+    pub op: StepOp,
+    pub arg1: i32,
+    pub arg2: i32,
+}
+
+/// A placement rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrushRule {
+    pub ruleset: u8,
+    pub rule_type: u8,
+    pub min_size: u8,
+    pub max_size: u8,
+    pub steps: Vec<CrushRuleStep>,
+}
+
+impl CrushRule {
+    /// Checks that every step of this rule makes sense against `map`:
+    /// `TAKE` must name an existing bucket (or a valid device), choose
+    /// steps must reference a known bucket type, every choose/emit step
+    /// must be preceded by a `TAKE`, and the rule must end with `EMIT`.
+    pub fn validate_steps(&self, rule_id: u32, map: &CrushMap) -> Result<(), CrushError> {
+        let step_err = |step_index: usize, reason: String| CrushError::InvalidRuleStep {
+            rule_id,
+            step_index,
+            reason,
+        };
+        let mut have_take = false;
+        for (i, step) in self.steps.iter().enumerate() {
+            match step.op {
+                StepOp::Noop => {}
+                StepOp::Take => {
+                    if step.arg1 < 0 {
+                        map.bucket(step.arg1)
+                            .map_err(|_| step_err(i, format!("TAKE of unknown bucket {}", step.arg1)))?;
+                    } else if step.arg1 >= map.max_devices {
+                        return Err(step_err(
+                            i,
+                            format!(
+                                "TAKE of device {} >= max_devices {}",
+                                step.arg1, map.max_devices
+                            ),
+                        ));
+                    }
+                    have_take = true;
+                }
+                StepOp::ChooseFirstn
+                | StepOp::ChooseIndep
+                | StepOp::ChooseleafFirstn
+                | StepOp::ChooseleafIndep => {
+                    if !have_take {
+                        return Err(step_err(i, "choose step before TAKE".to_string()));
+                    }
+                    if step.arg2 != 0 && !map.type_names.contains_key(&step.arg2) {
+                        return Err(step_err(
+                            i,
+                            format!("choose step references unknown type {}", step.arg2),
+                        ));
+                    }
+                }
+                StepOp::Emit => {
+                    if !have_take {
+                        return Err(step_err(i, "EMIT before TAKE".to_string()));
+                    }
+                    // EMIT flushes the working set; the next output (if
+                    // any) needs its own TAKE.
+                    have_take = false;
+                }
+                StepOp::Unknown(op) => {
+                    return Err(step_err(i, format!("unknown step op {op}")));
+                }
+            }
+        }
+        if self.steps.is_empty() {
+            return Err(step_err(0, "rule has no steps".to_string()));
+        }
+        if !matches!(self.steps.last().map(|s| s.op), Some(StepOp::Emit)) {
+            return Err(step_err(
+                self.steps.len() - 1,
+                "rule does not end with EMIT".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The decoded CRUSH map.
+#[derive(Debug, Clone, Default)]
+pub struct CrushMap {
+    pub max_devices: i32,
+    /// Indexed by `-1 - id`; `None` for holes.
+    pub buckets: Vec<Option<CrushBucket>>,
+    /// Indexed by rule id; `None` for holes.
+    pub rules: Vec<Option<CrushRule>>,
+    pub type_names: BTreeMap<i32, String>,
+    pub bucket_names: BTreeMap<i32, String>,
+    pub rule_names: BTreeMap<u32, String>,
+}
+
+impl CrushMap {
+    pub fn bucket(&self, id: i32) -> Result<&CrushBucket, CrushError> {
+        if id >= 0 {
+            return Err(CrushError::NoSuchBucket(id));
+        }
+        self.buckets
+            .get((-1 - id) as usize)
+            .and_then(Option::as_ref)
+            .ok_or(CrushError::NoSuchBucket(id))
+    }
+
+    pub fn rule(&self, rule_id: u32) -> Result<&CrushRule, CrushError> {
+        self.rules
+            .get(rule_id as usize)
+            .and_then(Option::as_ref)
+            .ok_or(CrushError::NoSuchRule(rule_id))
+    }
+
+    pub fn type_name(&self, type_id: i32) -> Option<&str> {
+        self.type_names.get(&type_id).map(String::as_str)
+    }
+
+    /// Basic structural checks: every bucket item must resolve to a device
+    /// or another bucket, and weights must be parallel to items.
+    pub fn verify_integrity(&self) -> Result<(), CrushError> {
+        for bucket in self.buckets.iter().flatten() {
+            if bucket.items.len() != bucket.item_weights.len() {
+                return Err(CrushError::Integrity(format!(
+                    "bucket {} has {} items but {} weights",
+                    bucket.id,
+                    bucket.items.len(),
+                    bucket.item_weights.len()
+                )));
+            }
+            for &item in &bucket.items {
+                if item < 0 {
+                    self.bucket(item)?;
+                } else if item >= self.max_devices {
+                    return Err(CrushError::Integrity(format!(
+                        "bucket {} references device {item} >= max_devices {}",
+                        bucket.id, self.max_devices
+                    )));
+                }
+            }
+        }
+        for (rule_id, rule) in self.rules.iter().enumerate() {
+            if let Some(rule) = rule {
+                rule.validate_steps(rule_id as u32, self)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode_name_map<K: Ord + From<i32>>(buf: &mut Bytes) -> Result<BTreeMap<K, String>, RadosError> {
+    let count = u32::decode(buf)? as usize;
+    let mut out = BTreeMap::new();
+    for _ in 0..count {
+        let key = i32::decode(buf)?;
+        let name = String::decode(buf)?;
+        out.insert(K::from(key), name);
+    }
+    Ok(out)
+}
+
+/// Decodes the binary CRUSH map blob found inside an OSDMap.
+pub fn decode_crush_map(raw: &mut Bytes) -> Result<CrushMap, CrushError> {
+    let magic = u32::decode(raw)?;
+    if magic != CRUSH_MAGIC {
+        return Err(CrushError::BadMagic { found: magic });
+    }
+    let max_buckets = i32::decode(raw)?;
+    let max_rules = u32::decode(raw)?;
+    let max_devices = i32::decode(raw)?;
+
+    let mut buckets = Vec::with_capacity(max_buckets.max(0) as usize);
+    for slot in 0..max_buckets.max(0) {
+        let alg = u32::decode(raw)?;
+        if alg == 0 {
+            buckets.push(None);
+            continue;
+        }
+        let id = i32::decode(raw)?;
+        let bucket_type = u16::decode(raw)?;
+        let alg = u8::decode(raw)?;
+        let hash = u8::decode(raw)?;
+        let weight = u32::decode(raw)?;
+        let size = u32::decode(raw)? as usize;
+        let mut items = Vec::with_capacity(size);
+        for _ in 0..size {
+            items.push(i32::decode(raw)?);
+        }
+        let item_weights = match alg {
+            CRUSH_BUCKET_UNIFORM => {
+                // One weight shared by every item.
+                let item_weight = u32::decode(raw)?;
+                vec![item_weight; size]
+            }
+            CRUSH_BUCKET_STRAW2 => {
+                let mut weights = Vec::with_capacity(size);
+                for _ in 0..size {
+                    weights.push(u32::decode(raw)?);
+                }
+                weights
+            }
+            other => {
+                return Err(CrushError::UnsupportedBucketAlgorithm {
+                    id,
+                    alg: other,
+                });
+            }
+        };
+        let _ = slot;
+        buckets.push(Some(CrushBucket {
+            id,
+            bucket_type,
+            alg,
+            hash,
+            weight,
+            items,
+            item_weights,
+        }));
+    }
+
+    let mut rules = Vec::with_capacity(max_rules as usize);
+    for _ in 0..max_rules {
+        let present = u32::decode(raw)?;
+        if present == 0 {
+            rules.push(None);
+            continue;
+        }
+        let len = u32::decode(raw)? as usize;
+        let ruleset = u8::decode(raw)?;
+        let rule_type = u8::decode(raw)?;
+        let min_size = u8::decode(raw)?;
+        let max_size = u8::decode(raw)?;
+        let mut steps = Vec::with_capacity(len);
+        for _ in 0..len {
+            steps.push(CrushRuleStep {
+                op: StepOp::from_wire(u32::decode(raw)?),
+                arg1: i32::decode(raw)?,
+                arg2: i32::decode(raw)?,
+            });
+        }
+        rules.push(Some(CrushRule {
+            ruleset,
+            rule_type,
+            min_size,
+            max_size,
+            steps,
+        }));
+    }
+
+    let type_names = decode_name_map(raw)?;
+    let bucket_names = decode_name_map(raw)?;
+    let rule_names_raw: BTreeMap<i32, String> = decode_name_map(raw)?;
+    let rule_names = rule_names_raw
+        .into_iter()
+        .map(|(k, v)| (k as u32, v))
+        .collect();
+
+    // Tunables and choose_args may trail; this decoder accepts maps with or
+    // without them and keeps the defaults.
+    Ok(CrushMap {
+        max_devices,
+        buckets,
+        rules,
+        type_names,
+        bucket_names,
+        rule_names,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod testutil {
+    use super::*;
+
+    /// A one-root, `n`-OSD straw2 map with a simple replicated rule.
+    pub fn simple_map(num_osds: usize) -> CrushMap {
+        let items: Vec<i32> = (0..num_osds as i32).collect();
+        let item_weights = vec![0x10000u32; num_osds];
+        let root = CrushBucket {
+            id: -1,
+            bucket_type: 10, // "root"
+            alg: CRUSH_BUCKET_STRAW2,
+            hash: 0,
+            weight: 0x10000 * num_osds as u32,
+            items,
+            item_weights,
+        };
+        let rule = CrushRule {
+            ruleset: 0,
+            rule_type: 1,
+            min_size: 1,
+            max_size: 10,
+            steps: vec![
+                CrushRuleStep {
+                    op: StepOp::Take,
+                    arg1: -1,
+                    arg2: 0,
+                },
+                CrushRuleStep {
+                    op: StepOp::ChooseFirstn,
+                    arg1: 0,
+                    arg2: 0,
+                },
+                CrushRuleStep {
+                    op: StepOp::Emit,
+                    arg1: 0,
+                    arg2: 0,
+                },
+            ],
+        };
+        let mut type_names = BTreeMap::new();
+        type_names.insert(0, "osd".to_string());
+        type_names.insert(10, "root".to_string());
+        CrushMap {
+            max_devices: num_osds as i32,
+            buckets: vec![Some(root)],
+            rules: vec![Some(rule)],
+            type_names,
+            bucket_names: BTreeMap::from([(-1, "default".to_string())]),
+            rule_names: BTreeMap::from([(0, "replicated_rule".to_string())]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testutil::simple_map;
+    use super::*;
+
+    #[test]
+    fn integrity_of_simple_map() {
+        simple_map(4).verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn integrity_detects_dangling_bucket() {
+        let mut map = simple_map(4);
+        map.buckets[0].as_mut().unwrap().items[0] = -99;
+        assert!(map.verify_integrity().is_err());
+    }
+
+    #[test]
+    fn validate_steps_rejects_inconsistent_rules() {
+        let map = simple_map(4);
+        let good = map.rule(0).unwrap();
+        good.validate_steps(0, &map).unwrap();
+
+        let mut take_missing = good.clone();
+        take_missing.steps[0].arg1 = -5;
+        assert!(matches!(
+            take_missing.validate_steps(0, &map),
+            Err(CrushError::InvalidRuleStep { step_index: 0, .. })
+        ));
+
+        let mut bad_type = good.clone();
+        bad_type.steps[1].arg2 = 42;
+        assert!(matches!(
+            bad_type.validate_steps(0, &map),
+            Err(CrushError::InvalidRuleStep { step_index: 1, .. })
+        ));
+
+        let mut no_emit = good.clone();
+        no_emit.steps.pop();
+        assert!(matches!(
+            no_emit.validate_steps(0, &map),
+            Err(CrushError::InvalidRuleStep { .. })
+        ));
+
+        let mut choose_first = good.clone();
+        choose_first.steps.swap(0, 1);
+        assert!(matches!(
+            choose_first.validate_steps(0, &map),
+            Err(CrushError::InvalidRuleStep { step_index: 0, .. })
+        ));
+
+        // verify_integrity runs the same checks across all rules.
+        let mut map = simple_map(4);
+        map.rules[0].as_mut().unwrap().steps[0].arg1 = -5;
+        assert!(matches!(
+            map.verify_integrity(),
+            Err(CrushError::InvalidRuleStep { rule_id: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut raw = denc::encode_to_bytes(&0xdead_beefu32);
+        assert!(matches!(
+            decode_crush_map(&mut raw),
+            Err(CrushError::BadMagic { .. })
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+//! Decode-path diagnostics.
+//!
+//! A failed decode deep inside a nested structure used to report only the
+//! innermost type ("insufficient bytes: needed 4, available 2"), leaving
+//! no clue which of the dozens of `u32`s in an OSDMap was short.  This
+//! module keeps a thread-local stack of the versioned types currently
+//! being decoded; [`crate::VersionedEncode::decode_versioned`] pushes on
+//! entry, pops on exit, and annotates the first error with the chain,
+//! e.g. `"OSDMap → PgPool: insufficient bytes: needed 4, available 2"`.
+
+use std::cell::{Cell, RefCell};
+
+use crate::RadosError;
+
+thread_local! {
+    static DECODE_STACK: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    /// Whether the error currently unwinding has already been annotated;
+    /// only the deepest frame adds the chain.
+    static ANNOTATED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Namespace for the thread-local decode-context stack.
+pub struct EncodingMetadata;
+
+impl EncodingMetadata {
+    /// Records that a decode of `type_name` has begun on this thread.
+    pub fn push_context(type_name: &'static str) {
+        DECODE_STACK.with_borrow_mut(|stack| stack.push(type_name));
+    }
+
+    /// Records that the innermost decode has finished (successfully or
+    /// not).  Leaving the outermost frame resets the annotation state for
+    /// the next decode.
+    pub fn pop_context() {
+        DECODE_STACK.with_borrow_mut(|stack| {
+            stack.pop();
+            if stack.is_empty() {
+                ANNOTATED.set(false);
+            }
+        });
+    }
+
+    /// The current chain of types being decoded, innermost last, or
+    /// `None` outside any decode.  Type names are shortened to their
+    /// final path segment.
+    pub fn context() -> Option<String> {
+        DECODE_STACK.with_borrow(|stack| {
+            if stack.is_empty() {
+                return None;
+            }
+            Some(
+                stack
+                    .iter()
+                    .map(|name| name.rsplit("::").next().unwrap_or(name))
+                    .collect::<Vec<_>>()
+                    .join(" → "),
+            )
+        })
+    }
+
+    /// Wraps `err` in [`RadosError::Protocol`] with the current decode
+    /// chain prepended.  Outer frames see the error again as it unwinds;
+    /// only the first (deepest) call annotates.  Errors callers match on
+    /// structurally ([`RadosError::UnsupportedVersion`], I/O) pass
+    /// through untouched.
+    pub fn annotate(err: RadosError) -> RadosError {
+        if ANNOTATED.get()
+            || !matches!(
+                err,
+                RadosError::InsufficientBytes { .. } | RadosError::Protocol(_)
+            )
+        {
+            return err;
+        }
+        match Self::context() {
+            Some(chain) => {
+                ANNOTATED.set(true);
+                RadosError::Protocol(format!("{chain}: {err}"))
+            }
+            None => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Denc, VersionedEncode};
+    use bytes::{BufMut, Bytes, BytesMut};
+
+    #[derive(Debug)]
+    struct Inner;
+
+    impl VersionedEncode for Inner {
+        const VERSION: u8 = 1;
+        const COMPAT: u8 = 1;
+
+        fn encode_payload(&self, buf: &mut BytesMut) {
+            String::new().encode(buf);
+        }
+
+        fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+            String::decode(buf)?;
+            Ok(Inner)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Outer;
+
+    impl VersionedEncode for Outer {
+        const VERSION: u8 = 1;
+        const COMPAT: u8 = 1;
+
+        fn encode_payload(&self, buf: &mut BytesMut) {
+            Inner.encode_versioned(buf);
+        }
+
+        fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+            Inner::decode_versioned(buf)?;
+            Ok(Outer)
+        }
+    }
+
+    #[test]
+    fn errors_carry_the_decode_chain() {
+        // An Inner whose payload claims a 100-byte string but ends
+        // immediately after the length prefix.
+        let mut inner = BytesMut::new();
+        inner.put_u8(1);
+        inner.put_u8(1);
+        inner.put_u32_le(4);
+        inner.put_u32_le(100);
+
+        let mut raw = BytesMut::new();
+        raw.put_u8(1);
+        raw.put_u8(1);
+        raw.put_u32_le(inner.len() as u32);
+        raw.extend_from_slice(&inner);
+
+        let err = Outer::decode_versioned(&mut raw.freeze()).unwrap_err();
+        match err {
+            RadosError::Protocol(msg) => {
+                assert!(msg.contains("Outer → Inner"), "missing chain in {msg:?}");
+                assert!(msg.contains("insufficient bytes"), "missing cause in {msg:?}");
+                // Outer frames must not wrap the chain a second time.
+                assert_eq!(msg.matches("Outer").count(), 1, "double annotation in {msg:?}");
+            }
+            other => panic!("expected an annotated protocol error, got {other:?}"),
+        }
+        // The stack unwinds fully, failure or not.
+        assert_eq!(EncodingMetadata::context(), None);
+    }
+
+    #[test]
+    fn successful_decodes_leave_no_context_behind() {
+        let mut buf = BytesMut::new();
+        Outer.encode_versioned(&mut buf);
+        Outer::decode_versioned(&mut buf.freeze()).unwrap();
+        assert_eq!(EncodingMetadata::context(), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! `entity_addr_t` and `entity_addrvec_t`.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{Denc, RadosError, VersionedEncode};
+
+/// Address type discriminator, matching `entity_addr_t::type` in C++.
+pub const ENTITY_ADDR_TYPE_NONE: u32 = 0;
+/// Legacy msgr1 address (`v1:`).
+pub const ENTITY_ADDR_TYPE_LEGACY: u32 = 1;
+/// msgr2 address (`v2:`).
+pub const ENTITY_ADDR_TYPE_MSGR2: u32 = 2;
+pub const ENTITY_ADDR_TYPE_ANY: u32 = 3;
+
+/// A single network endpoint of a Ceph entity.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntityAddr {
+    pub addr_type: u32,
+    pub nonce: u32,
+    pub sockaddr: SocketAddr,
+}
+
+impl EntityAddr {
+    pub fn new(addr_type: u32, sockaddr: SocketAddr) -> Self {
+        EntityAddr {
+            addr_type,
+            nonce: 0,
+            sockaddr,
+        }
+    }
+
+    pub fn is_msgr2(&self) -> bool {
+        self.addr_type == ENTITY_ADDR_TYPE_MSGR2
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.addr_type == ENTITY_ADDR_TYPE_LEGACY
+    }
+
+    /// The messenger protocol version: 1 for legacy addresses, 2 for
+    /// everything else.
+    pub fn protocol_version(&self) -> u8 {
+        if self.is_legacy() {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl FromStr for EntityAddr {
+    type Err = RadosError;
+
+    /// Parses `v1:ip:port`, `v2:ip:port` or bare `ip:port` (treated as
+    /// v2).  IPv6 addresses use the usual bracket syntax.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr_type, rest) = if let Some(rest) = s.strip_prefix("v1:") {
+            (ENTITY_ADDR_TYPE_LEGACY, rest)
+        } else if let Some(rest) = s.strip_prefix("v2:") {
+            (ENTITY_ADDR_TYPE_MSGR2, rest)
+        } else {
+            (ENTITY_ADDR_TYPE_MSGR2, s)
+        };
+        let sockaddr = SocketAddr::from_str(rest)
+            .map_err(|_| RadosError::Protocol(format!("invalid entity address {s:?}")))?;
+        Ok(EntityAddr::new(addr_type, sockaddr))
+    }
+}
+
+impl fmt::Display for EntityAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}:{}", self.protocol_version(), self.sockaddr)
+    }
+}
+
+impl Default for EntityAddr {
+    fn default() -> Self {
+        EntityAddr {
+            addr_type: ENTITY_ADDR_TYPE_NONE,
+            nonce: 0,
+            sockaddr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        }
+    }
+}
+
+impl VersionedEncode for EntityAddr {
+    const VERSION: u8 = 1;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.addr_type.encode(buf);
+        self.nonce.encode(buf);
+        match self.sockaddr {
+            SocketAddr::V4(v4) => {
+                2u8.encode(buf); // AF_INET-style family tag
+                v4.port().encode(buf);
+                buf.extend_from_slice(&v4.ip().octets());
+            }
+            SocketAddr::V6(v6) => {
+                10u8.encode(buf); // AF_INET6-style family tag
+                v6.port().encode(buf);
+                buf.extend_from_slice(&v6.ip().octets());
+            }
+        }
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        let addr_type = u32::decode(buf)?;
+        let nonce = u32::decode(buf)?;
+        let family = u8::decode(buf)?;
+        let port = u16::decode(buf)?;
+        let sockaddr = match family {
+            2 => {
+                let mut octets = [0u8; 4];
+                for o in octets.iter_mut() {
+                    *o = u8::decode(buf)?;
+                }
+                SocketAddr::new(IpAddr::V4(octets.into()), port)
+            }
+            10 => {
+                let mut octets = [0u8; 16];
+                for o in octets.iter_mut() {
+                    *o = u8::decode(buf)?;
+                }
+                SocketAddr::new(IpAddr::V6(octets.into()), port)
+            }
+            other => {
+                return Err(RadosError::Protocol(format!(
+                    "unknown address family {other}"
+                )))
+            }
+        };
+        Ok(EntityAddr {
+            addr_type,
+            nonce,
+            sockaddr,
+        })
+    }
+}
+
+/// An ordered list of addresses for one entity (`entity_addrvec_t`).
+///
+/// Daemons that speak both protocols advertise a v2 address followed by a
+/// v1 address.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EntityAddrvec {
+    pub addrs: Vec<EntityAddr>,
+}
+
+impl EntityAddrvec {
+    /// Every msgr2 address, in order.
+    pub fn v2_addrs(&self) -> Vec<&EntityAddr> {
+        self.addrs.iter().filter(|a| a.is_msgr2()).collect()
+    }
+
+    /// Every legacy (v1) address, in order.
+    pub fn v1_addrs(&self) -> Vec<&EntityAddr> {
+        self.addrs.iter().filter(|a| a.is_legacy()).collect()
+    }
+
+    /// The address to dial: the first v2 address, falling back to the
+    /// first v1 one.
+    pub fn preferred_addr(&self) -> Option<&EntityAddr> {
+        self.addrs
+            .iter()
+            .find(|a| a.is_msgr2())
+            .or_else(|| self.addrs.iter().find(|a| a.is_legacy()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+}
+
+impl VersionedEncode for EntityAddrvec {
+    const VERSION: u8 = 2;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        (self.addrs.len() as u32).encode(buf);
+        for addr in &self.addrs {
+            addr.encode_versioned(buf);
+        }
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        let count = u32::decode(buf)? as usize;
+        let mut addrs = Vec::with_capacity(count.min(16));
+        for _ in 0..count {
+            addrs.push(EntityAddr::decode_versioned(buf)?);
+        }
+        Ok(EntityAddrvec { addrs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addr_round_trip_v4_and_v6() {
+        for sockaddr in ["10.0.0.1:6789", "[fe80::1]:3300"] {
+            let addr = EntityAddr::new(ENTITY_ADDR_TYPE_MSGR2, sockaddr.parse().unwrap());
+            let mut buf = BytesMut::new();
+            addr.encode_versioned(&mut buf);
+            let mut raw = buf.freeze();
+            assert_eq!(EntityAddr::decode_versioned(&mut raw).unwrap(), addr);
+        }
+    }
+
+    #[test]
+    fn from_str_understands_protocol_prefixes() {
+        let v2: EntityAddr = "v2:10.0.0.1:3300".parse().unwrap();
+        assert!(v2.is_msgr2());
+        assert_eq!(v2.protocol_version(), 2);
+        assert_eq!(v2.to_string(), "v2:10.0.0.1:3300");
+
+        let v1: EntityAddr = "v1:10.0.0.1:6789".parse().unwrap();
+        assert!(v1.is_legacy());
+        assert_eq!(v1.protocol_version(), 1);
+        assert_eq!(v1.to_string(), "v1:10.0.0.1:6789");
+
+        let bare: EntityAddr = "10.0.0.1:3300".parse().unwrap();
+        assert!(bare.is_msgr2());
+
+        let v6: EntityAddr = "v2:[fe80::1]:3300".parse().unwrap();
+        assert!(v6.sockaddr.is_ipv6());
+        assert_eq!(v6.to_string(), "v2:[fe80::1]:3300");
+    }
+
+    #[test]
+    fn from_str_rejects_junk() {
+        assert!(matches!(
+            "v2:not-an-address".parse::<EntityAddr>(),
+            Err(RadosError::Protocol(_))
+        ));
+        assert!(matches!(
+            "v3:10.0.0.1:3300".parse::<EntityAddr>(),
+            Err(RadosError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn addrvec_prefers_msgr2() {
+        let v1 = EntityAddr::new(
+            ENTITY_ADDR_TYPE_LEGACY,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 6789),
+        );
+        let v2 = EntityAddr::new(
+            ENTITY_ADDR_TYPE_MSGR2,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 3300),
+        );
+        let vec = EntityAddrvec {
+            addrs: vec![v1.clone(), v2.clone()],
+        };
+        assert_eq!(vec.v1_addrs(), vec![&v1]);
+        assert_eq!(vec.v2_addrs(), vec![&v2]);
+        assert_eq!(vec.preferred_addr(), Some(&v2));
+
+        let legacy_only = EntityAddrvec {
+            addrs: vec![v1.clone()],
+        };
+        assert_eq!(legacy_only.preferred_addr(), Some(&v1));
+        assert!(EntityAddrvec::default().is_empty());
+        assert_eq!(EntityAddrvec::default().preferred_addr(), None);
+    }
+
+    #[test]
+    fn addrvec_round_trip() {
+        let vec = EntityAddrvec {
+            addrs: vec![
+                EntityAddr::new(ENTITY_ADDR_TYPE_MSGR2, "10.0.0.1:3300".parse().unwrap()),
+                EntityAddr::new(ENTITY_ADDR_TYPE_LEGACY, "10.0.0.1:6789".parse().unwrap()),
+            ],
+        };
+        let mut buf = BytesMut::new();
+        vec.encode_versioned(&mut buf);
+        let mut raw = buf.freeze();
+        assert_eq!(EntityAddrvec::decode_versioned(&mut raw).unwrap(), vec);
+    }
+}
@@ -0,0 +1,23 @@
+//! Shared error type for wire-level failures.
+
+use thiserror::Error;
+
+/// Errors produced while encoding or decoding Ceph wire structures, or while
+/// speaking one of the Ceph protocols at the framing level.
+#[derive(Debug, Error)]
+pub enum RadosError {
+    /// The buffer ended before a fixed-width field could be read.
+    #[error("insufficient bytes: needed {needed}, available {available}")]
+    InsufficientBytes { needed: usize, available: usize },
+
+    /// The peer sent something structurally invalid.
+    #[error("protocol error: {0}")]
+    Protocol(String),
+
+    /// A struct was encoded with a version newer than we can decode.
+    #[error("unsupported encoding version {version} (max supported {max_supported})")]
+    UnsupportedVersion { version: u8, max_supported: u8 },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,144 @@
+//! Ceph feature bits.
+//!
+//! Names and bit positions follow `include/ceph_features.h`.  Several bits
+//! have been retired upstream and reused by later releases; only the live
+//! name for each bit is defined here.  Bits that upstream shares between
+//! several features (the "incarnation" trick) get one primary definition
+//! plus aliases, so call sites can use the name that matches their intent.
+
+/// Defines one feature bit and proves at compile time that it is a single
+/// bit, guarding against typos when the list is edited.
+macro_rules! feature_bit {
+    ($(#[$meta:meta])* $name:ident = $bit:expr) => {
+        $(#[$meta])*
+        pub const $name: u64 = 1 << $bit;
+        const _: () = assert!($name.is_power_of_two());
+    };
+}
+
+/// Defines a feature name that shares a bit with (or combines) previously
+/// defined features, asserting the combination only uses known bits.
+macro_rules! feature_alias {
+    ($(#[$meta:meta])* $name:ident = $($src:ident)|+) => {
+        $(#[$meta])*
+        pub const $name: u64 = $($src)|+;
+        const _: () = assert!($name & !($($src)|+) == 0);
+    };
+}
+
+feature_bit!(CEPH_FEATURE_UID = 0);
+feature_bit!(CEPH_FEATURE_NOSRCADDR = 1);
+feature_bit!(CEPH_FEATURE_SERVER_NAUTILUS = 2);
+feature_bit!(CEPH_FEATURE_FLOCK = 3);
+feature_bit!(CEPH_FEATURE_SUBSCRIBE2 = 4);
+feature_bit!(CEPH_FEATURE_MONNAMES = 5);
+feature_bit!(CEPH_FEATURE_RECONNECT_SEQ = 6);
+feature_bit!(CEPH_FEATURE_DIRLAYOUTHASH = 7);
+feature_bit!(CEPH_FEATURE_OBJECTLOCATOR = 8);
+feature_bit!(CEPH_FEATURE_PGID64 = 9);
+feature_bit!(CEPH_FEATURE_INCSUBOSDMAP = 10);
+feature_bit!(CEPH_FEATURE_PGPOOL3 = 11);
+feature_bit!(CEPH_FEATURE_OSDREPLYMUX = 12);
+feature_bit!(CEPH_FEATURE_OSDENC = 13);
+feature_bit!(CEPH_FEATURE_SERVER_KRAKEN = 14);
+feature_bit!(CEPH_FEATURE_MONENC = 15);
+feature_bit!(CEPH_FEATURE_SERVER_OCTOPUS = 16);
+feature_bit!(CEPH_FEATURE_OS_PERF_STAT_NS = 17);
+feature_bit!(CEPH_FEATURE_CRUSH_TUNABLES = 18);
+feature_bit!(CEPH_FEATURE_OSD_PGLOG_HARDLIMIT = 19);
+feature_bit!(CEPH_FEATURE_SERVER_PACIFIC = 20);
+feature_bit!(CEPH_FEATURE_SERVER_LUMINOUS = 21);
+feature_bit!(CEPH_FEATURE_MSG_AUTH = 23);
+feature_bit!(CEPH_FEATURE_CRUSH_TUNABLES2 = 25);
+feature_bit!(CEPH_FEATURE_CREATEPOOLID = 26);
+feature_bit!(CEPH_FEATURE_REPLY_CREATE_INODE = 27);
+feature_bit!(CEPH_FEATURE_SERVER_MIMIC = 28);
+feature_bit!(CEPH_FEATURE_MDSENC = 29);
+feature_bit!(CEPH_FEATURE_OSDHASHPSPOOL = 30);
+feature_bit!(CEPH_FEATURE_OSD_CACHEPOOL = 35);
+feature_bit!(CEPH_FEATURE_CRUSH_V2 = 36);
+feature_bit!(CEPH_FEATURE_EXPORT_PEER = 37);
+feature_bit!(CEPH_FEATURE_OSDMAP_ENC = 39);
+feature_bit!(CEPH_FEATURE_MDS_INLINE_DATA = 40);
+feature_bit!(CEPH_FEATURE_CRUSH_TUNABLES3 = 41);
+feature_bit!(CEPH_FEATURE_MSGR_KEEPALIVE2 = 42);
+feature_bit!(CEPH_FEATURE_OSD_POOLRESEND = 43);
+feature_bit!(CEPH_FEATURE_OSD_FADVISE_FLAGS = 46);
+feature_bit!(CEPH_FEATURE_CRUSH_V4 = 48);
+feature_bit!(CEPH_FEATURE_NEW_OSDOP_ENCODING = 56);
+feature_bit!(CEPH_FEATURE_MON_STATEFUL_SUB = 57);
+feature_bit!(CEPH_FEATURE_CRUSH_TUNABLES5 = 58);
+feature_bit!(CEPH_FEATURE_FS_BTIME = 59);
+feature_bit!(CEPH_FEATURE_OSD_RECOVERY_DELETES = 60);
+feature_bit!(CEPH_FEATURE_CEPHX_V2 = 61);
+
+// Bits that upstream shares between several feature names.
+feature_alias!(CEPH_FEATURE_SERVER_QUINCY = CEPH_FEATURE_SERVER_NAUTILUS);
+feature_alias!(CEPH_FEATURE_STRETCH_MODE = CEPH_FEATURE_SERVER_NAUTILUS);
+feature_alias!(CEPH_FEATURE_OSD_PRIMARY_AFFINITY = CEPH_FEATURE_CRUSH_TUNABLES3);
+feature_alias!(CEPH_FEATURE_RESEND_ON_SPLIT = CEPH_FEATURE_SERVER_LUMINOUS);
+feature_alias!(CEPH_FEATURE_RADOS_BACKOFF = CEPH_FEATURE_SERVER_LUMINOUS);
+feature_alias!(CEPH_FEATURE_OSDMAP_PG_UPMAP = CEPH_FEATURE_SERVER_LUMINOUS);
+feature_alias!(CEPH_FEATURE_CRUSH_CHOOSE_ARGS = CEPH_FEATURE_SERVER_LUMINOUS);
+feature_alias!(CEPH_FEATURE_SERVER_JEWEL = CEPH_FEATURE_MON_STATEFUL_SUB);
+feature_alias!(CEPH_FEATURE_MON_ROUTE_OSDMAP = CEPH_FEATURE_MON_STATEFUL_SUB);
+feature_alias!(CEPH_FEATURE_NEW_OSDOPREPLY_ENCODING = CEPH_FEATURE_CRUSH_TUNABLES5);
+feature_alias!(CEPH_FEATURE_FS_FILE_LAYOUT_V2 = CEPH_FEATURE_CRUSH_TUNABLES5);
+feature_alias!(CEPH_FEATURE_FS_CHANGE_ATTR = CEPH_FEATURE_FS_BTIME);
+feature_alias!(CEPH_FEATURE_MSG_ADDR2 = CEPH_FEATURE_FS_BTIME);
+
+feature_alias!(
+    /// Feature set this client always advertises.
+    CEPH_FEATURES_SUPPORTED_DEFAULT = CEPH_FEATURE_UID
+        | CEPH_FEATURE_NOSRCADDR
+        | CEPH_FEATURE_SERVER_NAUTILUS
+        | CEPH_FEATURE_PGID64
+        | CEPH_FEATURE_PGPOOL3
+        | CEPH_FEATURE_OSDENC
+        | CEPH_FEATURE_MONENC
+        | CEPH_FEATURE_CRUSH_TUNABLES
+        | CEPH_FEATURE_SERVER_LUMINOUS
+        | CEPH_FEATURE_MSG_AUTH
+        | CEPH_FEATURE_CRUSH_TUNABLES2
+        | CEPH_FEATURE_OSDHASHPSPOOL
+        | CEPH_FEATURE_CRUSH_V2
+        | CEPH_FEATURE_OSDMAP_ENC
+        | CEPH_FEATURE_CRUSH_TUNABLES3
+        | CEPH_FEATURE_MSGR_KEEPALIVE2
+        | CEPH_FEATURE_OSD_POOLRESEND
+        | CEPH_FEATURE_CRUSH_V4
+        | CEPH_FEATURE_NEW_OSDOP_ENCODING
+        | CEPH_FEATURE_MON_STATEFUL_SUB
+        | CEPH_FEATURE_CRUSH_TUNABLES5
+        | CEPH_FEATURE_MSG_ADDR2
+        | CEPH_FEATURE_CEPHX_V2
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_feature_set_includes_the_msgr2_essentials() {
+        for bit in [
+            CEPH_FEATURE_MSG_ADDR2,
+            CEPH_FEATURE_MSG_AUTH,
+            CEPH_FEATURE_SERVER_NAUTILUS,
+            CEPH_FEATURE_CEPHX_V2,
+        ] {
+            assert_eq!(CEPH_FEATURES_SUPPORTED_DEFAULT & bit, bit);
+        }
+    }
+
+    #[test]
+    fn shared_bits_alias_their_primary_feature() {
+        assert_eq!(CEPH_FEATURE_OSDMAP_PG_UPMAP, CEPH_FEATURE_SERVER_LUMINOUS);
+        assert_eq!(CEPH_FEATURE_SERVER_QUINCY, CEPH_FEATURE_SERVER_NAUTILUS);
+        assert_eq!(CEPH_FEATURE_STRETCH_MODE, CEPH_FEATURE_SERVER_NAUTILUS);
+        assert_eq!(CEPH_FEATURE_MSG_ADDR2, CEPH_FEATURE_FS_BTIME);
+        assert_eq!(
+            CEPH_FEATURE_OSD_PRIMARY_AFFINITY,
+            CEPH_FEATURE_CRUSH_TUNABLES3
+        );
+    }
+}
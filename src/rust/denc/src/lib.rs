@@ -0,0 +1,357 @@
+//! Ceph wire-format encoding and decoding.
+//!
+//! This crate is the Rust counterpart of the C++ `encode`/`decode` machinery
+//! in `include/encoding.h` and `include/denc.h`.  All integers are encoded
+//! little-endian; strings and buffers are length-prefixed with a `u32`.
+
+pub mod encoding_metadata;
+pub mod entity_addr;
+pub mod error;
+pub mod features;
+pub mod hobject;
+pub mod monmap;
+pub mod pg_nls_response;
+pub mod types;
+pub mod versioned;
+pub mod zerocopy;
+
+use std::collections::BTreeMap;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+pub use encoding_metadata::EncodingMetadata;
+pub use error::RadosError;
+pub use versioned::VersionedEncode;
+
+/// A type that knows how to encode itself to, and decode itself from, the
+/// Ceph wire format.
+pub trait Denc: Sized {
+    fn encode(&self, buf: &mut BytesMut);
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError>;
+}
+
+/// Checks that `buf` holds at least `needed` more bytes before a fixed-width
+/// read, so decode errors surface as [`RadosError::InsufficientBytes`]
+/// instead of a panic inside the `bytes` crate.
+pub fn need(buf: &Bytes, needed: usize) -> Result<(), RadosError> {
+    if buf.remaining() < needed {
+        return Err(RadosError::InsufficientBytes {
+            needed,
+            available: buf.remaining(),
+        });
+    }
+    Ok(())
+}
+
+macro_rules! impl_denc_int {
+    ($($t:ty => $get:ident, $put:ident;)*) => {
+        $(
+            impl Denc for $t {
+                fn encode(&self, buf: &mut BytesMut) {
+                    buf.$put(*self);
+                }
+
+                fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+                    need(buf, std::mem::size_of::<$t>())?;
+                    Ok(buf.$get())
+                }
+            }
+        )*
+    };
+}
+
+impl_denc_int! {
+    u8 => get_u8, put_u8;
+    u16 => get_u16_le, put_u16_le;
+    u32 => get_u32_le, put_u32_le;
+    u64 => get_u64_le, put_u64_le;
+    i8 => get_i8, put_i8;
+    i16 => get_i16_le, put_i16_le;
+    i32 => get_i32_le, put_i32_le;
+    i64 => get_i64_le, put_i64_le;
+}
+
+impl Denc for bool {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u8(u8::from(*self));
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(u8::decode(buf)? != 0)
+    }
+}
+
+impl Denc for String {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u32_le(self.len() as u32);
+        buf.put_slice(self.as_bytes());
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let len = u32::decode(buf)? as usize;
+        need(buf, len)?;
+        let raw = buf.copy_to_bytes(len);
+        String::from_utf8(raw.to_vec())
+            .map_err(|e| RadosError::Protocol(format!("invalid utf-8 in string: {e}")))
+    }
+}
+
+impl Denc for Bytes {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u32_le(self.len() as u32);
+        buf.put_slice(self);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let len = u32::decode(buf)? as usize;
+        need(buf, len)?;
+        Ok(buf.copy_to_bytes(len))
+    }
+}
+
+impl<T: Denc> Denc for Vec<T> {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u32_le(self.len() as u32);
+        for item in self {
+            item.encode(buf);
+        }
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let count = u32::decode(buf)? as usize;
+        let mut out = Vec::with_capacity(count.min(4096));
+        for _ in 0..count {
+            out.push(T::decode(buf)?);
+        }
+        Ok(out)
+    }
+}
+
+/// An integer type usable as the length prefix of a [`DencVec`].
+pub trait LengthPrefix: Denc {
+    fn from_len(len: usize) -> Self;
+    fn to_len(self) -> usize;
+}
+
+macro_rules! impl_length_prefix {
+    ($($t:ty),*) => {
+        $(
+            impl LengthPrefix for $t {
+                fn from_len(len: usize) -> Self {
+                    debug_assert!(len <= <$t>::MAX as usize, "length overflows prefix");
+                    len as $t
+                }
+
+                fn to_len(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_length_prefix!(u8, u16, u32, u64);
+
+/// A `Vec<T>` whose wire form carries an `L`-wide length prefix instead of
+/// the default `u32`.  Some message fields use narrower counts — e.g. the
+/// `u8`-prefixed method lists in AUTH_REQUEST frames — and wrapping them in
+/// `DencVec` keeps the containing struct's encode/decode mechanical.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DencVec<T, L: LengthPrefix> {
+    pub items: Vec<T>,
+    _width: std::marker::PhantomData<L>,
+}
+
+/// The common `u32`-prefixed case, wire-compatible with the plain `Vec<T>`
+/// impl.
+pub type LengthPrefixed<T> = DencVec<T, u32>;
+
+impl<T, L: LengthPrefix> DencVec<T, L> {
+    pub fn new(items: Vec<T>) -> Self {
+        DencVec {
+            items,
+            _width: std::marker::PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T, L: LengthPrefix> From<Vec<T>> for DencVec<T, L> {
+    fn from(items: Vec<T>) -> Self {
+        DencVec::new(items)
+    }
+}
+
+impl<T, L: LengthPrefix> std::ops::Deref for DencVec<T, L> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.items
+    }
+}
+
+impl<T, L: LengthPrefix> std::ops::DerefMut for DencVec<T, L> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.items
+    }
+}
+
+impl<T: Denc, L: LengthPrefix> Denc for DencVec<T, L> {
+    fn encode(&self, buf: &mut BytesMut) {
+        L::from_len(self.items.len()).encode(buf);
+        for item in &self.items {
+            item.encode(buf);
+        }
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let count = L::decode(buf)?.to_len();
+        let mut items = Vec::with_capacity(count.min(4096));
+        for _ in 0..count {
+            items.push(T::decode(buf)?);
+        }
+        Ok(DencVec::new(items))
+    }
+}
+
+impl<K: Denc + Ord, V: Denc> Denc for BTreeMap<K, V> {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u32_le(self.len() as u32);
+        for (key, value) in self {
+            key.encode(buf);
+            value.encode(buf);
+        }
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        let count = u32::decode(buf)? as usize;
+        let mut out = BTreeMap::new();
+        for _ in 0..count {
+            let key = K::decode(buf)?;
+            let value = V::decode(buf)?;
+            out.insert(key, value);
+        }
+        Ok(out)
+    }
+}
+
+impl<T: Denc> Denc for Option<T> {
+    fn encode(&self, buf: &mut BytesMut) {
+        match self {
+            Some(v) => {
+                buf.put_u8(1);
+                v.encode(buf);
+            }
+            None => buf.put_u8(0),
+        }
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        if u8::decode(buf)? != 0 {
+            Ok(Some(T::decode(buf)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<A: Denc, B: Denc> Denc for (A, B) {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.0.encode(buf);
+        self.1.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok((A::decode(buf)?, B::decode(buf)?))
+    }
+}
+
+/// Encodes a value into a fresh buffer.  Convenience for tests and for
+/// callers that need a standalone `Bytes`.
+pub fn encode_to_bytes<T: Denc>(value: &T) -> Bytes {
+    let mut buf = BytesMut::new();
+    value.encode(&mut buf);
+    buf.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Denc + PartialEq + std::fmt::Debug>(value: T) {
+        let mut buf = encode_to_bytes(&value);
+        let decoded = T::decode(&mut buf).expect("decode");
+        assert_eq!(decoded, value);
+        assert!(buf.is_empty(), "trailing bytes after decode");
+    }
+
+    #[test]
+    fn primitives_round_trip() {
+        round_trip(0u8);
+        round_trip(0xdeadu16);
+        round_trip(0xdead_beefu32);
+        round_trip(u64::MAX);
+        round_trip(-42i32);
+        round_trip(true);
+    }
+
+    #[test]
+    fn string_and_bytes_round_trip() {
+        round_trip(String::from("rbd_header.10ab"));
+        round_trip(Bytes::from_static(b"\x00\x01\x02"));
+        round_trip(vec![1u32, 2, 3]);
+        round_trip(Some(7u64));
+        round_trip(Option::<u32>::None);
+    }
+
+    #[test]
+    fn denc_vec_prefix_widths() {
+        let items = vec![0xaau8, 0xbb, 0xcc];
+        round_trip(DencVec::<u8, u8>::new(items.clone()));
+        round_trip(DencVec::<u8, u16>::new(items.clone()));
+        round_trip(DencVec::<u8, u32>::new(items.clone()));
+        round_trip(DencVec::<u8, u64>::new(items.clone()));
+
+        // The prefix is exactly as wide as L.
+        assert_eq!(encode_to_bytes(&DencVec::<u8, u8>::new(items.clone())).len(), 1 + 3);
+        assert_eq!(encode_to_bytes(&DencVec::<u8, u16>::new(items.clone())).len(), 2 + 3);
+        assert_eq!(encode_to_bytes(&DencVec::<u8, u32>::new(items.clone())).len(), 4 + 3);
+        assert_eq!(encode_to_bytes(&DencVec::<u8, u64>::new(items.clone())).len(), 8 + 3);
+
+        // The u32 alias is wire-compatible with the plain Vec impl.
+        assert_eq!(
+            encode_to_bytes(&LengthPrefixed::<u8>::new(items.clone())),
+            encode_to_bytes(&items)
+        );
+    }
+
+    #[test]
+    fn btreemap_round_trip() {
+        round_trip(BTreeMap::<String, String>::new());
+
+        let mut single = BTreeMap::new();
+        single.insert("pg_num".to_string(), "32".to_string());
+        round_trip(single);
+
+        let omap: BTreeMap<String, Bytes> = (0..100)
+            .map(|i| (format!("key-{i:03}"), Bytes::from(vec![i as u8; 3])))
+            .collect();
+        round_trip(omap);
+    }
+
+    #[test]
+    fn short_buffer_is_an_error() {
+        let mut buf = Bytes::from_static(&[0x01]);
+        let err = u32::decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            RadosError::InsufficientBytes {
+                needed: 4,
+                available: 1
+            }
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+//! `MonMap` and `mon_info_t`.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::entity_addr::{
+    EntityAddr, EntityAddrvec, ENTITY_ADDR_TYPE_LEGACY, ENTITY_ADDR_TYPE_MSGR2,
+};
+use crate::types::{FsId, UTime};
+use crate::{Denc, RadosError, VersionedEncode};
+
+/// Everything the cluster knows about one monitor.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MonInfo {
+    pub name: String,
+    pub public_addrs: EntityAddrvec,
+    pub priority: u16,
+    pub weight: u16,
+}
+
+impl VersionedEncode for MonInfo {
+    const VERSION: u8 = 5;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.name.encode(buf);
+        self.public_addrs.encode_versioned(buf);
+        self.priority.encode(buf);
+        self.weight.encode(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        Ok(MonInfo {
+            name: String::decode(buf)?,
+            public_addrs: EntityAddrvec::decode_versioned(buf)?,
+            priority: u16::decode(buf)?,
+            weight: u16::decode(buf)?,
+        })
+    }
+}
+
+impl MonInfo {
+    pub fn builder() -> MonInfoBuilder {
+        MonInfoBuilder::default()
+    }
+}
+
+/// Builds a [`MonInfo`] field by field; used by monitor bootstrapping
+/// tools.
+#[derive(Debug, Default)]
+pub struct MonInfoBuilder {
+    name: Option<String>,
+    addrs: Vec<EntityAddr>,
+}
+
+impl MonInfoBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn v2_addr(mut self, addr: SocketAddr) -> Self {
+        self.addrs
+            .push(EntityAddr::new(ENTITY_ADDR_TYPE_MSGR2, addr));
+        self
+    }
+
+    pub fn v1_addr(mut self, addr: SocketAddr) -> Self {
+        self.addrs
+            .push(EntityAddr::new(ENTITY_ADDR_TYPE_LEGACY, addr));
+        self
+    }
+
+    pub fn build(self) -> Result<MonInfo, String> {
+        let name = self.name.ok_or("monitor name is required")?;
+        if name.is_empty() {
+            return Err("monitor name must not be empty".to_string());
+        }
+        if self.addrs.is_empty() {
+            return Err(format!("monitor {name} needs at least one address"));
+        }
+        Ok(MonInfo {
+            name,
+            public_addrs: EntityAddrvec { addrs: self.addrs },
+            priority: 0,
+            weight: 0,
+        })
+    }
+}
+
+/// The monitor map: who the monitors are and how to reach them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MonMap {
+    pub fsid: FsId,
+    pub epoch: u32,
+    pub mons: BTreeMap<String, MonInfo>,
+    pub last_changed: UTime,
+    pub created: UTime,
+}
+
+impl MonMap {
+    pub fn num_mons(&self) -> usize {
+        self.mons.len()
+    }
+
+    pub fn mon_names(&self) -> impl Iterator<Item = &str> {
+        self.mons.keys().map(String::as_str)
+    }
+
+    /// Adds a monitor, rejecting duplicate names and addresses.
+    pub fn add_mon(&mut self, info: MonInfo) -> Result<(), String> {
+        if self.mons.contains_key(&info.name) {
+            return Err(format!("monitor {} already exists", info.name));
+        }
+        for existing in self.mons.values() {
+            for addr in &existing.public_addrs.addrs {
+                if info.public_addrs.addrs.contains(addr) {
+                    return Err(format!(
+                        "address {addr} is already claimed by monitor {}",
+                        existing.name
+                    ));
+                }
+            }
+        }
+        self.mons.insert(info.name.clone(), info);
+        Ok(())
+    }
+
+    /// Removes the named monitor, returning its entry if it was present.
+    pub fn remove_mon(&mut self, name: &str) -> Option<MonInfo> {
+        self.mons.remove(name)
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    pub fn find_mon_by_name(&self, name: &str) -> Option<&MonInfo> {
+        self.mons.get(name)
+    }
+
+    /// Finds the monitor advertising `addr` (v1 or v2).  Useful when
+    /// reconnecting: the client knows which address failed but not the
+    /// monitor's name.
+    pub fn find_mon_by_addr(&self, addr: &EntityAddr) -> Option<&MonInfo> {
+        self.mons
+            .values()
+            .find(|info| info.public_addrs.addrs.contains(addr))
+    }
+}
+
+impl VersionedEncode for MonMap {
+    const VERSION: u8 = 9;
+    const COMPAT: u8 = 6;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.fsid.encode(buf);
+        self.epoch.encode(buf);
+        (self.mons.len() as u32).encode(buf);
+        for (name, info) in &self.mons {
+            name.encode(buf);
+            info.encode_versioned(buf);
+        }
+        self.last_changed.encode(buf);
+        self.created.encode(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        let fsid = FsId::decode(buf)?;
+        let epoch = u32::decode(buf)?;
+        let count = u32::decode(buf)? as usize;
+        let mut mons = BTreeMap::new();
+        for _ in 0..count {
+            let name = String::decode(buf)?;
+            let info = MonInfo::decode_versioned(buf)?;
+            mons.insert(name, info);
+        }
+        Ok(MonMap {
+            fsid,
+            epoch,
+            mons,
+            last_changed: UTime::decode(buf)?,
+            created: UTime::decode(buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity_addr::{EntityAddr, ENTITY_ADDR_TYPE_MSGR2};
+
+    pub(crate) fn sample_monmap() -> MonMap {
+        let mut mons = BTreeMap::new();
+        for (i, name) in ["a", "b", "c"].iter().enumerate() {
+            mons.insert(
+                name.to_string(),
+                MonInfo {
+                    name: name.to_string(),
+                    public_addrs: EntityAddrvec {
+                        addrs: vec![EntityAddr::new(
+                            ENTITY_ADDR_TYPE_MSGR2,
+                            format!("10.0.0.{}:3300", i + 1).parse().unwrap(),
+                        )],
+                    },
+                    priority: 0,
+                    weight: 0,
+                },
+            );
+        }
+        MonMap {
+            fsid: FsId(*b"abcdefghijklmnop"),
+            epoch: 5,
+            mons,
+            last_changed: UTime::new(100, 0),
+            created: UTime::new(1, 0),
+        }
+    }
+
+    #[test]
+    fn builder_validates_its_input() {
+        let info = MonInfo::builder()
+            .name("d")
+            .v2_addr("10.0.0.4:3300".parse().unwrap())
+            .v1_addr("10.0.0.4:6789".parse().unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(info.name, "d");
+        assert_eq!(info.public_addrs.v2_addrs().len(), 1);
+        assert_eq!(info.public_addrs.v1_addrs().len(), 1);
+
+        assert!(MonInfo::builder().name("e").build().is_err());
+        assert!(MonInfo::builder()
+            .v2_addr("10.0.0.4:3300".parse().unwrap())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn add_mon_rejects_duplicates() {
+        let mut map = sample_monmap();
+        let dup_name = MonInfo::builder()
+            .name("a")
+            .v2_addr("10.0.0.9:3300".parse().unwrap())
+            .build()
+            .unwrap();
+        assert!(map.add_mon(dup_name).is_err());
+
+        let dup_addr = MonInfo::builder()
+            .name("d")
+            .v2_addr("10.0.0.1:3300".parse().unwrap())
+            .build()
+            .unwrap();
+        assert!(map.add_mon(dup_addr).is_err());
+
+        let fresh = MonInfo::builder()
+            .name("d")
+            .v2_addr("10.0.0.4:3300".parse().unwrap())
+            .build()
+            .unwrap();
+        map.add_mon(fresh).unwrap();
+        assert_eq!(map.num_mons(), 4);
+
+        assert_eq!(map.remove_mon("d").unwrap().name, "d");
+        assert!(map.remove_mon("d").is_none());
+    }
+
+    #[test]
+    fn lookup_by_name_and_address() {
+        let map = sample_monmap();
+        assert_eq!(map.epoch(), 5);
+        assert_eq!(map.find_mon_by_name("b").unwrap().name, "b");
+        assert!(map.find_mon_by_name("z").is_none());
+
+        let addr = EntityAddr::new(ENTITY_ADDR_TYPE_MSGR2, "10.0.0.2:3300".parse().unwrap());
+        assert_eq!(map.find_mon_by_addr(&addr).unwrap().name, "b");
+
+        let unknown = EntityAddr::new(ENTITY_ADDR_TYPE_MSGR2, "10.0.0.9:3300".parse().unwrap());
+        assert!(map.find_mon_by_addr(&unknown).is_none());
+    }
+
+    #[test]
+    fn monmap_round_trip() {
+        let map = sample_monmap();
+        let mut buf = BytesMut::new();
+        map.encode_versioned(&mut buf);
+        let mut raw = buf.freeze();
+        assert_eq!(MonMap::decode_versioned(&mut raw).unwrap(), map);
+    }
+}
@@ -0,0 +1,183 @@
+//! `pg_nls_response_t`: the reply payload of a `PGNLS` (object listing) op.
+
+use bytes::{Bytes, BytesMut};
+
+use crate::hobject::HObject;
+use crate::{Denc, RadosError, VersionedEncode};
+
+/// One listed object.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListObjectImpl {
+    pub hobj: HObject,
+}
+
+impl VersionedEncode for ListObjectImpl {
+    const VERSION: u8 = 1;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.hobj.encode_versioned(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        Ok(ListObjectImpl {
+            hobj: HObject::decode_versioned(buf)?,
+        })
+    }
+}
+
+/// The listing page returned by one `PGNLS` op.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PgNlsResponse {
+    pub handle: u64,
+    pub entries: Vec<ListObjectImpl>,
+    /// Where to resume the listing: the OSD's position after this page,
+    /// [`HObject::MAX`] once the listing is complete.
+    pub cursor: HObject,
+}
+
+impl PgNlsResponse {
+    /// Iterates over the listed objects without cloning them.
+    pub fn iter(&self) -> PgNlsIter<'_> {
+        PgNlsIter {
+            entries: self.entries.iter(),
+        }
+    }
+
+    /// Whether this page ends the listing.
+    pub fn is_complete(&self) -> bool {
+        self.cursor.is_max()
+    }
+}
+
+/// Iterator over the objects of one listing page.
+pub struct PgNlsIter<'a> {
+    entries: std::slice::Iter<'a, ListObjectImpl>,
+}
+
+impl<'a> Iterator for PgNlsIter<'a> {
+    type Item = &'a HObject;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|entry| &entry.hobj)
+    }
+}
+
+impl<'a> IntoIterator for &'a PgNlsResponse {
+    type Item = &'a HObject;
+    type IntoIter = PgNlsIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl VersionedEncode for PgNlsResponse {
+    const VERSION: u8 = 1;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.handle.encode(buf);
+        (self.entries.len() as u32).encode(buf);
+        for entry in &self.entries {
+            entry.encode_versioned(buf);
+        }
+        self.cursor.encode_versioned(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        let handle = u64::decode(buf)?;
+        let count = u32::decode(buf)? as usize;
+        let mut entries = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            entries.push(ListObjectImpl::decode_versioned(buf)?);
+        }
+        // The cursor trails the entries, as in pg_nls_response_t::decode.
+        Ok(PgNlsResponse {
+            handle,
+            entries,
+            cursor: HObject::decode_versioned(buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listing_round_trip() {
+        let response = PgNlsResponse {
+            handle: 17,
+            entries: vec![
+                ListObjectImpl {
+                    hobj: HObject::new("obj-1", 2),
+                },
+                ListObjectImpl {
+                    hobj: HObject::new("obj-2", 2),
+                },
+            ],
+            // More objects remain: the cursor is a real position.
+            cursor: HObject::new("obj-2", 2),
+        };
+        let mut buf = BytesMut::new();
+        response.encode_versioned(&mut buf);
+        let mut raw = buf.freeze();
+        let decoded = PgNlsResponse::decode_versioned(&mut raw).unwrap();
+        assert_eq!(decoded, response);
+        assert!(!decoded.is_complete());
+    }
+
+    #[test]
+    fn max_cursor_marks_the_listing_complete() {
+        let done = PgNlsResponse {
+            handle: 3,
+            entries: Vec::new(),
+            cursor: HObject::MAX,
+        };
+        let mut buf = BytesMut::new();
+        done.encode_versioned(&mut buf);
+        let mut raw = buf.freeze();
+        assert!(PgNlsResponse::decode_versioned(&mut raw)
+            .unwrap()
+            .is_complete());
+    }
+
+    #[test]
+    fn iter_yields_each_object_in_order() {
+        let empty = PgNlsResponse::default();
+        assert_eq!(empty.iter().count(), 0);
+
+        let single = PgNlsResponse {
+            handle: 1,
+            entries: vec![ListObjectImpl {
+                hobj: HObject::new("only", 2),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            single.iter().map(|h| h.oid.as_str()).collect::<Vec<_>>(),
+            ["only"]
+        );
+
+        let multi = PgNlsResponse {
+            handle: 2,
+            entries: vec![
+                ListObjectImpl {
+                    hobj: HObject::new("a", 2),
+                },
+                ListObjectImpl {
+                    hobj: HObject::new("b", 2),
+                },
+                ListObjectImpl {
+                    hobj: HObject::new("c", 2),
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            (&multi).into_iter().map(|h| h.oid.as_str()).collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Small common wire types shared across the Ceph protocols.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{need, Denc, RadosError};
+
+/// Ceph's `utime_t`: seconds and nanoseconds since the Unix epoch, both
+/// encoded as `u32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct UTime {
+    pub sec: u32,
+    pub nsec: u32,
+}
+
+impl UTime {
+    pub const fn new(sec: u32, nsec: u32) -> Self {
+        UTime { sec, nsec }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.sec == 0 && self.nsec == 0
+    }
+
+    /// The current wall-clock time.
+    pub fn now() -> UTime {
+        UTime::from_system_time(SystemTime::now())
+    }
+
+    /// Converts a [`SystemTime`]; times before the epoch (not
+    /// representable in a `utime_t`) clamp to zero.
+    pub fn from_system_time(t: SystemTime) -> UTime {
+        match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => UTime::new(d.as_secs() as u32, d.subsec_nanos()),
+            Err(_) => UTime::new(0, 0),
+        }
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::new(u64::from(self.sec), self.nsec)
+    }
+
+    /// How much later `self` is than `other`; [`Duration::ZERO`] when
+    /// `other` is not earlier.
+    pub fn elapsed_since(&self, other: &UTime) -> Duration {
+        let this = Duration::new(u64::from(self.sec), self.nsec);
+        let that = Duration::new(u64::from(other.sec), other.nsec);
+        this.saturating_sub(that)
+    }
+}
+
+impl Denc for UTime {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.sec.encode(buf);
+        self.nsec.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(UTime {
+            sec: u32::decode(buf)?,
+            nsec: u32::decode(buf)?,
+        })
+    }
+}
+
+/// The cluster fsid (`uuid_d` in C++): 16 raw bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FsId(pub [u8; 16]);
+
+impl FsId {
+    pub const ZERO: FsId = FsId([0; 16]);
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 16]
+    }
+}
+
+impl fmt::Display for FsId {
+    /// Formats as a hyphenated lowercase UUID, matching `uuid_d`'s
+    /// printing in the C++ tools.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+            b[8], b[9], b[10], b[11], b[12], b[13], b[14], b[15],
+        )
+    }
+}
+
+impl FromStr for FsId {
+    type Err = RadosError;
+
+    /// Parses the hyphenated 8-4-4-4-12 UUID form, as found in a
+    /// ceph.conf `fsid` option.  Case-insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || RadosError::Protocol(format!("invalid fsid {s:?}"));
+        let mut raw = [0u8; 16];
+        let mut groups = s.split('-');
+        let mut offset = 0;
+        for expected_len in [8, 4, 4, 4, 12] {
+            let group = groups.next().ok_or_else(bad)?;
+            if group.len() != expected_len || !group.is_ascii() {
+                return Err(bad());
+            }
+            for i in (0..expected_len).step_by(2) {
+                raw[offset] = u8::from_str_radix(&group[i..i + 2], 16).map_err(|_| bad())?;
+                offset += 1;
+            }
+        }
+        if groups.next().is_some() {
+            return Err(bad());
+        }
+        Ok(FsId(raw))
+    }
+}
+
+impl Denc for FsId {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_slice(&self.0);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        need(buf, 16)?;
+        let mut raw = [0u8; 16];
+        buf.copy_to_slice(&mut raw);
+        Ok(FsId(raw))
+    }
+}
+
+/// Ceph's `eversion_t`: a (version, epoch) pair identifying a point in an
+/// OSD's log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EVersion {
+    pub version: u64,
+    pub epoch: u32,
+}
+
+impl EVersion {
+    /// True for the zero version that predates any log entry.
+    pub fn is_initial(&self) -> bool {
+        self.version == 0 && self.epoch == 0
+    }
+
+    /// The next version within the same epoch.
+    pub fn increment_version(&self) -> EVersion {
+        EVersion {
+            version: self.version + 1,
+            epoch: self.epoch,
+        }
+    }
+
+    /// The first version of `new_epoch`: the version counter restarts at
+    /// zero when the epoch advances.
+    pub fn advance_epoch(&self, new_epoch: u32) -> EVersion {
+        EVersion {
+            version: 0,
+            epoch: new_epoch,
+        }
+    }
+}
+
+// Log entries order by epoch first: anything from a newer epoch is newer,
+// however far the version counter ran within the old one.
+impl Ord for EVersion {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then(self.version.cmp(&other.version))
+    }
+}
+
+impl PartialOrd for EVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Denc for EVersion {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.version.encode(buf);
+        self.epoch.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(EVersion {
+            version: u64::decode(buf)?,
+            epoch: u32::decode(buf)?,
+        })
+    }
+}
+
+/// Explicit padding bytes in a wire structure.
+///
+/// `Padding` carries its byte count, so unlike most wire types it cannot
+/// implement [`Denc::decode`] (the count is not on the wire); use
+/// [`Padding::encode_to`] and [`Padding::decode_from`] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Padding {
+    pub len: usize,
+}
+
+impl Padding {
+    /// Padding of exactly `n` zero bytes.
+    pub fn zero(n: usize) -> Padding {
+        Padding { len: n }
+    }
+
+    /// The padding that aligns a structure of `size` bytes to the next
+    /// `align` boundary; zero-length when `size` is already aligned.
+    /// `align` must be a power of two.
+    pub fn aligned_to(size: usize, align: usize) -> Padding {
+        debug_assert!(align.is_power_of_two());
+        Padding {
+            len: size.wrapping_neg() & (align - 1),
+        }
+    }
+
+    /// True when no bytes are needed.
+    pub fn is_zero(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn encode_to(&self, buf: &mut BytesMut) {
+        buf.put_bytes(0, self.len);
+    }
+
+    /// Consumes the padding bytes.  Their value is not checked: peers are
+    /// only required to reserve the space, not to zero it.
+    pub fn decode_from(&self, buf: &mut Bytes) -> Result<(), RadosError> {
+        need(buf, self.len)?;
+        buf.advance(self.len);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_to_bytes;
+
+    #[test]
+    fn utime_round_trip() {
+        let t = UTime::new(1_700_000_000, 999);
+        let mut buf = encode_to_bytes(&t);
+        assert_eq!(UTime::decode(&mut buf).unwrap(), t);
+    }
+
+    #[test]
+    fn utime_system_time_round_trip() {
+        let t = UTime::new(1_700_000_000, 123_456_789);
+        assert_eq!(UTime::from_system_time(t.to_system_time()), t);
+        assert!(UTime::now().sec > 1_700_000_000);
+        let pre_epoch = UNIX_EPOCH - Duration::from_secs(1);
+        assert!(UTime::from_system_time(pre_epoch).is_zero());
+    }
+
+    #[test]
+    fn utime_elapsed_since_saturates() {
+        let earlier = UTime::new(100, 500_000_000);
+        let later = UTime::new(102, 250_000_000);
+        assert_eq!(
+            later.elapsed_since(&earlier),
+            Duration::new(1, 750_000_000)
+        );
+        assert_eq!(earlier.elapsed_since(&later), Duration::ZERO);
+    }
+
+    #[test]
+    fn fsid_round_trip() {
+        let fsid = FsId(*b"0123456789abcdef");
+        let mut buf = encode_to_bytes(&fsid);
+        assert_eq!(FsId::decode(&mut buf).unwrap(), fsid);
+    }
+
+    #[test]
+    fn fsid_parses_and_prints_the_uuid_form() {
+        let text = "7150dbe1-1803-44b9-9a3d-b893308fd02e";
+        let fsid: FsId = text.parse().unwrap();
+        assert_eq!(fsid.to_string(), text);
+        assert_eq!(fsid.0[0], 0x71);
+        assert_eq!(fsid.0[15], 0x2e);
+
+        // Case-insensitive, printed back as lowercase.
+        let upper: FsId = text.to_uppercase().parse().unwrap();
+        assert_eq!(upper, fsid);
+
+        for junk in [
+            "",
+            "7150dbe1",
+            "7150dbe1-1803-44b9-9a3d",
+            "7150dbe1-1803-44b9-9a3d-b893308fd02e-ff",
+            "g150dbe1-1803-44b9-9a3d-b893308fd02e",
+            "7150dbe-11803-44b9-9a3d-b893308fd02e",
+        ] {
+            assert!(
+                matches!(junk.parse::<FsId>(), Err(RadosError::Protocol(_))),
+                "accepted {junk:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn padding_alignment_for_powers_of_two() {
+        assert!(Padding::aligned_to(8, 8).is_zero());
+        assert_eq!(Padding::aligned_to(1, 8).len, 7);
+        assert_eq!(Padding::aligned_to(13, 4).len, 3);
+        assert_eq!(Padding::aligned_to(0, 16).len, 0);
+        assert_eq!(Padding::zero(3).len, 3);
+        assert!(!Padding::zero(3).is_zero());
+
+        // Encode emits zeroes; decode only reserves the space, so junk
+        // padding from a sloppy peer is tolerated.
+        let pad = Padding::aligned_to(5, 4);
+        let mut buf = BytesMut::new();
+        pad.encode_to(&mut buf);
+        assert_eq!(&buf[..], &[0, 0, 0]);
+        let mut junk = Bytes::from_static(&[0xde, 0xad, 0xbe, 0x77]);
+        pad.decode_from(&mut junk).unwrap();
+        assert_eq!(junk.len(), 1);
+
+        // Truncated padding is still an error.
+        let mut short = Bytes::from_static(&[0]);
+        assert!(pad.decode_from(&mut short).is_err());
+    }
+
+    #[test]
+    fn eversion_round_trip() {
+        let v = EVersion {
+            version: 42,
+            epoch: 7,
+        };
+        let mut buf = encode_to_bytes(&v);
+        assert_eq!(EVersion::decode(&mut buf).unwrap(), v);
+    }
+
+    #[test]
+    fn eversion_orders_by_epoch_then_version() {
+        let at = |version, epoch| EVersion { version, epoch };
+
+        // Same epoch: the version decides.
+        assert!(at(1, 3) < at(2, 3));
+        // A newer epoch beats any version from an older one.
+        assert!(at(1_000_000, 3) < at(0, 4));
+        assert_eq!(at(5, 2), at(5, 2));
+        assert!(at(5, 2) >= at(5, 2));
+
+        assert!(EVersion::default().is_initial());
+        assert!(!at(0, 1).is_initial());
+
+        let v = at(5, 2).increment_version();
+        assert_eq!(v, at(6, 2));
+        assert!(at(5, 2) < v);
+
+        // Advancing the epoch restarts the version counter but still
+        // orders after everything from the old epoch.
+        let advanced = v.advance_epoch(3);
+        assert_eq!(advanced, at(0, 3));
+        assert!(v < advanced);
+    }
+}
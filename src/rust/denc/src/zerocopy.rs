@@ -0,0 +1,69 @@
+//! Zero-copy decode support.
+//!
+//! Types whose wire layout is identical to their in-memory layout can be
+//! decoded by reinterpreting the buffer instead of field-by-field reads.
+
+/// Marker trait for types that are safe to decode by reinterpreting wire
+/// bytes: `repr(C, packed)`, little-endian fields only, no padding.
+///
+/// # Safety
+///
+/// Implementors must guarantee that every bit pattern of
+/// `size_of::<Self>()` bytes is a valid value of `Self` and that the struct
+/// contains no implicit padding.
+pub unsafe trait ZeroCopyDencode: Copy {}
+
+/// Proves at compile time that a [`ZeroCopyDencode`] type really has the
+/// layout the marker promises: alignment 1 (i.e. `repr(C, packed)`) and no
+/// hidden padding.  The fields are restated so the macro can sum their
+/// sizes — a mismatch against `size_of::<Self>()` means padding snuck in.
+///
+/// ```
+/// # use denc::assert_zero_copy_safe;
+/// # use denc::zerocopy::ZeroCopyDencode;
+/// #[derive(Clone, Copy)]
+/// #[repr(C, packed)]
+/// struct Header {
+///     version: u8,
+///     length: u32,
+/// }
+/// unsafe impl ZeroCopyDencode for Header {}
+/// assert_zero_copy_safe!(Header { version: u8, length: u32 });
+/// ```
+#[macro_export]
+macro_rules! assert_zero_copy_safe {
+    ($ty:ty { $($field:ident : $ftype:ty),+ $(,)? }) => {
+        const _: () = {
+            const fn marked<T: $crate::zerocopy::ZeroCopyDencode>() {}
+            marked::<$ty>();
+            assert!(
+                std::mem::align_of::<$ty>() == 1,
+                "zero-copy types must be repr(C, packed)"
+            );
+            assert!(
+                std::mem::size_of::<$ty>()
+                    == 0 $(+ std::mem::size_of::<$ftype>())+,
+                "field sizes do not sum to the struct size: hidden padding"
+            );
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZeroCopyDencode;
+
+    #[derive(Clone, Copy)]
+    #[repr(C, packed)]
+    struct WireHeader {
+        tag: u8,
+        seq: u64,
+        len: u32,
+    }
+
+    unsafe impl ZeroCopyDencode for WireHeader {}
+
+    // The assertion itself is the test: a padded or unmarked struct fails
+    // to compile.
+    crate::assert_zero_copy_safe!(WireHeader { tag: u8, seq: u64, len: u32 });
+}
@@ -0,0 +1,265 @@
+//! Type introspection for the `schema` command.
+//!
+//! Each registry type describes its wire-level fields so third-party
+//! tooling can learn the layout without reading the Rust sources.  The
+//! descriptions are maintained by hand next to the decoders; keep them in
+//! sync when a type gains a field.
+
+use crush::PgId;
+use denc::entity_addr::EntityAddrvec;
+use denc::hobject::HObject;
+use denc::monmap::MonMap;
+use osdclient::osdmap::{OSDMap, PgPool};
+use serde_json::{json, Value};
+
+/// A recursive description of one type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeSchema {
+    /// A leaf type, named as in Rust (`u32`, `string`, `bytes`, ...).
+    Primitive(&'static str),
+    Vec(Box<TypeSchema>),
+    Map(Box<TypeSchema>, Box<TypeSchema>),
+    /// No registry type carries an optional field yet; kept so schemas
+    /// for such types can be added without touching the enum.
+    #[allow(dead_code)]
+    Optional(Box<TypeSchema>),
+    Struct {
+        name: &'static str,
+        fields: Vec<(&'static str, TypeSchema)>,
+    },
+}
+
+impl TypeSchema {
+    /// A short rendering used for field types: `vec<u32>`,
+    /// `map<u64, pg_pool_t>`, or the struct's name.
+    pub fn type_name(&self) -> String {
+        match self {
+            TypeSchema::Primitive(name) => (*name).to_string(),
+            TypeSchema::Vec(inner) => format!("vec<{}>", inner.type_name()),
+            TypeSchema::Map(key, value) => {
+                format!("map<{}, {}>", key.type_name(), value.type_name())
+            }
+            TypeSchema::Optional(inner) => format!("optional<{}>", inner.type_name()),
+            TypeSchema::Struct { name, .. } => (*name).to_string(),
+        }
+    }
+
+    /// The JSON form printed by `dencoder schema`.
+    pub fn to_json(&self) -> Value {
+        match self {
+            TypeSchema::Struct { name, fields } => json!({
+                "type": name,
+                "fields": fields
+                    .iter()
+                    .map(|(field, schema)| json!({
+                        "name": field,
+                        "type": schema.type_name(),
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            other => json!({ "type": other.type_name() }),
+        }
+    }
+}
+
+/// Implemented by every type in the `dencoder` registry.
+pub trait SchemaProvider {
+    fn schema() -> TypeSchema;
+}
+
+fn primitive(name: &'static str) -> TypeSchema {
+    TypeSchema::Primitive(name)
+}
+
+impl SchemaProvider for PgId {
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            name: "pg_t",
+            fields: vec![("pool", primitive("u64")), ("seed", primitive("u32"))],
+        }
+    }
+}
+
+impl SchemaProvider for EntityAddrvec {
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            name: "entity_addrvec_t",
+            fields: vec![(
+                "addrs",
+                TypeSchema::Vec(Box::new(primitive("entity_addr_t"))),
+            )],
+        }
+    }
+}
+
+impl SchemaProvider for HObject {
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            name: "hobject_t",
+            fields: vec![
+                ("key", primitive("string")),
+                ("oid", primitive("string")),
+                ("snap", primitive("u64")),
+                ("hash", primitive("u32")),
+                ("max", primitive("bool")),
+                ("nspace", primitive("string")),
+                ("pool", primitive("i64")),
+            ],
+        }
+    }
+}
+
+impl SchemaProvider for MonMap {
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            name: "MonMap",
+            fields: vec![
+                ("fsid", primitive("uuid")),
+                ("epoch", primitive("u32")),
+                (
+                    "mons",
+                    TypeSchema::Map(
+                        Box::new(primitive("string")),
+                        Box::new(primitive("mon_info_t")),
+                    ),
+                ),
+                ("last_changed", primitive("utime_t")),
+                ("created", primitive("utime_t")),
+            ],
+        }
+    }
+}
+
+impl SchemaProvider for PgPool {
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            name: "pg_pool_t",
+            fields: vec![
+                ("id", primitive("u64")),
+                ("pool_type", primitive("u8")),
+                ("size", primitive("u32")),
+                ("min_size", primitive("u32")),
+                ("pg_num", primitive("u32")),
+                ("pg_num_target", primitive("u32")),
+                ("crush_rule", primitive("u32")),
+                ("object_hash", primitive("u8")),
+                ("flags", primitive("u64")),
+                (
+                    "snaps",
+                    TypeSchema::Map(
+                        Box::new(primitive("u64")),
+                        Box::new(primitive("pool_snap_info_t")),
+                    ),
+                ),
+                ("expected_num_objects", primitive("u64")),
+            ],
+        }
+    }
+}
+
+impl SchemaProvider for OSDMap {
+    fn schema() -> TypeSchema {
+        TypeSchema::Struct {
+            name: "OSDMap",
+            fields: vec![
+                ("epoch", primitive("u32")),
+                ("fsid", primitive("uuid")),
+                ("created", primitive("utime_t")),
+                ("modified", primitive("utime_t")),
+                ("flags", primitive("u64")),
+                (
+                    "pools",
+                    TypeSchema::Map(
+                        Box::new(primitive("u64")),
+                        Box::new(PgPool::schema()),
+                    ),
+                ),
+                (
+                    "pool_name",
+                    TypeSchema::Map(Box::new(primitive("u64")), Box::new(primitive("string"))),
+                ),
+                ("osd_state", TypeSchema::Vec(Box::new(primitive("u32")))),
+                ("osd_weight", TypeSchema::Vec(Box::new(primitive("u32")))),
+                (
+                    "osd_addrs",
+                    TypeSchema::Vec(Box::new(EntityAddrvec::schema())),
+                ),
+                ("osd_info", TypeSchema::Vec(Box::new(primitive("osd_info_t")))),
+                (
+                    "osd_xinfo",
+                    TypeSchema::Vec(Box::new(primitive("osd_xinfo_t"))),
+                ),
+                (
+                    "pg_temp",
+                    TypeSchema::Map(
+                        Box::new(PgId::schema()),
+                        Box::new(TypeSchema::Vec(Box::new(primitive("u32")))),
+                    ),
+                ),
+                (
+                    "pg_upmap",
+                    TypeSchema::Map(
+                        Box::new(PgId::schema()),
+                        Box::new(TypeSchema::Vec(Box::new(primitive("u32")))),
+                    ),
+                ),
+                (
+                    "pg_upmap_items",
+                    TypeSchema::Map(
+                        Box::new(PgId::schema()),
+                        Box::new(TypeSchema::Vec(Box::new(primitive("pair<u32, u32>")))),
+                    ),
+                ),
+                ("crush", primitive("bytes")),
+            ],
+        }
+    }
+}
+
+/// Dispatches `type_name` to the matching [`SchemaProvider`].
+pub fn schema_for(type_name: &str) -> Option<TypeSchema> {
+    match type_name {
+        "pg_t" => Some(PgId::schema()),
+        "entity_addrvec_t" => Some(EntityAddrvec::schema()),
+        "hobject_t" => Some(HObject::schema()),
+        "MonMap" => Some(MonMap::schema()),
+        "OSDMap" => Some(OSDMap::schema()),
+        "pg_pool_t" => Some(PgPool::schema()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pg_t_schema_lists_its_fields() {
+        let value = schema_for("pg_t").unwrap().to_json();
+        assert_eq!(value["type"], "pg_t");
+        assert_eq!(
+            value["fields"],
+            json!([
+                { "name": "pool", "type": "u64" },
+                { "name": "seed", "type": "u32" },
+            ])
+        );
+    }
+
+    #[test]
+    fn osdmap_schema_renders_nested_types() {
+        let value = schema_for("OSDMap").unwrap().to_json();
+        assert_eq!(value["type"], "OSDMap");
+        let fields: Vec<(&str, &str)> = value["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| (f["name"].as_str().unwrap(), f["type"].as_str().unwrap()))
+            .collect();
+        assert!(fields.contains(&("epoch", "u32")));
+        assert!(fields.contains(&("pools", "map<u64, pg_pool_t>")));
+        assert!(fields.contains(&("pg_temp", "map<pg_t, vec<u32>>")));
+
+        assert!(schema_for("no_such_t").is_none());
+    }
+}
@@ -0,0 +1,810 @@
+//! The monitor client proper.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use auth::provider::{AuthProvider, MonitorAuthProvider, NoneAuthProvider};
+use auth::{EntityName, Keyring};
+use bytes::Bytes;
+use denc::entity_addr::EntityAddr;
+use denc::monmap::MonMap;
+use denc::types::FsId;
+use msgr2::protocol::MessageHandler;
+use msgr2::state_machine::{ConnectionConfig, ConnectionMode};
+use msgr2::{Connection, Message};
+use tokio::sync::{oneshot, watch, Mutex};
+
+use crate::error::MonClientError;
+use crate::messages::{
+    MMonCommand, MMonCommandAck, MMonGetVersion, MMonGetVersionReply, MMonMap, CEPH_MSG_MON_MAP,
+    CEPH_MSG_MON_GET_VERSION_REPLY, MSG_MON_COMMAND_ACK,
+};
+use crate::types::{AuthEntry, CommandResult, DfResult, MonStatus};
+
+/// The default msgr2 monitor port, assumed when an address omits one.
+const DEFAULT_MON_PORT: u16 = 3300;
+
+/// Configuration for a monitor session.
+#[derive(Clone)]
+pub struct MonClientConfig {
+    pub mon_addrs: Vec<EntityAddr>,
+    pub entity: EntityName,
+    pub keyring: Option<Keyring>,
+    pub mode: ConnectionMode,
+    pub command_timeout: Duration,
+    /// How long to wait for commands serviced by the mgr, which may be
+    /// slower than the monitors themselves.
+    pub mgr_timeout: Duration,
+}
+
+impl MonClientConfig {
+    pub fn new(mon_addrs: Vec<EntityAddr>, entity: EntityName) -> Self {
+        MonClientConfig {
+            mon_addrs,
+            entity,
+            keyring: None,
+            mode: ConnectionMode::default(),
+            command_timeout: Duration::from_secs(30),
+            mgr_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Builds a configuration from `CEPH_*` environment variables, for
+    /// containerized deployments where mounting a ceph.conf is
+    /// inconvenient.
+    ///
+    /// `CEPH_MON_HOST` (comma-separated addresses) and `CEPH_ENTITY` are
+    /// required, as is `CEPH_KEYRING` when `CEPH_AUTH_METHOD` is `cephx`;
+    /// all missing required variables are reported in one error.
+    /// `CEPH_AUTH_METHOD` defaults to `none` and `CEPH_CONNECTION_MODE`
+    /// (`crc` or `secure`) to `crc`.
+    pub fn from_env() -> Result<MonClientConfig, MonClientError> {
+        Self::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    fn from_env_with(
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<MonClientConfig, MonClientError> {
+        let mon_host = var("CEPH_MON_HOST");
+        let entity = var("CEPH_ENTITY");
+        let auth_method = var("CEPH_AUTH_METHOD").unwrap_or_else(|| "none".to_string());
+        let keyring_path = var("CEPH_KEYRING");
+
+        let mut missing = Vec::new();
+        if mon_host.is_none() {
+            missing.push("CEPH_MON_HOST");
+        }
+        if entity.is_none() {
+            missing.push("CEPH_ENTITY");
+        }
+        if auth_method == "cephx" && keyring_path.is_none() {
+            missing.push("CEPH_KEYRING");
+        }
+        if !missing.is_empty() {
+            return Err(MonClientError::BadConfig(format!(
+                "missing environment variables: {}",
+                missing.join(", ")
+            )));
+        }
+
+        let mut mon_addrs = Vec::new();
+        for part in mon_host
+            .unwrap()
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let addr = part
+                .parse::<EntityAddr>()
+                .or_else(|_| format!("{part}:{DEFAULT_MON_PORT}").parse())
+                .map_err(|_| {
+                    MonClientError::BadConfig(format!(
+                        "CEPH_MON_HOST: invalid monitor address {part:?}"
+                    ))
+                })?;
+            mon_addrs.push(addr);
+        }
+        if mon_addrs.is_empty() {
+            return Err(MonClientError::BadConfig(
+                "CEPH_MON_HOST: no monitor addresses".to_string(),
+            ));
+        }
+
+        let entity: EntityName = entity
+            .unwrap()
+            .parse()
+            .map_err(|e| MonClientError::BadConfig(format!("CEPH_ENTITY: {e}")))?;
+
+        let keyring = match auth_method.as_str() {
+            "none" => None,
+            "cephx" => Some(
+                Keyring::from_file(keyring_path.unwrap())
+                    .map_err(|e| MonClientError::BadConfig(format!("CEPH_KEYRING: {e}")))?,
+            ),
+            other => {
+                return Err(MonClientError::BadConfig(format!(
+                    "CEPH_AUTH_METHOD must be cephx or none, got {other:?}"
+                )))
+            }
+        };
+
+        let mode = match var("CEPH_CONNECTION_MODE").as_deref() {
+            None | Some("crc") => ConnectionMode::Crc,
+            Some("secure") => ConnectionMode::Secure,
+            Some(other) => {
+                return Err(MonClientError::BadConfig(format!(
+                    "CEPH_CONNECTION_MODE must be crc or secure, got {other:?}"
+                )))
+            }
+        };
+
+        let mut config = MonClientConfig::new(mon_addrs, entity);
+        config.keyring = keyring;
+        config.mode = mode;
+        Ok(config)
+    }
+
+    /// The auth provider for this configuration: cephx when the keyring
+    /// holds a key for `entity`, otherwise `none`.
+    pub fn auth_provider(&self) -> Arc<dyn AuthProvider> {
+        if let Some(keyring) = &self.keyring {
+            if let Some(key) = keyring.get_key(&self.entity.to_string()) {
+                return Arc::new(MonitorAuthProvider::new(self.entity.clone(), key.clone()));
+            }
+        }
+        Arc::new(NoneAuthProvider::new(self.entity.clone()))
+    }
+}
+
+/// Shared state updated by the message handler.
+#[derive(Default)]
+struct Inner {
+    /// In-flight commands' completion channels, keyed by tid.  The ack
+    /// echoes the command's tid, so any number may be outstanding.
+    pending_commands: BTreeMap<u64, oneshot::Sender<CommandResult>>,
+}
+
+/// Correlates `MMonGetVersion` queries with their replies: each query
+/// gets a fresh monotonically increasing handle, and the reply echoing
+/// that handle resolves the matching waiter.
+#[derive(Default)]
+pub struct MMonGetVersionCorrelator {
+    next_handle: AtomicU64,
+    pending: StdMutex<BTreeMap<u64, oneshot::Sender<MMonGetVersionReply>>>,
+}
+
+impl MMonGetVersionCorrelator {
+    /// Allocates a handle; the returned receiver resolves when the reply
+    /// carrying it arrives.
+    pub fn register(&self) -> (u64, oneshot::Receiver<MMonGetVersionReply>) {
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed) + 1;
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(handle, tx);
+        (handle, rx)
+    }
+
+    /// Routes `reply` to its waiter; returns false for unknown (e.g.
+    /// already timed-out) handles.
+    pub fn complete(&self, reply: MMonGetVersionReply) -> bool {
+        match self.pending.lock().unwrap().remove(&reply.handle) {
+            Some(tx) => tx.send(reply).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Drops the waiter for `handle`, if still registered.
+    pub fn cancel(&self, handle: u64) {
+        self.pending.lock().unwrap().remove(&handle);
+    }
+}
+
+/// A session with the monitor cluster.
+pub struct MonClient {
+    config: MonClientConfig,
+    connection: Mutex<Option<Arc<Connection>>>,
+    inner: Arc<StdMutex<Inner>>,
+    versions: Arc<MMonGetVersionCorrelator>,
+    /// The latest MonMap; `None` until the first one arrives after the
+    /// handshake.  A watch channel so waiters need not poll.
+    monmap_tx: watch::Sender<Option<Arc<MonMap>>>,
+    next_tid: AtomicU64,
+}
+
+impl MonClient {
+    pub fn new(config: MonClientConfig) -> Self {
+        MonClient {
+            config,
+            connection: Mutex::new(None),
+            inner: Arc::new(StdMutex::new(Inner::default())),
+            versions: Arc::new(MMonGetVersionCorrelator::default()),
+            monmap_tx: watch::channel(None).0,
+            next_tid: AtomicU64::new(1),
+        }
+    }
+
+    fn make_handler(
+        inner: Arc<StdMutex<Inner>>,
+        versions: Arc<MMonGetVersionCorrelator>,
+        monmap_tx: watch::Sender<Option<Arc<MonMap>>>,
+    ) -> MessageHandler {
+        Arc::new(move |msg: Message| {
+            let inner = inner.clone();
+            let versions = versions.clone();
+            let monmap_tx = monmap_tx.clone();
+            Box::pin(async move {
+                match msg.msg_type {
+                    CEPH_MSG_MON_MAP => {
+                        let mut front = msg.front.clone();
+                        let monmap = MMonMap::decode_front(&mut front)?;
+                        monmap_tx.send_replace(Some(Arc::new(monmap.monmap)));
+                    }
+                    MSG_MON_COMMAND_ACK => {
+                        let mut front = msg.front.clone();
+                        let ack = MMonCommandAck::decode_front(&mut front)?;
+                        let sender = inner.lock().unwrap().pending_commands.remove(&msg.tid);
+                        if let Some(sender) = sender {
+                            let _ = sender.send(CommandResult {
+                                code: ack.retval,
+                                status: ack.status,
+                                data: msg.data.clone(),
+                            });
+                        }
+                    }
+                    CEPH_MSG_MON_GET_VERSION_REPLY => {
+                        let mut front = msg.front.clone();
+                        let reply = MMonGetVersionReply::decode_front(&mut front)?;
+                        if !versions.complete(reply) {
+                            tracing::debug!("dropping version reply for unknown handle");
+                        }
+                    }
+                    other => {
+                        tracing::debug!("ignoring unhandled message type {other}");
+                    }
+                }
+                Ok(())
+            })
+        })
+    }
+
+    /// Connects to the first reachable monitor.
+    pub async fn connect(&self) -> Result<(), MonClientError> {
+        let mut last_err = MonClientError::NotConnected;
+        for addr in &self.config.mon_addrs {
+            let mut config = ConnectionConfig::new(self.config.auth_provider());
+            config.mode = self.config.mode;
+            match Connection::connect(addr.sockaddr, config).await {
+                Ok(connection) => {
+                    connection.set_handler(Self::make_handler(
+                        self.inner.clone(),
+                        self.versions.clone(),
+                        self.monmap_tx.clone(),
+                    ));
+                    *self.connection.lock().await = Some(Arc::new(connection));
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::debug!("monitor {addr:?} unreachable: {e}");
+                    last_err = e.into();
+                }
+            }
+        }
+        Err(last_err)
+    }
+
+    async fn connection(&self) -> Result<Arc<Connection>, MonClientError> {
+        self.connection
+            .lock()
+            .await
+            .clone()
+            .ok_or(MonClientError::NotConnected)
+    }
+
+    /// Issues a monitor command and waits for its ack, bounded by the
+    /// configured command timeout.
+    pub async fn send_command(
+        &self,
+        cmd: Vec<String>,
+        input: Option<Bytes>,
+    ) -> Result<CommandResult, MonClientError> {
+        let tid = self.next_tid.fetch_add(1, Ordering::Relaxed);
+        match tokio::time::timeout(self.config.command_timeout, self.command_inner(tid, cmd, input))
+            .await
+        {
+            Ok(result) => result,
+            Err(_) => {
+                self.inner.lock().unwrap().pending_commands.remove(&tid);
+                Err(MonClientError::Timeout)
+            }
+        }
+    }
+
+    /// Issues a monitor command without a timeout bound.  Commands issued
+    /// concurrently are correlated with their acks by tid, so any number
+    /// may be in flight on one connection.
+    pub async fn command_async(
+        &self,
+        cmd: Vec<String>,
+        input: Option<Bytes>,
+    ) -> Result<CommandResult, MonClientError> {
+        let tid = self.next_tid.fetch_add(1, Ordering::Relaxed);
+        self.command_inner(tid, cmd, input).await
+    }
+
+    /// Issues a command serviced by the active mgr (balancer,
+    /// pg_autoscaler, ...), routed there by the monitor.  Each command's
+    /// `prefix` gains a `mgr ` routing prefix unless it already has one.
+    /// Bounded by [`MonClientConfig::mgr_timeout`]; fails with
+    /// [`MonClientError::MgrNotAvailable`] when no mgr is active.
+    pub async fn mgr_command(
+        &self,
+        cmd: Vec<String>,
+        input: Option<Bytes>,
+    ) -> Result<CommandResult, MonClientError> {
+        let cmd = cmd.into_iter().map(|c| Self::route_to_mgr(&c)).collect();
+        let tid = self.next_tid.fetch_add(1, Ordering::Relaxed);
+        let result = match tokio::time::timeout(
+            self.config.mgr_timeout,
+            self.command_inner(tid, cmd, input),
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(_) => {
+                self.inner.lock().unwrap().pending_commands.remove(&tid);
+                return Err(MonClientError::Timeout);
+            }
+        };
+        Self::mgr_result(result)
+    }
+
+    /// Maps the monitor's "no active mgr" report (ENXIO) to
+    /// [`MonClientError::MgrNotAvailable`].
+    fn mgr_result(result: CommandResult) -> Result<CommandResult, MonClientError> {
+        const ENXIO: i32 = 6;
+        if result.code == -ENXIO {
+            return Err(MonClientError::MgrNotAvailable);
+        }
+        Ok(result)
+    }
+
+    /// Prepends the `mgr ` routing prefix to a JSON command's `prefix`
+    /// field.  Commands without a JSON prefix pass through untouched.
+    fn route_to_mgr(cmd: &str) -> String {
+        let Ok(mut value) = serde_json::from_str::<serde_json::Value>(cmd) else {
+            return cmd.to_string();
+        };
+        let Some(prefix) = value.get("prefix").and_then(|p| p.as_str()) else {
+            return cmd.to_string();
+        };
+        if !prefix.starts_with("mgr ") {
+            let routed = format!("mgr {prefix}");
+            value["prefix"] = serde_json::Value::String(routed);
+        }
+        value.to_string()
+    }
+
+    async fn command_inner(
+        &self,
+        tid: u64,
+        cmd: Vec<String>,
+        input: Option<Bytes>,
+    ) -> Result<CommandResult, MonClientError> {
+        let connection = self.connection().await?;
+        let (tx, rx) = oneshot::channel();
+        self.inner.lock().unwrap().pending_commands.insert(tid, tx);
+        let msg = MMonCommand::new(cmd).into_message(tid, input);
+        if let Err(e) = connection.send_message(msg).await {
+            self.inner.lock().unwrap().pending_commands.remove(&tid);
+            return Err(e.into());
+        }
+        rx.await.map_err(|_| MonClientError::NotConnected)
+    }
+
+    /// Asks the monitors for the newest and oldest committed version of
+    /// `what` (e.g. `"osdmap"`), returning `(newest, oldest)`.  Bounded
+    /// by the configured command timeout; concurrent queries are
+    /// correlated with their replies by handle.
+    pub async fn get_version(&self, what: &str) -> Result<(u64, u64), MonClientError> {
+        let connection = self.connection().await?;
+        let (handle, receiver) = self.versions.register();
+        let msg = MMonGetVersion {
+            handle,
+            what: what.to_string(),
+        }
+        .into_message();
+        if let Err(e) = connection.send_message(msg).await {
+            self.versions.cancel(handle);
+            return Err(e.into());
+        }
+        match tokio::time::timeout(self.config.command_timeout, receiver).await {
+            Ok(Ok(reply)) => Ok((reply.version, reply.oldest_version)),
+            Ok(Err(_)) => Err(MonClientError::NotConnected),
+            Err(_) => {
+                self.versions.cancel(handle);
+                Err(MonClientError::Timeout)
+            }
+        }
+    }
+
+    /// Issues the `mon status` command and parses the quorum report.
+    /// Fails with [`MonClientError::NoQuorum`] when the quorum is empty.
+    pub async fn mon_status(&self) -> Result<MonStatus, MonClientError> {
+        let cmd = serde_json::json!({"prefix": "mon status", "format": "json"});
+        let result = self.send_checked(cmd).await?;
+        let status: MonStatus = result.parse_json_reply()?;
+        if status.quorum.is_empty() {
+            return Err(MonClientError::NoQuorum);
+        }
+        Ok(status)
+    }
+
+    /// Issues the `df` command and parses its JSON report.
+    pub async fn get_df(&self) -> Result<DfResult, MonClientError> {
+        let cmd = serde_json::json!({"prefix": "df", "format": "json"});
+        let result = self.send_checked(cmd).await?;
+        result.parse_json_reply()
+    }
+
+    /// Creates a pool with `pg_num` placement groups.
+    pub async fn create_pool(&self, name: &str, pg_num: u32) -> Result<(), MonClientError> {
+        let cmd = serde_json::json!({
+            "prefix": "osd pool create",
+            "pool": name,
+            "pg_num": pg_num,
+        });
+        self.send_checked(cmd).await.map(drop)
+    }
+
+    /// Deletes a pool.  The monitor requires the pool name twice plus an
+    /// explicit confirmation flag; the caller is expected to have obtained
+    /// that confirmation from the user.
+    pub async fn delete_pool(&self, name: &str) -> Result<(), MonClientError> {
+        let cmd = serde_json::json!({
+            "prefix": "osd pool delete",
+            "pool": name,
+            "pool2": name,
+            "yes_i_really_really_mean_it": true,
+        });
+        self.send_checked(cmd).await.map(drop)
+    }
+
+    /// Lists the pool names.
+    pub async fn list_pools(&self) -> Result<Vec<String>, MonClientError> {
+        let cmd = serde_json::json!({"prefix": "osd pool ls", "format": "json"});
+        let result = self.send_checked(cmd).await?;
+        result.parse_json_reply()
+    }
+
+    /// Lists every CephX entity and its capabilities via `auth ls`.
+    pub async fn auth_list(&self) -> Result<Vec<AuthEntry>, MonClientError> {
+        #[derive(serde::Deserialize)]
+        struct AuthDump {
+            auth_dump: Vec<AuthEntry>,
+        }
+        let cmd = serde_json::json!({"prefix": "auth ls", "format": "json"});
+        let result = self.send_checked(cmd).await?;
+        result
+            .parse_json_reply::<AuthDump>()
+            .map(|dump| dump.auth_dump)
+    }
+
+    /// Looks up a single entity via `auth get`, which reports a one-entry
+    /// dump.
+    pub async fn auth_get(&self, entity: &str) -> Result<AuthEntry, MonClientError> {
+        let cmd = serde_json::json!({
+            "prefix": "auth get",
+            "entity": entity,
+            "format": "json",
+        });
+        let result = self.send_checked(cmd).await?;
+        result
+            .parse_json_reply::<Vec<AuthEntry>>()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                MonClientError::BadResponse(format!("empty auth dump for {entity}"))
+            })
+    }
+
+    /// Reads `key` from the monitors' distributed config-key store.
+    /// `Ok(None)` when the key is not set.
+    pub async fn get_config_key(&self, key: &str) -> Result<Option<Bytes>, MonClientError> {
+        let cmd = serde_json::json!({"prefix": "config-key get", "key": key});
+        let result = self.send_command(vec![cmd.to_string()], None).await?;
+        if result.code == -2 {
+            // ENOENT: absent keys are an expected outcome, not an error.
+            return Ok(None);
+        }
+        Self::checked(result).map(|result| Some(result.data))
+    }
+
+    /// Stores `value` under `key` in the config-key store, overwriting any
+    /// previous value.  The value travels as the command's input buffer,
+    /// so arbitrary binary data is fine.
+    pub async fn set_config_key(&self, key: &str, value: Bytes) -> Result<(), MonClientError> {
+        let cmd = serde_json::json!({"prefix": "config-key set", "key": key});
+        let result = self.send_command(vec![cmd.to_string()], Some(value)).await?;
+        Self::checked(result).map(drop)
+    }
+
+    /// Lists the config-key store keys starting with `prefix`; an empty
+    /// prefix lists everything.
+    pub async fn list_config_keys(&self, prefix: &str) -> Result<Vec<String>, MonClientError> {
+        let cmd = serde_json::json!({"prefix": "config-key ls", "format": "json"});
+        let result = self.send_checked(cmd).await?;
+        let keys: Vec<String> = result.parse_json_reply()?;
+        Ok(keys
+            .into_iter()
+            .filter(|key| key.starts_with(prefix))
+            .collect())
+    }
+
+    /// Sends one JSON command, turning a negative retval into
+    /// [`MonClientError::CommandFailed`].
+    async fn send_checked(
+        &self,
+        cmd: serde_json::Value,
+    ) -> Result<CommandResult, MonClientError> {
+        let result = self.send_command(vec![cmd.to_string()], None).await?;
+        Self::checked(result)
+    }
+
+    /// Turns a failed [`CommandResult`] into
+    /// [`MonClientError::CommandFailed`].
+    fn checked(result: CommandResult) -> Result<CommandResult, MonClientError> {
+        if !result.status_ok() {
+            return Err(MonClientError::CommandFailed {
+                code: result.code,
+                message: result.error_message().unwrap_or_default().to_string(),
+            });
+        }
+        Ok(result)
+    }
+
+    /// The latest monitor map, if one has been received.
+    pub fn monmap(&self) -> Option<Arc<MonMap>> {
+        self.monmap_tx.borrow().clone()
+    }
+
+    /// Waits until a MonMap has been received, up to `timeout`.  The map
+    /// arrives asynchronously right after the handshake, so this resolves
+    /// almost immediately on a healthy connection.
+    pub async fn wait_for_monmap(
+        &self,
+        timeout: Duration,
+    ) -> Result<Arc<MonMap>, MonClientError> {
+        let mut rx = self.monmap_tx.subscribe();
+        let wait = async move {
+            loop {
+                if let Some(map) = rx.borrow_and_update().clone() {
+                    return map;
+                }
+                // The sender lives as long as the client, so this cannot
+                // fail while anyone holds `&self`.
+                let _ = rx.changed().await;
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| MonClientError::Timeout)
+    }
+
+    /// The cluster fsid, waiting for the first MonMap if necessary.
+    pub async fn get_fsid(&self) -> Result<FsId, MonClientError> {
+        self.wait_for_monmap(self.config.command_timeout)
+            .await
+            .map(|m| m.fsid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use denc::Denc;
+
+    #[tokio::test]
+    async fn acks_resolve_pending_commands_by_tid() {
+        let inner = Arc::new(StdMutex::new(Inner::default()));
+        let handler = MonClient::make_handler(
+            inner.clone(),
+            Arc::new(MMonGetVersionCorrelator::default()),
+            watch::channel(None).0,
+        );
+
+        let mut receivers = Vec::new();
+        for tid in 1..=3u64 {
+            let (tx, rx) = oneshot::channel();
+            inner.lock().unwrap().pending_commands.insert(tid, tx);
+            receivers.push(rx);
+        }
+
+        // Acks arrive out of order; each must resolve its own command.
+        for tid in [2u64, 3, 1] {
+            let mut front = BytesMut::new();
+            0i32.encode(&mut front);
+            format!("ok {tid}").encode(&mut front);
+            let mut msg = Message::new(MSG_MON_COMMAND_ACK, front.freeze());
+            msg.tid = tid;
+            handler(msg).await.unwrap();
+        }
+
+        for (i, rx) in receivers.into_iter().enumerate() {
+            let result = rx.await.unwrap();
+            assert_eq!(result.code, 0);
+            assert_eq!(result.status, format!("ok {}", i + 1));
+        }
+        assert!(inner.lock().unwrap().pending_commands.is_empty());
+    }
+
+    #[tokio::test]
+    async fn concurrent_version_queries_get_their_own_replies() {
+        let versions = Arc::new(MMonGetVersionCorrelator::default());
+        let handler = MonClient::make_handler(
+            Arc::new(StdMutex::new(Inner::default())),
+            versions.clone(),
+            watch::channel(None).0,
+        );
+
+        // Two queries in flight at once, say for the osdmap and monmap.
+        let (osdmap_handle, osdmap_rx) = versions.register();
+        let (monmap_handle, monmap_rx) = versions.register();
+        assert_ne!(osdmap_handle, monmap_handle);
+
+        // Replies arrive in the opposite order; the handle routes each to
+        // its own waiter.
+        for (handle, version) in [(monmap_handle, 11u64), (osdmap_handle, 4242)] {
+            let reply = MMonGetVersionReply {
+                handle,
+                version,
+                oldest_version: 1,
+            };
+            let msg = Message::new(CEPH_MSG_MON_GET_VERSION_REPLY, reply.encode_front());
+            handler(msg).await.unwrap();
+        }
+
+        assert_eq!(osdmap_rx.await.unwrap().version, 4242);
+        assert_eq!(monmap_rx.await.unwrap().version, 11);
+
+        // A reply for a handle nobody waits on is dropped quietly.
+        assert!(!versions.complete(MMonGetVersionReply::default()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiting_for_the_monmap_is_bounded() {
+        let config = MonClientConfig::new(Vec::new(), "client.admin".parse().unwrap());
+        let client = MonClient::new(config);
+
+        // No map ever arrives: the wait times out.
+        let result = client.wait_for_monmap(Duration::from_millis(50)).await;
+        assert!(matches!(result, Err(MonClientError::Timeout)));
+
+        // A map arriving moments later wakes the waiter without polling.
+        let tx = client.monmap_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            tx.send_replace(Some(Arc::new(MonMap::default())));
+        });
+        let started = tokio::time::Instant::now();
+        let map = client
+            .wait_for_monmap(Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert!(started.elapsed() <= Duration::from_millis(10));
+        assert_eq!(client.monmap(), Some(map));
+    }
+
+    #[test]
+    fn mgr_commands_are_routed_and_checked() {
+        assert_eq!(
+            MonClient::route_to_mgr(r#"{"prefix":"balancer status"}"#),
+            r#"{"prefix":"mgr balancer status"}"#
+        );
+        // Already routed or non-JSON commands pass through untouched.
+        assert_eq!(
+            MonClient::route_to_mgr(r#"{"prefix":"mgr balancer status"}"#),
+            r#"{"prefix":"mgr balancer status"}"#
+        );
+        assert_eq!(MonClient::route_to_mgr("not json"), "not json");
+
+        assert!(matches!(
+            MonClient::mgr_result(CommandResult {
+                code: -6,
+                status: "no active mgr".to_string(),
+                data: Bytes::new(),
+            }),
+            Err(MonClientError::MgrNotAvailable)
+        ));
+        assert!(MonClient::mgr_result(CommandResult::default()).is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mgr_command_fails_fast_without_a_connection() {
+        let config = MonClientConfig::new(Vec::new(), "client.admin".parse().unwrap());
+        assert_eq!(config.mgr_timeout, Duration::from_secs(60));
+        let client = MonClient::new(config);
+        // No connection: the command fails before the mgr timeout bites.
+        let started = tokio::time::Instant::now();
+        let err = client
+            .mgr_command(vec![r#"{"prefix":"balancer status"}"#.to_string()], None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MonClientError::NotConnected));
+        assert!(started.elapsed() < Duration::from_secs(60));
+    }
+
+    fn write_test_keyring() -> std::path::PathBuf {
+        let key = auth::CryptoKey::new_aes(Bytes::from_static(&[9u8; 16])).unwrap();
+        let path = std::env::temp_dir().join(format!("monclient-env-{}.keyring", std::process::id()));
+        std::fs::write(
+            &path,
+            format!("[client.admin]\n\tkey = {}\n", key.to_base64()),
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn from_env_picks_up_every_variable() {
+        let keyring = write_test_keyring();
+        let vars = [
+            ("CEPH_MON_HOST", "10.0.0.1,v2:10.0.0.2:3301".to_string()),
+            ("CEPH_ENTITY", "client.admin".to_string()),
+            ("CEPH_AUTH_METHOD", "cephx".to_string()),
+            ("CEPH_KEYRING", keyring.display().to_string()),
+            ("CEPH_CONNECTION_MODE", "secure".to_string()),
+        ];
+        let config = MonClientConfig::from_env_with(|name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.clone())
+        })
+        .unwrap();
+
+        assert_eq!(config.mon_addrs.len(), 2);
+        // The first entry has no port: the msgr2 default is assumed.
+        assert_eq!(config.mon_addrs[0].sockaddr.port(), DEFAULT_MON_PORT);
+        assert_eq!(config.mon_addrs[1].sockaddr.port(), 3301);
+        assert_eq!(config.entity.to_string(), "client.admin");
+        assert_eq!(config.mode, ConnectionMode::Secure);
+        assert!(config
+            .keyring
+            .as_ref()
+            .is_some_and(|k| k.get_key("client.admin").is_some()));
+
+        std::fs::remove_file(keyring).unwrap();
+    }
+
+    #[test]
+    fn from_env_reports_all_missing_variables() {
+        let Err(err) = MonClientConfig::from_env_with(|name| {
+            (name == "CEPH_AUTH_METHOD").then(|| "cephx".to_string())
+        }) else {
+            panic!("expected missing variables to be an error");
+        };
+        match err {
+            MonClientError::BadConfig(msg) => {
+                assert!(msg.contains("CEPH_MON_HOST"));
+                assert!(msg.contains("CEPH_ENTITY"));
+                assert!(msg.contains("CEPH_KEYRING"));
+            }
+            other => panic!("expected a config error, got {other}"),
+        }
+    }
+
+    #[test]
+    fn from_env_reads_the_process_environment() {
+        // The only test touching the real environment, so no races with
+        // the injected-lookup tests above.
+        std::env::set_var("CEPH_MON_HOST", "10.0.0.1:3300");
+        std::env::set_var("CEPH_ENTITY", "client.admin");
+        let config = MonClientConfig::from_env().unwrap();
+        assert_eq!(config.mon_addrs.len(), 1);
+        assert!(config.keyring.is_none());
+        assert_eq!(config.mode, ConnectionMode::Crc);
+        std::env::remove_var("CEPH_MON_HOST");
+        std::env::remove_var("CEPH_ENTITY");
+    }
+}
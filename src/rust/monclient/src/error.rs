@@ -0,0 +1,40 @@
+//! Monitor client errors.
+
+use denc::RadosError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MonClientError {
+    #[error("not connected to any monitor")]
+    NotConnected,
+
+    #[error("timed out waiting for the monitor")]
+    Timeout,
+
+    #[error("command failed with {code}: {message}")]
+    CommandFailed { code: i32, message: String },
+
+    #[error("malformed monitor response: {0}")]
+    BadResponse(String),
+
+    #[error("bad configuration: {0}")]
+    BadConfig(String),
+
+    #[error("the monitors have no quorum")]
+    NoQuorum,
+
+    #[error("no active mgr to service the command")]
+    MgrNotAvailable,
+
+    #[error("unsupported paxos service version {version} (supported: {min}..={max})")]
+    UnsupportedVersion { version: u64, min: u64, max: u64 },
+
+    #[error(transparent)]
+    Messenger(#[from] msgr2::Error),
+
+    #[error(transparent)]
+    Encoding(#[from] RadosError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,15 @@
+//! Monitor client.
+//!
+//! Maintains a session with one monitor, issues commands and receives
+//! cluster maps, mirroring `src/mon/MonClient.{h,cc}`.
+
+pub mod client;
+pub mod error;
+pub mod messages;
+pub mod paxos_service_message;
+pub mod types;
+pub mod wait_helper;
+
+pub use client::{MonClient, MonClientConfig};
+pub use error::MonClientError;
+pub use types::{AuthEntry, CommandResult, DfResult};
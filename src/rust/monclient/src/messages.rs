@@ -0,0 +1,175 @@
+//! Monitor message payloads.
+//!
+//! Message type ids follow `include/msgr.h` / `src/messages`.
+
+use bytes::{Bytes, BytesMut};
+use denc::monmap::MonMap;
+use denc::types::FsId;
+use denc::{Denc, RadosError, VersionedEncode};
+use msgr2::Message;
+
+/// `CEPH_MSG_MON_MAP`
+pub const CEPH_MSG_MON_MAP: u16 = 4;
+/// `CEPH_MSG_MON_GET_VERSION`
+pub const CEPH_MSG_MON_GET_VERSION: u16 = 19;
+/// `CEPH_MSG_MON_GET_VERSION_REPLY`
+pub const CEPH_MSG_MON_GET_VERSION_REPLY: u16 = 20;
+/// `MSG_MON_COMMAND`
+pub const MSG_MON_COMMAND: u16 = 50;
+/// `MSG_MON_COMMAND_ACK`
+pub const MSG_MON_COMMAND_ACK: u16 = 51;
+/// `CEPH_MSG_OSD_MAP`
+pub const CEPH_MSG_OSD_MAP: u16 = 41;
+
+/// `MMonCommand`: a command addressed to the monitor cluster, as a list
+/// of JSON fragments plus optional input data.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MMonCommand {
+    pub fsid: FsId,
+    pub cmd: Vec<String>,
+}
+
+impl MMonCommand {
+    pub fn new(cmd: Vec<String>) -> Self {
+        MMonCommand {
+            fsid: FsId::ZERO,
+            cmd,
+        }
+    }
+
+    pub fn encode_front(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.fsid.encode(&mut buf);
+        self.cmd.encode(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn into_message(self, tid: u64, input: Option<Bytes>) -> Message {
+        let mut msg = Message::new(MSG_MON_COMMAND, self.encode_front());
+        msg.tid = tid;
+        msg.data = input.unwrap_or_default();
+        msg
+    }
+}
+
+/// `MMonCommandAck`: the reply carrying the return code, status string and
+/// any output payload in the message data section.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MMonCommandAck {
+    pub retval: i32,
+    pub status: String,
+}
+
+impl MMonCommandAck {
+    pub fn decode_front(front: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(MMonCommandAck {
+            retval: i32::decode(front)?,
+            status: String::decode(front)?,
+        })
+    }
+}
+
+/// `MMonMap`: a full monitor map.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MMonMap {
+    pub monmap: MonMap,
+}
+
+impl MMonMap {
+    pub fn decode_front(front: &mut Bytes) -> Result<Self, RadosError> {
+        // The map is nested in a length-prefixed bufferlist.
+        let mut inner = Bytes::decode(front)?;
+        Ok(MMonMap {
+            monmap: MonMap::decode_versioned(&mut inner)?,
+        })
+    }
+
+    pub fn encode_front(&self) -> Bytes {
+        let mut inner = BytesMut::new();
+        self.monmap.encode_versioned(&mut inner);
+        let mut buf = BytesMut::new();
+        inner.freeze().encode(&mut buf);
+        buf.freeze()
+    }
+}
+
+/// `MMonGetVersion`: asks for the newest and oldest committed version of
+/// one of the cluster maps (`what` is e.g. `"osdmap"`).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MMonGetVersion {
+    pub handle: u64,
+    pub what: String,
+}
+
+impl MMonGetVersion {
+    pub fn encode_front(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.handle.encode(&mut buf);
+        self.what.encode(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn decode_front(front: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(MMonGetVersion {
+            handle: u64::decode(front)?,
+            what: String::decode(front)?,
+        })
+    }
+
+    pub fn into_message(self) -> Message {
+        Message::new(CEPH_MSG_MON_GET_VERSION, self.encode_front())
+    }
+}
+
+/// `MMonGetVersionReply`: the newest and oldest committed versions, with
+/// the query's `handle` echoed back for correlation.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MMonGetVersionReply {
+    pub handle: u64,
+    pub version: u64,
+    pub oldest_version: u64,
+}
+
+impl MMonGetVersionReply {
+    pub fn encode_front(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.handle.encode(&mut buf);
+        self.version.encode(&mut buf);
+        self.oldest_version.encode(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn decode_front(front: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(MMonGetVersionReply {
+            handle: u64::decode(front)?,
+            version: u64::decode(front)?,
+            oldest_version: u64::decode(front)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mon_command_front_round_trips_through_ack() {
+        let cmd = MMonCommand::new(vec!["{\"prefix\": \"df\"}".to_string()]);
+        let front = cmd.encode_front();
+        assert!(!front.is_empty());
+
+        let mut ack_front = BytesMut::new();
+        (-22i32).encode(&mut ack_front);
+        "invalid command".to_string().encode(&mut ack_front);
+        let ack = MMonCommandAck::decode_front(&mut ack_front.freeze()).unwrap();
+        assert_eq!(ack.retval, -22);
+        assert_eq!(ack.status, "invalid command");
+    }
+
+    #[test]
+    fn monmap_message_round_trip() {
+        let msg = MMonMap::default();
+        let mut front = msg.encode_front();
+        assert_eq!(MMonMap::decode_front(&mut front).unwrap(), msg);
+    }
+}
@@ -0,0 +1,107 @@
+//! Common fields of messages addressed to a Paxos service.
+
+use bytes::Bytes;
+use denc::{Denc, RadosError};
+
+use crate::error::MonClientError;
+
+/// The version prologue shared by Paxos service messages: which committed
+/// version the sender has seen and which one it wants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PaxosServiceMessage {
+    pub version: u64,
+    pub deprecated_session_mon: i16,
+    pub deprecated_session_mon_tid: u64,
+}
+
+impl PaxosServiceMessage {
+    /// Checks the sender's version against the range this client
+    /// understands, for forward compatibility: a server running a newer
+    /// (or long-retired) protocol revision is rejected up front instead
+    /// of misdecoding the payload that follows.
+    pub fn check_version(&self, min_supported: u64, max_supported: u64) -> Result<(), MonClientError> {
+        if self.version < min_supported || self.version > max_supported {
+            return Err(MonClientError::UnsupportedVersion {
+                version: self.version,
+                min: min_supported,
+                max: max_supported,
+            });
+        }
+        Ok(())
+    }
+
+    /// Decodes the prologue and validates the version in one step; map
+    /// message decoders call this before touching the rest of the front.
+    pub fn decode_checked(
+        buf: &mut Bytes,
+        min_supported: u64,
+        max_supported: u64,
+    ) -> Result<Self, MonClientError> {
+        let msg = Self::decode(buf)?;
+        msg.check_version(min_supported, max_supported)?;
+        Ok(msg)
+    }
+}
+
+impl Denc for PaxosServiceMessage {
+    fn encode(&self, buf: &mut bytes::BytesMut) {
+        self.version.encode(buf);
+        self.deprecated_session_mon.encode(buf);
+        self.deprecated_session_mon_tid.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(PaxosServiceMessage {
+            version: u64::decode(buf)?,
+            deprecated_session_mon: i16::decode(buf)?,
+            deprecated_session_mon_tid: u64::decode(buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_range_is_enforced() {
+        let msg = PaxosServiceMessage {
+            version: 5,
+            ..Default::default()
+        };
+        msg.check_version(1, 10).unwrap();
+        msg.check_version(5, 5).unwrap();
+
+        // Too old for us.
+        assert!(matches!(
+            msg.check_version(6, 10),
+            Err(MonClientError::UnsupportedVersion {
+                version: 5,
+                min: 6,
+                max: 10
+            })
+        ));
+        // Too new for us.
+        assert!(matches!(
+            msg.check_version(1, 4),
+            Err(MonClientError::UnsupportedVersion { version: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn decode_checked_round_trip() {
+        let msg = PaxosServiceMessage {
+            version: 3,
+            deprecated_session_mon: -1,
+            deprecated_session_mon_tid: 0,
+        };
+        let mut raw = denc::encode_to_bytes(&msg);
+        assert_eq!(
+            PaxosServiceMessage::decode_checked(&mut raw, 1, 10).unwrap(),
+            msg
+        );
+
+        let mut raw = denc::encode_to_bytes(&msg);
+        assert!(PaxosServiceMessage::decode_checked(&mut raw, 4, 10).is_err());
+    }
+}
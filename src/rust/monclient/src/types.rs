@@ -0,0 +1,220 @@
+//! Monitor client value types.
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use serde::Deserialize;
+
+/// The outcome of a monitor command: return code, human-readable status
+/// and any output payload.
+#[derive(Debug, Clone, Default)]
+pub struct CommandResult {
+    pub code: i32,
+    pub status: String,
+    pub data: Bytes,
+}
+
+impl CommandResult {
+    /// Whether the command succeeded (a non-negative return code).
+    pub fn status_ok(&self) -> bool {
+        self.code >= 0
+    }
+
+    /// The monitor's error text for a failed command, if it provided any.
+    pub fn error_message(&self) -> Option<&str> {
+        if self.status_ok() {
+            return None;
+        }
+        let message = self.status.trim();
+        (!message.is_empty()).then_some(message)
+    }
+
+    /// Deserializes the output payload as JSON.  Callers issue the
+    /// command with `"format": "json"` and hand the result here instead
+    /// of poking at `data` by hand.
+    pub fn parse_json_reply<T: serde::de::DeserializeOwned>(
+        &self,
+    ) -> Result<T, crate::error::MonClientError> {
+        serde_json::from_slice(&self.data)
+            .map_err(|e| crate::error::MonClientError::BadResponse(e.to_string()))
+    }
+}
+
+/// Cluster-wide capacity, from the `df` command (`"stats"` object).
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+pub struct DfStats {
+    pub total_bytes: u64,
+    pub total_used_bytes: u64,
+    pub total_avail_bytes: u64,
+}
+
+/// Utilization of one pool (`"stats"` of a `"pools"` entry).
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+pub struct PoolDfStats {
+    #[serde(default)]
+    pub stored: u64,
+    #[serde(default)]
+    pub objects: u64,
+    #[serde(default)]
+    pub kb_used: u64,
+    #[serde(default)]
+    pub bytes_used: u64,
+    #[serde(default)]
+    pub max_avail: u64,
+}
+
+/// One pool in the `df` report.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct PoolDf {
+    pub name: String,
+    pub id: u64,
+    pub stats: PoolDfStats,
+}
+
+/// Quorum membership, from the `mon status` command.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct MonStatus {
+    /// Ranks of the monitors in quorum.
+    #[serde(default)]
+    pub quorum: Vec<u32>,
+    #[serde(default)]
+    pub quorum_names: Vec<String>,
+    /// Rank of the leader.
+    #[serde(default)]
+    pub leader: u32,
+    #[serde(default)]
+    pub leader_name: String,
+    /// Monitors known to the map but not in quorum.
+    #[serde(default)]
+    pub outside_quorum: Vec<String>,
+}
+
+/// One keyring entry from the `auth ls` / `auth get` dump: entity name,
+/// key type and the capability string per service.  The secret itself is
+/// not retained, only its type tag.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct AuthEntry {
+    pub entity: String,
+    /// Key type discriminator (`CEPH_CRYPTO_AES` in practice), recovered
+    /// from the base64 `"key"` field of the dump.
+    #[serde(default, rename = "key", deserialize_with = "key_type_from_base64")]
+    pub key_type: u16,
+    #[serde(default)]
+    pub caps: HashMap<String, String>,
+}
+
+fn key_type_from_base64<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let encoded = String::deserialize(deserializer)?;
+    auth::CryptoKey::from_base64(&encoded)
+        .map(|key| key.key_type)
+        .map_err(serde::de::Error::custom)
+}
+
+/// The parsed response of the `df` command.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct DfResult {
+    pub stats: DfStats,
+    pub pools: Vec<PoolDf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_result_accessors() {
+        let ok = CommandResult {
+            code: 0,
+            status: "".to_string(),
+            data: Bytes::from_static(br#"{"quorum": [0, 1]}"#),
+        };
+        assert!(ok.status_ok());
+        assert_eq!(ok.error_message(), None);
+        let status: MonStatus = ok.parse_json_reply().unwrap();
+        assert_eq!(status.quorum, vec![0, 1]);
+
+        let failed = CommandResult {
+            code: -22,
+            status: "  invalid command  ".to_string(),
+            data: Bytes::new(),
+        };
+        assert!(!failed.status_ok());
+        assert_eq!(failed.error_message(), Some("invalid command"));
+
+        let silent = CommandResult {
+            code: -1,
+            ..Default::default()
+        };
+        assert_eq!(silent.error_message(), None);
+
+        assert!(ok.parse_json_reply::<Vec<String>>().is_err());
+    }
+
+    #[test]
+    fn df_report_parses() {
+        let raw = r#"{
+            "stats": {"total_bytes": 100, "total_used_bytes": 40,
+                      "total_avail_bytes": 60, "num_osds": 3},
+            "pools": [
+                {"name": "rbd", "id": 1,
+                 "stats": {"stored": 10, "objects": 3, "max_avail": 50}}
+            ]
+        }"#;
+        let df: DfResult = serde_json::from_str(raw).unwrap();
+        assert_eq!(df.stats.total_bytes, 100);
+        assert_eq!(df.pools.len(), 1);
+        assert_eq!(df.pools[0].name, "rbd");
+        assert_eq!(df.pools[0].stats.objects, 3);
+        assert_eq!(df.pools[0].stats.kb_used, 0);
+    }
+
+    #[test]
+    fn auth_entry_parses() {
+        let key = auth::CryptoKey::new_aes(Bytes::from_static(&[7u8; 16]))
+            .unwrap()
+            .to_base64();
+        let raw = format!(
+            r#"{{
+                "entity": "client.admin",
+                "key": "{key}",
+                "caps": {{"mon": "allow *", "osd": "allow *"}}
+            }}"#
+        );
+        let entry: AuthEntry = serde_json::from_str(&raw).unwrap();
+        assert_eq!(entry.entity, "client.admin");
+        assert_eq!(entry.key_type, auth::types::CEPH_CRYPTO_AES);
+        assert_eq!(entry.caps["mon"], "allow *");
+        assert_eq!(entry.caps.len(), 2);
+
+        // `auth ls` omits the key for entities the caller may not read.
+        let bare: AuthEntry =
+            serde_json::from_str(r#"{"entity": "osd.0"}"#).unwrap();
+        assert_eq!(bare.key_type, 0);
+        assert!(bare.caps.is_empty());
+    }
+
+    #[test]
+    fn mon_status_parses() {
+        let raw = r#"{
+            "quorum": [0, 1, 2],
+            "quorum_names": ["a", "b", "c"],
+            "leader": 0,
+            "leader_name": "a",
+            "outside_quorum": ["d"],
+            "monmap": {"epoch": 5}
+        }"#;
+        let status: MonStatus = serde_json::from_str(raw).unwrap();
+        assert_eq!(status.quorum, [0, 1, 2]);
+        assert_eq!(status.quorum_names, ["a", "b", "c"]);
+        assert_eq!(status.leader, 0);
+        assert_eq!(status.leader_name, "a");
+        assert_eq!(status.outside_quorum, ["d"]);
+
+        // A monitor still probing reports no quorum at all.
+        let probing: MonStatus = serde_json::from_str(r#"{"quorum": []}"#).unwrap();
+        assert!(probing.quorum.is_empty());
+    }
+}
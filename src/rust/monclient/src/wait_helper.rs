@@ -0,0 +1,98 @@
+//! Waiting for asynchronous conditions.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::MonClientError;
+
+/// Polls `condition` until it returns `Some(value)` or the deadline (if
+/// any) passes.
+///
+/// Helpers built with [`WaitHelper::new`] wait forever; callers that need
+/// a bound use [`WaitHelper::new_with_timeout`] or
+/// [`WaitHelper::new_with_deadline`] and get
+/// [`MonClientError::Timeout`] on expiry.
+pub struct WaitHelper {
+    poll_interval: Duration,
+    deadline: Option<Instant>,
+}
+
+impl Default for WaitHelper {
+    fn default() -> Self {
+        WaitHelper {
+            poll_interval: Duration::from_millis(10),
+            deadline: None,
+        }
+    }
+}
+
+impl WaitHelper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bounds the wait by an absolute deadline.
+    pub fn new_with_deadline(deadline: Instant) -> Self {
+        WaitHelper {
+            deadline: Some(deadline),
+            ..Self::default()
+        }
+    }
+
+    /// Bounds the wait by a duration from now.
+    pub fn new_with_timeout(duration: Duration) -> Self {
+        Self::new_with_deadline(Instant::now() + duration)
+    }
+
+    pub async fn wait_for<T, F: FnMut() -> Option<T>>(
+        &self,
+        condition: F,
+    ) -> Result<T, MonClientError> {
+        match self.deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, self.poll(condition))
+                .await
+                .map_err(|_| MonClientError::Timeout),
+            None => Ok(self.poll(condition).await),
+        }
+    }
+
+    async fn poll<T, F: FnMut() -> Option<T>>(&self, mut condition: F) -> T {
+        loop {
+            if let Some(value) = condition() {
+                return value;
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn condition_satisfied_before_deadline() {
+        let helper = WaitHelper::new_with_timeout(Duration::from_secs(1));
+        let mut polls = 0;
+        let value = helper
+            .wait_for(|| {
+                polls += 1;
+                (polls >= 3).then_some(42)
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, 42);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unsatisfied_condition_times_out() {
+        let helper = WaitHelper::new_with_timeout(Duration::from_millis(50));
+        let result: Result<(), _> = helper.wait_for(|| None).await;
+        assert!(matches!(result, Err(MonClientError::Timeout)));
+
+        let helper = WaitHelper::new_with_deadline(Instant::now() + Duration::from_millis(50));
+        let result: Result<(), _> = helper.wait_for(|| None).await;
+        assert!(matches!(result, Err(MonClientError::Timeout)));
+    }
+}
@@ -0,0 +1,109 @@
+//! The fixed banner each side sends on connect, before any frames.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::error::Error;
+
+/// The msgr2 banner prefix (`CEPH_BANNER_V2_PREFIX`).
+pub const BANNER_PREFIX: &[u8] = b"ceph v2\n";
+
+/// Banner payload: two feature bitmaps, preceded by a `u16` payload length.
+pub const BANNER_PAYLOAD_LEN: usize = 16;
+
+/// Builds the banner to send: prefix, payload length, supported and
+/// required feature bits.
+pub fn build_banner(supported: u64, required: u64) -> Bytes {
+    let mut buf = BytesMut::with_capacity(BANNER_PREFIX.len() + 2 + BANNER_PAYLOAD_LEN);
+    buf.put_slice(BANNER_PREFIX);
+    buf.put_u16_le(BANNER_PAYLOAD_LEN as u16);
+    buf.put_u64_le(supported);
+    buf.put_u64_le(required);
+    buf.freeze()
+}
+
+/// Total size of a banner on the wire.
+pub const BANNER_LEN: usize = 8 + 2 + BANNER_PAYLOAD_LEN;
+
+/// Parses a peer banner, returning `(supported, required)` feature bits.
+pub fn parse_banner(raw: &[u8]) -> Result<(u64, u64), Error> {
+    if raw.len() < BANNER_LEN || &raw[..BANNER_PREFIX.len()] != BANNER_PREFIX {
+        return Err(Error::BadBanner);
+    }
+    let mut payload = &raw[BANNER_PREFIX.len()..];
+    let len = payload.get_u16_le() as usize;
+    if len < BANNER_PAYLOAD_LEN || payload.remaining() < len {
+        return Err(Error::BadBanner);
+    }
+    let supported = payload.get_u64_le();
+    let required = payload.get_u64_le();
+    Ok((supported, required))
+}
+
+/// The banner handshake as one unit: building our banner, parsing the
+/// peer's, and validating feature compatibility — so callers need not
+/// stitch the free functions together themselves.
+pub struct BannerExchange;
+
+impl BannerExchange {
+    /// The banner a connecting client sends first.
+    pub fn client_banner(supported: u64, required: u64) -> Bytes {
+        build_banner(supported, required)
+    }
+
+    /// Parses the server's banner into
+    /// `(peer_supported_features, peer_required_features)`.
+    pub fn parse_server_banner(data: &[u8]) -> Result<(u64, u64), Error> {
+        parse_banner(data)
+    }
+
+    /// Checks that every feature in `our_required` is offered in
+    /// `peer_supported`.  Run once in each direction for mutual
+    /// validation.
+    pub fn check_compatibility(our_required: u64, peer_supported: u64) -> Result<(), Error> {
+        if our_required & !peer_supported != 0 {
+            return Err(Error::IncompatibleFeatures {
+                required: our_required,
+                supported: peer_supported,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banner_round_trip() {
+        let raw = build_banner(0xff, 0x0f);
+        assert_eq!(parse_banner(&raw).unwrap(), (0xff, 0x0f));
+    }
+
+    #[test]
+    fn exchange_round_trip_and_compatibility() {
+        let raw = BannerExchange::client_banner(0xff, 0x03);
+        let (supported, required) = BannerExchange::parse_server_banner(&raw).unwrap();
+        assert_eq!((supported, required), (0xff, 0x03));
+
+        // The peer supports everything we require: compatible.
+        BannerExchange::check_compatibility(0x03, supported).unwrap();
+        // Requiring a bit the peer lacks is not.
+        let err = BannerExchange::check_compatibility(0x100, supported).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::IncompatibleFeatures {
+                required: 0x100,
+                supported: 0xff,
+            }
+        ));
+    }
+
+    #[test]
+    fn junk_banner_is_rejected() {
+        assert!(matches!(
+            parse_banner(b"GET / HTTP/1.1\r\n\r\n pad pad pad"),
+            Err(Error::BadBanner)
+        ));
+    }
+}
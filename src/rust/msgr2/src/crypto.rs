@@ -0,0 +1,251 @@
+//! Secure-mode payload encryption: AES-128-GCM keyed by the connection
+//! secret negotiated during authentication.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Key, Nonce};
+use bytes::Bytes;
+
+use crate::error::Error;
+
+/// 96-bit nonces, as required by GCM.
+pub const NONCE_LEN: usize = 12;
+/// The largest value the 96-bit nonce counter can hold.  The final value
+/// is never used: reusing a GCM nonce under the same key leaks the
+/// authentication subkey, so the connection errors out first.
+const NONCE_MAX: u128 = (1 << 96) - 1;
+/// AES-128 key length.
+pub const KEY_LEN: usize = 16;
+/// Layout of the connection secret: key, rx nonce, tx nonce.
+pub const CONNECTION_SECRET_LEN: usize = KEY_LEN + 2 * NONCE_LEN;
+
+/// Which cipher the connection secret keys, as negotiated in `AUTH_DONE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionSecretVersion {
+    Aes128Gcm,
+    Aes256Gcm,
+}
+
+impl ConnectionSecretVersion {
+    /// The key length the version demands.
+    pub fn key_len(&self) -> usize {
+        match self {
+            ConnectionSecretVersion::Aes128Gcm => 16,
+            ConnectionSecretVersion::Aes256Gcm => 32,
+        }
+    }
+
+    /// The full secret length: key plus the two directional nonces.
+    pub fn secret_len(&self) -> usize {
+        self.key_len() + 2 * NONCE_LEN
+    }
+}
+
+/// One direction of a secure connection.  The nonce is a 96-bit counter
+/// incremented after every payload.
+pub struct Aes128GcmEncryptor {
+    cipher: Aes128Gcm,
+    nonce: u128,
+}
+
+impl std::fmt::Debug for Aes128GcmEncryptor {
+    // Never expose key material; the nonce counter is safe to log.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Aes128GcmEncryptor(nonce={:#x})", self.nonce)
+    }
+}
+
+impl Aes128GcmEncryptor {
+    pub fn new(key: &[u8], initial_nonce: &[u8]) -> Result<Self, Error> {
+        if key.len() != KEY_LEN {
+            return Err(Error::CryptoError(format!(
+                "AES-128-GCM key must be {KEY_LEN} bytes, got {}",
+                key.len()
+            )));
+        }
+        if initial_nonce.len() != NONCE_LEN {
+            return Err(Error::CryptoError(format!(
+                "nonce must be {NONCE_LEN} bytes, got {}",
+                initial_nonce.len()
+            )));
+        }
+        let mut nonce_bytes = [0u8; 16];
+        nonce_bytes[..NONCE_LEN].copy_from_slice(initial_nonce);
+        Ok(Aes128GcmEncryptor {
+            cipher: Aes128Gcm::new(Key::<Aes128Gcm>::from_slice(key)),
+            nonce: u128::from_le_bytes(nonce_bytes),
+        })
+    }
+
+    fn nonce_bytes(&self) -> [u8; NONCE_LEN] {
+        let mut out = [0u8; NONCE_LEN];
+        out.copy_from_slice(&self.nonce.to_le_bytes()[..NONCE_LEN]);
+        out
+    }
+
+    fn advance_nonce(&mut self) {
+        self.nonce += 1;
+    }
+
+    /// How many more payloads this direction can protect before the nonce
+    /// counter runs out.
+    pub fn remaining_capacity(&self) -> u128 {
+        NONCE_MAX - self.nonce
+    }
+
+    /// Errors out instead of reusing nonce space once the counter is
+    /// spent.
+    fn check_nonce(&self) -> Result<(), Error> {
+        if self.nonce >= NONCE_MAX {
+            return Err(Error::NonceExhausted);
+        }
+        Ok(())
+    }
+
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Bytes, Error> {
+        self.check_nonce()?;
+        let nonce = self.nonce_bytes();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload::from(plaintext))
+            .map_err(|_| Error::CryptoError("encrypt failed".into()))?;
+        self.advance_nonce();
+        Ok(Bytes::from(ciphertext))
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Bytes, Error> {
+        self.check_nonce()?;
+        let nonce = self.nonce_bytes();
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload::from(ciphertext))
+            .map_err(|_| Error::CryptoError("decrypt failed (auth tag mismatch)".into()))?;
+        self.advance_nonce();
+        Ok(Bytes::from(plaintext))
+    }
+}
+
+/// Splits the connection secret from `AUTH_DONE` into the two directional
+/// encryptors: `(tx, rx)` from the client's point of view.  Assumes
+/// AES-128-GCM, the mode this messenger negotiates.
+pub fn parse_connection_secret(
+    secret: &[u8],
+) -> Result<(Aes128GcmEncryptor, Aes128GcmEncryptor), Error> {
+    parse_connection_secret_versioned(secret, ConnectionSecretVersion::Aes128Gcm)
+}
+
+/// Like [`parse_connection_secret`], but validates the key material
+/// against the declared cipher version before building anything.
+pub fn parse_connection_secret_versioned(
+    secret: &[u8],
+    version: ConnectionSecretVersion,
+) -> Result<(Aes128GcmEncryptor, Aes128GcmEncryptor), Error> {
+    let key_len = version.key_len();
+    if secret.len() < version.secret_len() {
+        return Err(Error::CryptoError(format!(
+            "key material too short: {} < {} for {version:?}",
+            secret.len(),
+            version.secret_len()
+        )));
+    }
+    if version != ConnectionSecretVersion::Aes128Gcm {
+        return Err(Error::CryptoError(format!(
+            "{version:?} connection secrets are not supported yet"
+        )));
+    }
+    let key = &secret[..key_len];
+    let rx_nonce = &secret[key_len..key_len + NONCE_LEN];
+    let tx_nonce = &secret[key_len + NONCE_LEN..version.secret_len()];
+    Ok((
+        Aes128GcmEncryptor::new(key, tx_nonce)?,
+        Aes128GcmEncryptor::new(key, rx_nonce)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let key = [0x42u8; KEY_LEN];
+        let nonce = [7u8; NONCE_LEN];
+        let mut tx = Aes128GcmEncryptor::new(&key, &nonce).unwrap();
+        let mut rx = Aes128GcmEncryptor::new(&key, &nonce).unwrap();
+        for payload in [&b"first frame"[..], b"second frame", b""] {
+            let ciphertext = tx.encrypt(payload).unwrap();
+            assert_eq!(rx.decrypt(&ciphertext).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn nonce_reuse_is_detected_by_receiver() {
+        let key = [0x42u8; KEY_LEN];
+        let nonce = [7u8; NONCE_LEN];
+        let mut tx = Aes128GcmEncryptor::new(&key, &nonce).unwrap();
+        let mut rx = Aes128GcmEncryptor::new(&key, &nonce).unwrap();
+        let first = tx.encrypt(b"one").unwrap();
+        rx.decrypt(&first).unwrap();
+        // Replaying the first ciphertext fails: rx has moved its nonce on.
+        assert!(rx.decrypt(&first).is_err());
+    }
+
+    #[test]
+    fn nonce_exhaustion_fails_gracefully() {
+        let key = [0x42u8; KEY_LEN];
+        // One payload away from the end of the nonce space.
+        let near_max = (NONCE_MAX - 1).to_le_bytes();
+        let mut tx = Aes128GcmEncryptor::new(&key, &near_max[..NONCE_LEN]).unwrap();
+        assert_eq!(tx.remaining_capacity(), 1);
+        tx.encrypt(b"last one").unwrap();
+        assert_eq!(tx.remaining_capacity(), 0);
+        assert!(matches!(
+            tx.encrypt(b"one too many"),
+            Err(Error::NonceExhausted)
+        ));
+        // And the counter no longer advances past the limit.
+        assert!(matches!(tx.decrypt(b"junk"), Err(Error::NonceExhausted)));
+    }
+
+    #[test]
+    fn connection_secret_length_is_validated() {
+        let secret = vec![1u8; CONNECTION_SECRET_LEN];
+        assert!(
+            parse_connection_secret_versioned(&secret, ConnectionSecretVersion::Aes128Gcm).is_ok()
+        );
+
+        // One byte short of the declared version's requirement.
+        let short = vec![1u8; CONNECTION_SECRET_LEN - 1];
+        match parse_connection_secret_versioned(&short, ConnectionSecretVersion::Aes128Gcm)
+            .unwrap_err()
+        {
+            Error::CryptoError(msg) => assert!(msg.contains("key material too short")),
+            other => panic!("expected a length error, got {other}"),
+        }
+
+        // An AES-128-sized secret is far too short for AES-256-GCM.
+        match parse_connection_secret_versioned(&secret, ConnectionSecretVersion::Aes256Gcm)
+            .unwrap_err()
+        {
+            Error::CryptoError(msg) => assert!(msg.contains("key material too short")),
+            other => panic!("expected a length error, got {other}"),
+        }
+        assert_eq!(ConnectionSecretVersion::Aes256Gcm.secret_len(), 32 + 24);
+    }
+
+    #[test]
+    fn connection_secret_split() {
+        let mut secret = vec![1u8; KEY_LEN];
+        secret.extend(std::iter::repeat_n(2, NONCE_LEN)); // rx
+        secret.extend(std::iter::repeat_n(3, NONCE_LEN)); // tx
+        let (mut client_tx, _client_rx) = parse_connection_secret(&secret).unwrap();
+        // The server derives the mirror image: its rx is our tx.
+        let (_server_tx, mut server_rx) = {
+            let mut mirrored = vec![1u8; KEY_LEN];
+            mirrored.extend(std::iter::repeat_n(3, NONCE_LEN));
+            mirrored.extend(std::iter::repeat_n(2, NONCE_LEN));
+            parse_connection_secret(&mirrored).unwrap()
+        };
+        let ciphertext = client_tx.encrypt(b"hello").unwrap();
+        assert_eq!(server_rx.decrypt(&ciphertext).unwrap(), &b"hello"[..]);
+    }
+}
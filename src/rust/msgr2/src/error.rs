@@ -0,0 +1,44 @@
+//! msgr2 errors.
+
+use auth::CephXError;
+use denc::RadosError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("bad banner from peer")]
+    BadBanner,
+
+    #[error("incompatible features: we require {required:#x}, peer supports {supported:#x}")]
+    IncompatibleFeatures { required: u64, supported: u64 },
+
+    #[error("unexpected frame {tag:?} in state {state}")]
+    UnexpectedFrame { tag: crate::frames::Tag, state: &'static str },
+
+    #[error("crc mismatch")]
+    CrcMismatch,
+
+    #[error("bad connection config: {0}")]
+    BadConfig(String),
+
+    #[error("crypto error: {0}")]
+    CryptoError(String),
+
+    #[error("gcm nonce space exhausted; the connection must be rekeyed")]
+    NonceExhausted,
+
+    #[error("authentication failed: {0}")]
+    Auth(#[from] CephXError),
+
+    #[error("connection is not ready")]
+    NotReady,
+
+    #[error("connection closed")]
+    Closed,
+
+    #[error(transparent)]
+    Encoding(#[from] RadosError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
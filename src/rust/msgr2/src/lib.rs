@@ -0,0 +1,22 @@
+//! Ceph messenger v2.
+//!
+//! The on-wire protocol spoken on port 3300: banner exchange, frame
+//! format, authentication handshake and (optionally) AES-GCM payload
+//! encryption.  The layering mirrors `src/msg/async/ProtocolV2` in the C++
+//! tree: [`frames`] is the framing layer, [`state_machine`] drives the
+//! handshake, and [`protocol`] owns the socket.
+
+pub mod banner;
+pub mod crypto;
+pub mod error;
+pub mod frames;
+pub mod message;
+pub mod protocol;
+pub mod state_machine;
+pub mod throttle;
+
+pub use error::Error;
+pub use frames::{Frame, Preamble, Tag};
+pub use message::Message;
+pub use protocol::Connection;
+pub use state_machine::{ConnectionConfig, StateKind, StateMachine};
@@ -0,0 +1,198 @@
+//! The message layer: typed payloads carried in `Tag::Message` frames.
+
+use std::sync::OnceLock;
+
+use bytes::{Bytes, BytesMut};
+use denc::{Denc, RadosError};
+
+use crate::error::Error;
+use crate::frames::{Frame, FrameTrait, Tag};
+
+/// Message priorities (`CEPH_MSG_PRIO_*`).
+pub const CEPH_MSG_PRIO_LOW: u8 = 64;
+pub const CEPH_MSG_PRIO_DEFAULT: u8 = 127;
+pub const CEPH_MSG_PRIO_HIGH: u8 = 196;
+pub const CEPH_MSG_PRIO_HIGHEST: u8 = 255;
+
+/// Header flag: the header carries a `data_crc` checksum of the data
+/// payload, for end-to-end integrity beyond the per-frame CRCs.
+pub const CEPH_MSG_HEADER_FLAG_CRC: u8 = 1 << 0;
+
+/// The lazily computed CRC32-C of the data payload.  Derived state:
+/// cloning drops it and it never participates in comparisons.
+#[derive(Debug, Default)]
+struct BodyCrcCache(OnceLock<u32>);
+
+impl Clone for BodyCrcCache {
+    fn clone(&self) -> Self {
+        BodyCrcCache::default()
+    }
+}
+
+impl PartialEq for BodyCrcCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// A Ceph message: the `ceph_msg_header2` fields plus the three payload
+/// buffers (front, middle, data).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Message {
+    pub seq: u64,
+    pub tid: u64,
+    pub msg_type: u16,
+    pub priority: u16,
+    pub version: u16,
+    pub flags: u8,
+    /// CRC32-C of `data` as carried in the header; only meaningful when
+    /// [`CEPH_MSG_HEADER_FLAG_CRC`] is set in `flags`.
+    pub data_crc: u32,
+    pub front: Bytes,
+    pub middle: Bytes,
+    pub data: Bytes,
+    body_crc: BodyCrcCache,
+}
+
+impl Message {
+    pub fn new(msg_type: u16, front: Bytes) -> Self {
+        Message {
+            msg_type,
+            priority: CEPH_MSG_PRIO_DEFAULT as u16,
+            ..Default::default()
+        }
+        .with_front(front)
+    }
+
+    fn with_front(mut self, front: Bytes) -> Self {
+        self.front = front;
+        self
+    }
+
+    fn header_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.seq.encode(&mut buf);
+        self.tid.encode(&mut buf);
+        self.msg_type.encode(&mut buf);
+        self.priority.encode(&mut buf);
+        self.version.encode(&mut buf);
+        self.flags.encode(&mut buf);
+        if self.flags & CEPH_MSG_HEADER_FLAG_CRC != 0 {
+            // Always send the checksum of what we actually carry.
+            self.body_checksum().encode(&mut buf);
+        }
+        buf.freeze()
+    }
+
+    /// The CRC32-C of the data payload, computed on first use and cached;
+    /// call only once the payload is final.
+    pub fn body_checksum(&self) -> u32 {
+        *self.body_crc.0.get_or_init(|| crc32c::crc32c(&self.data))
+    }
+
+    /// Whether the data payload matches the header's `data_crc`.
+    /// Messages sent without [`CEPH_MSG_HEADER_FLAG_CRC`] have nothing to
+    /// verify and pass trivially.
+    pub fn verify_checksum(&self) -> bool {
+        self.flags & CEPH_MSG_HEADER_FLAG_CRC == 0 || self.data_crc == self.body_checksum()
+    }
+
+    /// Reassembles a message from a `Tag::Message` frame.
+    pub fn from_frame(frame: &Frame) -> Result<Message, Error> {
+        if frame.tag() != Tag::Message {
+            return Err(Error::UnexpectedFrame {
+                tag: frame.tag(),
+                state: "message decode",
+            });
+        }
+        let mut header = frame.segments.first().cloned().ok_or_else(|| {
+            Error::Encoding(RadosError::Protocol("message frame without header".into()))
+        })?;
+        let seq = u64::decode(&mut header)?;
+        let tid = u64::decode(&mut header)?;
+        let msg_type = u16::decode(&mut header)?;
+        let priority = u16::decode(&mut header)?;
+        let version = u16::decode(&mut header)?;
+        let flags = u8::decode(&mut header)?;
+        let data_crc = if flags & CEPH_MSG_HEADER_FLAG_CRC != 0 {
+            u32::decode(&mut header)?
+        } else {
+            0
+        };
+        Ok(Message {
+            seq,
+            tid,
+            msg_type,
+            priority,
+            version,
+            flags,
+            data_crc,
+            front: frame.segments.get(1).cloned().unwrap_or_default(),
+            middle: frame.segments.get(2).cloned().unwrap_or_default(),
+            data: frame.segments.get(3).cloned().unwrap_or_default(),
+            body_crc: BodyCrcCache::default(),
+        })
+    }
+}
+
+impl FrameTrait for Message {
+    fn tag(&self) -> Tag {
+        Tag::Message
+    }
+
+    fn segments(&self) -> Vec<Bytes> {
+        vec![
+            self.header_bytes(),
+            self.front.clone(),
+            self.middle.clone(),
+            self.data.clone(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frames::create_frame_from_trait;
+
+    #[test]
+    fn message_frame_round_trip() {
+        let msg = Message {
+            seq: 3,
+            tid: 99,
+            msg_type: 42,
+            priority: CEPH_MSG_PRIO_DEFAULT as u16,
+            version: 1,
+            flags: 0,
+            front: Bytes::from_static(b"front"),
+            middle: Bytes::new(),
+            data: Bytes::from_static(b"data"),
+            ..Default::default()
+        };
+        let frame = create_frame_from_trait(&msg, 0);
+        let mut raw = frame.encode();
+        let parsed = Frame::decode(&mut raw).unwrap();
+        assert_eq!(Message::from_frame(&parsed).unwrap(), msg);
+    }
+
+    #[test]
+    fn tampered_data_fails_checksum_verification() {
+        let mut msg = Message::new(42, Bytes::from_static(b"front"));
+        msg.flags = CEPH_MSG_HEADER_FLAG_CRC;
+        msg.data = Bytes::from_static(b"payload under protection");
+
+        let frame = create_frame_from_trait(&msg, 0);
+        let mut raw = frame.encode();
+        let intact = Message::from_frame(&Frame::decode(&mut raw).unwrap()).unwrap();
+        assert_eq!(intact.data_crc, msg.body_checksum());
+        assert!(intact.verify_checksum());
+
+        // Corrupt the data segment after reassembly.
+        let mut tampered = intact.clone();
+        tampered.data = Bytes::from_static(b"payload under protectioN");
+        assert!(!tampered.verify_checksum());
+
+        // Without the flag there is nothing to check.
+        assert!(Message::new(42, Bytes::new()).verify_checksum());
+    }
+}
@@ -0,0 +1,417 @@
+//! The socket-owning connection layer.
+//!
+//! [`Connection`] runs the [`StateMachine`] over a TCP stream: it performs
+//! the banner exchange and handshake in [`Connection::connect`], then
+//! spawns a reader task that decodes incoming frames and hands messages to
+//! the registered handler.
+
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use bytes::{Bytes, BytesMut};
+use denc::RadosError;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::banner::BANNER_LEN;
+use crate::error::Error;
+use crate::frames::{create_frame_from_trait, Frame, Preamble, PREAMBLE_LEN};
+use crate::message::{Message, CEPH_MSG_PRIO_DEFAULT};
+use crate::state_machine::{ConnectionConfig, ConnectionEvent, StateMachine};
+
+/// Callback invoked for every incoming message once the connection is
+/// ready.
+pub type MessageHandler = Arc<
+    dyn Fn(Message) -> Pin<Box<dyn Future<Output = Result<(), RadosError>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// An established msgr2 connection.
+pub struct Connection {
+    peer: SocketAddr,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    state: Arc<Mutex<StateMachine>>,
+    handler: Arc<StdMutex<Option<MessageHandler>>>,
+    next_seq: AtomicU64,
+    /// Messages waiting for the wire, drained highest priority first.
+    send_queue: Mutex<BinaryHeap<QueuedMessage>>,
+    next_queue_seq: AtomicU64,
+}
+
+/// One queued message; ordered by priority, then FIFO within a priority
+/// level.
+struct QueuedMessage {
+    priority: u8,
+    queue_seq: u64,
+    msg: Message,
+}
+
+impl PartialEq for QueuedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedMessage {}
+
+impl PartialOrd for QueuedMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedMessage {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority
+            .cmp(&other.priority)
+            .then(other.queue_seq.cmp(&self.queue_seq))
+    }
+}
+
+/// Reads exactly one frame from the stream.
+async fn read_frame(stream: &mut OwnedReadHalf) -> Result<Frame, Error> {
+    let mut preamble_raw = [0u8; PREAMBLE_LEN];
+    stream.read_exact(&mut preamble_raw).await?;
+    let preamble = Preamble::decode(&preamble_raw)?;
+    if !preamble.crc_valid() {
+        return Err(Error::CrcMismatch);
+    }
+    let mut segments = Vec::with_capacity(preamble.num_segments as usize);
+    for i in 0..preamble.num_segments as usize {
+        let mut segment = vec![0u8; preamble.segment_lengths[i] as usize];
+        stream.read_exact(&mut segment).await?;
+        segments.push(Bytes::from(segment));
+    }
+    Ok(Frame { preamble, segments })
+}
+
+async fn write_frames(writer: &Mutex<OwnedWriteHalf>, frames: &[Frame]) -> Result<(), Error> {
+    if frames.is_empty() {
+        return Ok(());
+    }
+    let mut out = BytesMut::new();
+    for frame in frames {
+        out.extend_from_slice(&frame.encode());
+    }
+    let mut writer = writer.lock().await;
+    writer.write_all(&out).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+impl Connection {
+    /// Opens a TCP connection to `peer` and completes the msgr2 handshake.
+    pub async fn connect(peer: SocketAddr, config: ConnectionConfig) -> Result<Connection, Error> {
+        config.validate()?;
+        let stream = TcpStream::connect(peer).await?;
+        stream.set_nodelay(true)?;
+        let (mut reader, writer) = stream.into_split();
+        let writer = Arc::new(Mutex::new(writer));
+
+        let mut sm = StateMachine::new(config);
+        {
+            let mut w = writer.lock().await;
+            w.write_all(&sm.start()).await?;
+            w.flush().await?;
+        }
+
+        let mut banner_raw = [0u8; BANNER_LEN];
+        reader.read_exact(&mut banner_raw).await?;
+        let result = sm.handle_banner(&banner_raw)?;
+        write_frames(&writer, &result.send).await?;
+
+        loop {
+            let frame = read_frame(&mut reader).await?;
+            let result = sm.handle_frame(frame)?;
+            write_frames(&writer, &result.send).await?;
+            if matches!(result.event, Some(ConnectionEvent::Ready)) {
+                break;
+            }
+        }
+
+        let state = Arc::new(Mutex::new(sm));
+        let handler: Arc<StdMutex<Option<MessageHandler>>> = Arc::new(StdMutex::new(None));
+        let connection = Connection {
+            peer,
+            writer: writer.clone(),
+            state: state.clone(),
+            handler: handler.clone(),
+            next_seq: AtomicU64::new(1),
+            send_queue: Mutex::new(BinaryHeap::new()),
+            next_queue_seq: AtomicU64::new(0),
+        };
+
+        tokio::spawn(async move {
+            loop {
+                let frame = match read_frame(&mut reader).await {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        tracing::debug!("connection reader exiting: {e}");
+                        state.lock().await.close();
+                        return;
+                    }
+                };
+                let result = match state.lock().await.handle_frame(frame) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        tracing::warn!("protocol error from peer: {e}");
+                        state.lock().await.close();
+                        return;
+                    }
+                };
+                if write_frames(&writer, &result.send).await.is_err() {
+                    state.lock().await.close();
+                    return;
+                }
+                if let Some(ConnectionEvent::ReceivedMessage(msg)) = result.event {
+                    let callback = handler.lock().unwrap().clone();
+                    if let Some(callback) = callback {
+                        if let Err(e) = callback(msg).await {
+                            tracing::warn!("message handler failed: {e}");
+                        }
+                    } else {
+                        tracing::debug!("dropping message: no handler registered");
+                    }
+                }
+            }
+        });
+
+        Ok(connection)
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Registers the callback for incoming messages.
+    pub fn set_handler(&self, handler: MessageHandler) {
+        *self.handler.lock().unwrap() = Some(handler);
+    }
+
+    /// Registers `f` as the message callback, boxing the future
+    /// internally so callers need not spell out the [`MessageHandler`]
+    /// type.  Replaces any previously registered handler; messages
+    /// already being dispatched still complete on the old one.
+    pub fn set_message_handler<F, Fut>(&self, f: F)
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), RadosError>> + Send + 'static,
+    {
+        self.set_handler(Arc::new(move |msg| Box::pin(f(msg))));
+    }
+
+    /// Unregisters the message callback; subsequent messages are dropped
+    /// until a new one is installed.
+    pub fn clear_message_handler(&self) {
+        *self.handler.lock().unwrap() = None;
+    }
+
+    pub async fn is_ready(&self) -> bool {
+        self.state.lock().await.current_state_kind() == crate::state_machine::StateKind::Ready
+    }
+
+    /// Sends one message at the default priority.
+    pub async fn send_message(&self, msg: Message) -> Result<(), Error> {
+        self.send_message_with_priority(msg, CEPH_MSG_PRIO_DEFAULT)
+            .await
+    }
+
+    /// Queues one message at `priority` (higher is more urgent) and
+    /// flushes the queue.
+    ///
+    /// Priority is advisory: it only affects the order in which messages
+    /// that are queued concurrently reach the wire, so under congestion a
+    /// higher-priority message may still be reordered relative to ones
+    /// already written.
+    pub async fn send_message_with_priority(
+        &self,
+        mut msg: Message,
+        priority: u8,
+    ) -> Result<(), Error> {
+        if !self.is_ready().await {
+            return Err(Error::NotReady);
+        }
+        msg.priority = priority as u16;
+        self.send_queue.lock().await.push(QueuedMessage {
+            priority,
+            queue_seq: self.next_queue_seq.fetch_add(1, Ordering::Relaxed),
+            msg,
+        });
+        self.flush_send_queue().await
+    }
+
+    /// Writes `frame` to the wire immediately, bypassing the send queue:
+    /// the fast path for frames that must not wait behind large queued
+    /// messages, such as keepalives and other `CEPH_MSG_PRIO_HIGHEST`
+    /// traffic.  The writer lock is held for the whole frame, so
+    /// concurrent senders on either path never interleave bytes within a
+    /// frame — a high-priority frame lands between queued frames, never
+    /// inside one.
+    pub async fn send_high_priority(&self, frame: Frame) -> Result<(), Error> {
+        write_frames(&self.writer, std::slice::from_ref(&frame)).await
+    }
+
+    /// Drains the send queue to the wire, highest priority first.
+    async fn flush_send_queue(&self) -> Result<(), Error> {
+        loop {
+            let queued = self.send_queue.lock().await.pop();
+            let Some(mut queued) = queued else {
+                return Ok(());
+            };
+            queued.msg.seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+            let frame = create_frame_from_trait(&queued.msg, 0);
+            write_frames(&self.writer, std::slice::from_ref(&frame)).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth::provider::NoneAuthProvider;
+
+    /// A connection over a loopback socket that never completes the
+    /// handshake; enough to exercise handler registration.
+    async fn loopback_connection() -> Connection {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (_reader, writer) = stream.into_split();
+        let config = ConnectionConfig::new(Arc::new(NoneAuthProvider::new(
+            "client.admin".parse().unwrap(),
+        )));
+        Connection {
+            peer: addr,
+            writer: Arc::new(Mutex::new(writer)),
+            state: Arc::new(Mutex::new(StateMachine::new(config))),
+            handler: Arc::new(StdMutex::new(None)),
+            next_seq: AtomicU64::new(1),
+            send_queue: Mutex::new(BinaryHeap::new()),
+            next_queue_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Dispatches `msg` the way the reader task does.
+    async fn deliver(connection: &Connection, msg: Message) {
+        let callback = connection
+            .handler
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("a handler is registered");
+        callback(msg).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_high_priority_sends_do_not_interleave() {
+        use crate::frames::{FrameBuilder, Tag};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accept = tokio::spawn(async move { listener.accept().await.unwrap().0 });
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut server = accept.await.unwrap();
+        let (_reader, writer) = stream.into_split();
+        let config = ConnectionConfig::new(Arc::new(NoneAuthProvider::new(
+            "client.admin".parse().unwrap(),
+        )));
+        let connection = Arc::new(Connection {
+            peer: addr,
+            writer: Arc::new(Mutex::new(writer)),
+            state: Arc::new(Mutex::new(StateMachine::new(config))),
+            handler: Arc::new(StdMutex::new(None)),
+            next_seq: AtomicU64::new(1),
+            send_queue: Mutex::new(BinaryHeap::new()),
+            next_queue_seq: AtomicU64::new(0),
+        });
+
+        const SENDERS: usize = 8;
+        let mut tasks = Vec::new();
+        for i in 0..SENDERS as u8 {
+            // A keepalive on the fast path, racing...
+            let conn = connection.clone();
+            tasks.push(tokio::spawn(async move {
+                let mut builder = FrameBuilder::new(Tag::Keepalive2);
+                builder.add_segment(Bytes::from(vec![i; 512 * (i as usize + 1)]));
+                conn.send_high_priority(builder.build(0)).await.unwrap();
+            }));
+            // ...a message frame on the normal write path.
+            let conn = connection.clone();
+            tasks.push(tokio::spawn(async move {
+                let msg = Message::new(42, Bytes::from(vec![0xee; 4096]));
+                let frame = create_frame_from_trait(&msg, 0);
+                write_frames(&conn.writer, std::slice::from_ref(&frame))
+                    .await
+                    .unwrap();
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // Close the writer so the server sees EOF.
+        drop(connection);
+
+        let mut raw = Vec::new();
+        server.read_to_end(&mut raw).await.unwrap();
+        let mut buf = Bytes::from(raw);
+        let mut keepalives = Vec::new();
+        let mut messages = 0;
+        while !buf.is_empty() {
+            // Any interleaved bytes would corrupt a preamble or its CRC.
+            let frame = Frame::decode(&mut buf).unwrap();
+            match frame.tag() {
+                Tag::Keepalive2 => {
+                    let payload = frame.payload();
+                    let fill = payload[0];
+                    assert_eq!(payload.len(), 512 * (fill as usize + 1));
+                    assert!(payload.iter().all(|b| *b == fill), "torn frame payload");
+                    keepalives.push(fill);
+                }
+                Tag::Message => messages += 1,
+                other => panic!("unexpected frame tag {other:?}"),
+            }
+        }
+        keepalives.sort_unstable();
+        assert_eq!(keepalives, (0..SENDERS as u8).collect::<Vec<_>>());
+        assert_eq!(messages, SENDERS);
+    }
+
+    #[tokio::test]
+    async fn handlers_can_be_replaced_mid_session() {
+        let connection = loopback_connection().await;
+        let log = Arc::new(StdMutex::new(Vec::new()));
+
+        let sink = log.clone();
+        connection.set_message_handler(move |msg: Message| {
+            let sink = sink.clone();
+            async move {
+                sink.lock().unwrap().push(format!("first: {}", msg.msg_type));
+                Ok(())
+            }
+        });
+        deliver(&connection, Message::new(1, Bytes::new())).await;
+
+        // Replacing the handler redirects subsequent messages.
+        let sink = log.clone();
+        connection.set_message_handler(move |msg: Message| {
+            let sink = sink.clone();
+            async move {
+                sink.lock().unwrap().push(format!("second: {}", msg.msg_type));
+                Ok(())
+            }
+        });
+        deliver(&connection, Message::new(2, Bytes::new())).await;
+
+        connection.clear_message_handler();
+        assert!(connection.handler.lock().unwrap().is_none());
+        assert_eq!(*log.lock().unwrap(), ["first: 1", "second: 2"]);
+    }
+}
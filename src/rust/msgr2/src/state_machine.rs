@@ -0,0 +1,649 @@
+//! The client-side handshake state machine.
+//!
+//! Pure protocol logic, no I/O: the caller feeds it the peer banner and
+//! then frames, and sends whatever [`StateResult::send`] contains.  The
+//! state progression is
+//! `BannerConnecting → HelloConnecting → AuthConnecting →
+//! SessionConnecting → Ready`.
+
+use std::sync::Arc;
+
+use auth::provider::{AuthProvider, AuthStep};
+use bytes::{Bytes, BytesMut};
+use denc::entity_addr::EntityAddr;
+use denc::features::CEPH_FEATURES_SUPPORTED_DEFAULT;
+use denc::{Denc, VersionedEncode};
+
+use crate::banner;
+use crate::crypto::{parse_connection_secret, Aes128GcmEncryptor};
+use crate::error::Error;
+use crate::frames::{Frame, Preamble, Tag};
+use crate::message::Message;
+use crate::throttle::ThrottleConfig;
+
+/// Whether payloads are merely checksummed or fully encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionMode {
+    #[default]
+    Crc,
+    Secure,
+}
+
+/// Everything needed to open one connection.
+#[derive(Clone)]
+pub struct ConnectionConfig {
+    pub auth_provider: Arc<dyn AuthProvider>,
+    pub mode: ConnectionMode,
+    pub features_supported: u64,
+    pub features_required: u64,
+    pub throttle: ThrottleConfig,
+    /// Auth exchange attempts before giving up.
+    pub auth_max_retries: usize,
+}
+
+impl ConnectionConfig {
+    pub fn new(auth_provider: Arc<dyn AuthProvider>) -> Self {
+        ConnectionConfig {
+            auth_provider,
+            mode: ConnectionMode::Crc,
+            features_supported: CEPH_FEATURES_SUPPORTED_DEFAULT,
+            features_required: 0,
+            throttle: ThrottleConfig::default(),
+            auth_max_retries: DEFAULT_AUTH_MAX_RETRIES,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.features_required & !self.features_supported != 0 {
+            return Err(Error::IncompatibleFeatures {
+                required: self.features_required,
+                supported: self.features_supported,
+            });
+        }
+        if self.auth_max_retries == 0 {
+            return Err(Error::BadConfig(
+                "auth_max_retries must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The externally visible state of the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateKind {
+    BannerConnecting,
+    HelloConnecting,
+    AuthConnecting,
+    SessionConnecting,
+    Ready,
+    Failed,
+    Closed,
+}
+
+impl StateKind {
+    /// Whether the handshake has run its course: the connection is either
+    /// usable ([`StateKind::Ready`]) or permanently done for.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, StateKind::Ready | StateKind::Failed | StateKind::Closed)
+    }
+
+    /// Whether the peer is expected to talk in frames.  False while the
+    /// banner (raw bytes, not a frame) is outstanding and once the
+    /// connection is dead.
+    pub fn expects_frames(&self) -> bool {
+        matches!(
+            self,
+            StateKind::HelloConnecting
+                | StateKind::AuthConnecting
+                | StateKind::SessionConnecting
+                | StateKind::Ready
+        )
+    }
+
+    pub fn is_error_state(&self) -> bool {
+        matches!(self, StateKind::Failed | StateKind::Closed)
+    }
+}
+
+/// Default for [`ConnectionConfig::auth_max_retries`].
+pub const DEFAULT_AUTH_MAX_RETRIES: usize = 3;
+
+/// Per-state data.
+enum State {
+    BannerConnecting,
+    HelloConnecting,
+    AuthConnecting(AuthConnecting),
+    SessionConnecting,
+    Ready,
+    Failed,
+    Closed,
+}
+
+struct AuthConnecting {
+    retries: usize,
+    limit: usize,
+}
+
+/// Something the caller must act on after feeding input.
+#[derive(Debug, Default)]
+pub struct StateResult {
+    /// Frames to put on the wire, in order.
+    pub send: Vec<Frame>,
+    /// A state transition or received message, if any.
+    pub event: Option<ConnectionEvent>,
+}
+
+#[derive(Debug)]
+pub enum ConnectionEvent {
+    /// The handshake finished; messages may now be exchanged.
+    Ready,
+    /// A message arrived (only once Ready).
+    ReceivedMessage(Message),
+}
+
+pub struct StateMachine {
+    config: ConnectionConfig,
+    state: State,
+    server_addr: Option<EntityAddr>,
+    client_addr: Option<EntityAddr>,
+    client_cookie: u64,
+    server_cookie: u64,
+    global_id: u64,
+    tx_encryptor: Option<Aes128GcmEncryptor>,
+    rx_encryptor: Option<Aes128GcmEncryptor>,
+    /// Everything sent/received before auth completes, kept for the
+    /// signature check in `AUTH_SIGNATURE`.
+    pre_auth_buf: Vec<u8>,
+    /// Encoded frames captured by [`StateMachine::inject_frame_raw`],
+    /// drained by [`StateMachine::collect_outgoing`].
+    outgoing: Vec<Bytes>,
+}
+
+impl StateMachine {
+    pub fn new(config: ConnectionConfig) -> Self {
+        StateMachine {
+            config,
+            state: State::BannerConnecting,
+            server_addr: None,
+            client_addr: None,
+            client_cookie: rand::random(),
+            server_cookie: 0,
+            global_id: 0,
+            tx_encryptor: None,
+            rx_encryptor: None,
+            pre_auth_buf: Vec::new(),
+            outgoing: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ConnectionConfig {
+        &self.config
+    }
+
+    pub fn current_state_kind(&self) -> StateKind {
+        match self.state {
+            State::BannerConnecting => StateKind::BannerConnecting,
+            State::HelloConnecting => StateKind::HelloConnecting,
+            State::AuthConnecting(_) => StateKind::AuthConnecting,
+            State::SessionConnecting => StateKind::SessionConnecting,
+            State::Ready => StateKind::Ready,
+            State::Failed => StateKind::Failed,
+            State::Closed => StateKind::Closed,
+        }
+    }
+
+    pub fn global_id(&self) -> u64 {
+        self.global_id
+    }
+
+    pub fn set_addrs(&mut self, client: EntityAddr, server: EntityAddr) {
+        self.client_addr = Some(client);
+        self.server_addr = Some(server);
+    }
+
+    /// The banner to send as soon as the socket opens.
+    pub fn start(&self) -> Bytes {
+        banner::build_banner(
+            self.config.features_supported,
+            self.config.features_required,
+        )
+    }
+
+    /// Consumes the peer's banner; on success the machine advances to
+    /// `HelloConnecting` and returns the hello frame to send.
+    pub fn handle_banner(&mut self, raw: &[u8]) -> Result<StateResult, Error> {
+        if !matches!(self.state, State::BannerConnecting) {
+            return Err(Error::NotReady);
+        }
+        let (peer_supported, peer_required) = banner::BannerExchange::parse_server_banner(raw)?;
+        if let Err(e) =
+            banner::BannerExchange::check_compatibility(peer_required, self.config.features_supported)
+                .and_then(|()| {
+                    banner::BannerExchange::check_compatibility(
+                        self.config.features_required,
+                        peer_supported,
+                    )
+                })
+        {
+            self.state = State::Failed;
+            return Err(e);
+        }
+        self.pre_auth_buf.extend_from_slice(raw);
+        self.state = State::HelloConnecting;
+        Ok(StateResult {
+            send: vec![self.hello_frame()],
+            event: None,
+        })
+    }
+
+    fn hello_frame(&self) -> Frame {
+        let mut payload = BytesMut::new();
+        self.config
+            .auth_provider
+            .entity_name()
+            .entity_type
+            .encode(&mut payload);
+        // Our address as we believe the peer sees it, if known.
+        if let Some(addr) = &self.client_addr {
+            1u8.encode(&mut payload);
+            addr.encode_versioned(&mut payload);
+        } else {
+            0u8.encode(&mut payload);
+        }
+        single_segment_frame(Tag::HelloFrame, payload.freeze())
+    }
+
+    fn auth_request_frame(&self) -> Result<Frame, Error> {
+        let mut payload = BytesMut::new();
+        self.config.auth_provider.method().encode(&mut payload);
+        let initial = self.config.auth_provider.build_initial_request()?;
+        initial.encode(&mut payload);
+        Ok(single_segment_frame(Tag::AuthRequest, payload.freeze()))
+    }
+
+    fn client_ident_frame(&self) -> Frame {
+        let mut payload = BytesMut::new();
+        self.client_cookie.encode(&mut payload);
+        self.global_id.encode(&mut payload);
+        self.config.features_supported.encode(&mut payload);
+        single_segment_frame(Tag::ClientIdent, payload.freeze())
+    }
+
+    /// Feeds one frame from the peer into the machine.
+    pub fn handle_frame(&mut self, frame: Frame) -> Result<StateResult, Error> {
+        let kind = self.current_state_kind();
+        if kind.is_error_state() {
+            return Err(Error::Closed);
+        }
+        if !kind.expects_frames() {
+            return Err(Error::UnexpectedFrame {
+                tag: frame.tag(),
+                state: "BannerConnecting",
+            });
+        }
+        match &mut self.state {
+            State::HelloConnecting => self.handle_hello_reply(frame),
+            State::AuthConnecting(_) => self.handle_auth_frame(frame),
+            State::SessionConnecting => self.handle_session_frame(frame),
+            State::Ready => self.handle_ready_frame(frame),
+            State::BannerConnecting | State::Failed | State::Closed => unreachable!(),
+        }
+    }
+
+    fn handle_hello_reply(&mut self, frame: Frame) -> Result<StateResult, Error> {
+        if frame.tag() != Tag::HelloFrame {
+            return Err(Error::UnexpectedFrame {
+                tag: frame.tag(),
+                state: "HelloConnecting",
+            });
+        }
+        self.pre_auth_buf.extend_from_slice(&frame.encode());
+        self.state = State::AuthConnecting(AuthConnecting {
+            retries: 0,
+            limit: self.config.auth_max_retries,
+        });
+        Ok(StateResult {
+            send: vec![self.auth_request_frame()?],
+            event: None,
+        })
+    }
+
+    fn handle_auth_frame(&mut self, frame: Frame) -> Result<StateResult, Error> {
+        self.pre_auth_buf.extend_from_slice(&frame.encode());
+        match frame.tag() {
+            Tag::AuthReplyMore => {
+                let mut payload = frame.payload();
+                match self.config.auth_provider.handle_reply(&mut payload)? {
+                    AuthStep::Continue(more) => Ok(StateResult {
+                        send: vec![single_segment_frame(Tag::AuthRequestMore, more)],
+                        event: None,
+                    }),
+                    AuthStep::Done {
+                        global_id,
+                        connection_secret,
+                    } => self.finish_auth(global_id, connection_secret),
+                }
+            }
+            Tag::AuthDone => {
+                let mut payload = frame.payload();
+                let global_id = u64::decode(&mut payload)?;
+                let connection_secret = Bytes::decode(&mut payload)?;
+                self.finish_auth(global_id, connection_secret)
+            }
+            Tag::AuthBadMethod => {
+                let State::AuthConnecting(auth) = &mut self.state else {
+                    unreachable!("handle_auth_frame outside AuthConnecting");
+                };
+                auth.retries += 1;
+                if auth.retries >= auth.limit {
+                    self.state = State::Failed;
+                    return Err(Error::Auth(auth::CephXError::AccessDenied(
+                        "server rejected all auth attempts".into(),
+                    )));
+                }
+                Ok(StateResult {
+                    send: vec![self.auth_request_frame()?],
+                    event: None,
+                })
+            }
+            tag => Err(Error::UnexpectedFrame {
+                tag,
+                state: "AuthConnecting",
+            }),
+        }
+    }
+
+    fn finish_auth(
+        &mut self,
+        global_id: u64,
+        connection_secret: Bytes,
+    ) -> Result<StateResult, Error> {
+        self.global_id = global_id;
+        if matches!(self.config.mode, ConnectionMode::Secure) {
+            let (tx, rx) = parse_connection_secret(&connection_secret)?;
+            self.tx_encryptor = Some(tx);
+            self.rx_encryptor = Some(rx);
+        }
+        self.state = State::SessionConnecting;
+        Ok(StateResult {
+            send: vec![self.client_ident_frame()],
+            event: None,
+        })
+    }
+
+    fn handle_session_frame(&mut self, frame: Frame) -> Result<StateResult, Error> {
+        match frame.tag() {
+            Tag::ServerIdent => {
+                let mut payload = frame.payload();
+                self.server_cookie = u64::decode(&mut payload)?;
+                self.pre_auth_buf.clear();
+                self.state = State::Ready;
+                Ok(StateResult {
+                    send: Vec::new(),
+                    event: Some(ConnectionEvent::Ready),
+                })
+            }
+            Tag::IdentMissingFeatures => {
+                self.state = State::Failed;
+                let mut payload = frame.payload();
+                let missing = u64::decode(&mut payload).unwrap_or(0);
+                Err(Error::IncompatibleFeatures {
+                    required: missing,
+                    supported: self.config.features_supported,
+                })
+            }
+            tag => Err(Error::UnexpectedFrame {
+                tag,
+                state: "SessionConnecting",
+            }),
+        }
+    }
+
+    fn handle_ready_frame(&mut self, frame: Frame) -> Result<StateResult, Error> {
+        match frame.tag() {
+            Tag::Message => {
+                let msg = Message::from_frame(&frame)?;
+                Ok(StateResult {
+                    send: Vec::new(),
+                    event: Some(ConnectionEvent::ReceivedMessage(msg)),
+                })
+            }
+            Tag::Keepalive2 => {
+                // Echo the peer's timestamp back.
+                let mut preamble = Preamble::new(Tag::Keepalive2Ack);
+                preamble.num_segments = 1;
+                preamble.segment_lengths[0] = frame.payload().len() as u32;
+                preamble.set_crc();
+                Ok(StateResult {
+                    send: vec![Frame {
+                        preamble,
+                        segments: vec![frame.payload()],
+                    }],
+                    event: None,
+                })
+            }
+            Tag::Keepalive2Ack | Tag::Ack => Ok(StateResult::default()),
+            tag => Err(Error::UnexpectedFrame {
+                tag,
+                state: "Ready",
+            }),
+        }
+    }
+
+    /// Rewinds the machine to `BannerConnecting` so the same instance can
+    /// drive a reconnect, keeping the configuration and any learned
+    /// addresses.  With `preserve_cookies` the client and server cookies
+    /// survive too, for session resumption; otherwise a fresh client
+    /// cookie is drawn.  Pre-auth buffers and encryption contexts from the
+    /// previous attempt are always discarded.
+    pub fn reset_for_reconnect(&mut self, preserve_cookies: bool) {
+        self.state = State::BannerConnecting;
+        if !preserve_cookies {
+            self.client_cookie = rand::random();
+            self.server_cookie = 0;
+        }
+        self.global_id = 0;
+        self.tx_encryptor = None;
+        self.rx_encryptor = None;
+        self.pre_auth_buf.clear();
+    }
+
+    /// Marks the connection closed; subsequent frames are rejected.
+    pub fn close(&mut self) {
+        self.state = State::Closed;
+    }
+
+    /// Decodes `raw` as one frame and feeds it in as if it had arrived on
+    /// the wire, capturing whatever the machine wants to send.  Together
+    /// with [`StateMachine::collect_outgoing`] this exercises individual
+    /// state transitions without a socket or a [`crate::Connection`].
+    pub fn inject_frame_raw(&mut self, raw: &[u8]) -> Result<StateResult, Error> {
+        let frame = Frame::decode(&mut Bytes::copy_from_slice(raw))?;
+        let result = self.handle_frame(frame)?;
+        self.outgoing.extend(result.send.iter().map(Frame::encode));
+        Ok(result)
+    }
+
+    /// Drains the wire-format frames captured since the last call, oldest
+    /// first.
+    pub fn collect_outgoing(&mut self) -> Vec<Bytes> {
+        std::mem::take(&mut self.outgoing)
+    }
+}
+
+/// Helper to build a single-segment control frame.
+pub(crate) fn single_segment_frame(tag: Tag, payload: Bytes) -> Frame {
+    let mut preamble = Preamble::new(tag);
+    preamble.num_segments = 1;
+    preamble.segment_lengths[0] = payload.len() as u32;
+    preamble.set_crc();
+    Frame {
+        preamble,
+        segments: vec![payload],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth::provider::NoneAuthProvider;
+
+    pub(crate) fn test_config() -> ConnectionConfig {
+        ConnectionConfig::new(Arc::new(NoneAuthProvider::new(
+            "client.admin".parse().unwrap(),
+        )))
+    }
+
+    fn server_frame(tag: Tag, payload: Bytes) -> Frame {
+        single_segment_frame(tag, payload)
+    }
+
+    /// Drives a machine through the full happy-path handshake.
+    pub(crate) fn drive_to_ready(sm: &mut StateMachine) {
+        let banner = banner::build_banner(CEPH_FEATURES_SUPPORTED_DEFAULT, 0);
+        let result = sm.handle_banner(&banner).unwrap();
+        assert_eq!(result.send[0].tag(), Tag::HelloFrame);
+
+        let result = sm
+            .handle_frame(server_frame(Tag::HelloFrame, Bytes::new()))
+            .unwrap();
+        assert_eq!(result.send[0].tag(), Tag::AuthRequest);
+
+        let mut done = BytesMut::new();
+        77u64.encode(&mut done); // global_id
+        Bytes::new().encode(&mut done); // connection secret (crc mode)
+        let result = sm
+            .handle_frame(server_frame(Tag::AuthDone, done.freeze()))
+            .unwrap();
+        assert_eq!(result.send[0].tag(), Tag::ClientIdent);
+
+        let mut ident = BytesMut::new();
+        0xabcdu64.encode(&mut ident); // server cookie
+        let result = sm
+            .handle_frame(server_frame(Tag::ServerIdent, ident.freeze()))
+            .unwrap();
+        assert!(matches!(result.event, Some(ConnectionEvent::Ready)));
+    }
+
+    #[test]
+    fn happy_path_handshake() {
+        let mut sm = StateMachine::new(test_config());
+        assert_eq!(sm.current_state_kind(), StateKind::BannerConnecting);
+        drive_to_ready(&mut sm);
+        assert_eq!(sm.current_state_kind(), StateKind::Ready);
+        assert_eq!(sm.global_id(), 77);
+    }
+
+    #[test]
+    fn bad_method_retries_then_fails() {
+        let mut sm = StateMachine::new(test_config());
+        let banner = banner::build_banner(CEPH_FEATURES_SUPPORTED_DEFAULT, 0);
+        sm.handle_banner(&banner).unwrap();
+        sm.handle_frame(server_frame(Tag::HelloFrame, Bytes::new()))
+            .unwrap();
+        for _ in 0..DEFAULT_AUTH_MAX_RETRIES - 1 {
+            let result = sm
+                .handle_frame(server_frame(Tag::AuthBadMethod, Bytes::new()))
+                .unwrap();
+            assert_eq!(result.send[0].tag(), Tag::AuthRequest);
+        }
+        assert!(sm
+            .handle_frame(server_frame(Tag::AuthBadMethod, Bytes::new()))
+            .is_err());
+        assert_eq!(sm.current_state_kind(), StateKind::Failed);
+    }
+
+    #[test]
+    fn injected_frames_drive_the_machine_without_io() {
+        let mut sm = StateMachine::new(test_config());
+        let banner = banner::build_banner(CEPH_FEATURES_SUPPORTED_DEFAULT, 0);
+        sm.handle_banner(&banner).unwrap();
+        assert!(sm.collect_outgoing().is_empty());
+
+        let hello = server_frame(Tag::HelloFrame, Bytes::new()).encode();
+        sm.inject_frame_raw(&hello).unwrap();
+        assert_eq!(sm.current_state_kind(), StateKind::AuthConnecting);
+
+        let outgoing = sm.collect_outgoing();
+        assert_eq!(outgoing.len(), 1);
+        let sent = Frame::decode(&mut outgoing[0].clone()).unwrap();
+        assert_eq!(sent.tag(), Tag::AuthRequest);
+        // collect_outgoing drains: a second call comes back empty.
+        assert!(sm.collect_outgoing().is_empty());
+
+        // Undecodable input is rejected before it reaches the machine.
+        assert!(sm.inject_frame_raw(&[0u8; 8]).is_err());
+        assert_eq!(sm.current_state_kind(), StateKind::AuthConnecting);
+    }
+
+    #[test]
+    fn state_kind_predicates() {
+        assert!(StateKind::Ready.is_terminal());
+        assert!(StateKind::Failed.is_terminal());
+        assert!(StateKind::Closed.is_terminal());
+        assert!(!StateKind::AuthConnecting.is_terminal());
+
+        assert!(!StateKind::BannerConnecting.expects_frames());
+        assert!(StateKind::Ready.expects_frames());
+        assert!(!StateKind::Closed.expects_frames());
+
+        assert!(StateKind::Failed.is_error_state());
+        assert!(!StateKind::Ready.is_error_state());
+    }
+
+    #[test]
+    fn reset_allows_a_second_handshake() {
+        let mut sm = StateMachine::new(test_config());
+        drive_to_ready(&mut sm);
+        let resumable_cookie = sm.client_cookie;
+
+        sm.close();
+        sm.reset_for_reconnect(true);
+        assert_eq!(sm.current_state_kind(), StateKind::BannerConnecting);
+        assert_eq!(sm.client_cookie, resumable_cookie);
+        assert_eq!(sm.global_id(), 0);
+        drive_to_ready(&mut sm);
+        assert_eq!(sm.current_state_kind(), StateKind::Ready);
+
+        sm.reset_for_reconnect(false);
+        assert_eq!(sm.server_cookie, 0);
+        drive_to_ready(&mut sm);
+        assert_eq!(sm.current_state_kind(), StateKind::Ready);
+    }
+
+    #[test]
+    fn auth_retry_limit_is_configurable() {
+        let mut config = test_config();
+        config.auth_max_retries = 1;
+        config.validate().unwrap();
+        let mut sm = StateMachine::new(config);
+        let banner = banner::build_banner(CEPH_FEATURES_SUPPORTED_DEFAULT, 0);
+        sm.handle_banner(&banner).unwrap();
+        sm.handle_frame(server_frame(Tag::HelloFrame, Bytes::new()))
+            .unwrap();
+        assert!(sm
+            .handle_frame(server_frame(Tag::AuthBadMethod, Bytes::new()))
+            .is_err());
+        assert_eq!(sm.current_state_kind(), StateKind::Failed);
+
+        let mut config = test_config();
+        config.auth_max_retries = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn message_delivery_when_ready() {
+        let mut sm = StateMachine::new(test_config());
+        drive_to_ready(&mut sm);
+        let msg = Message::new(42, Bytes::from_static(b"payload"));
+        let frame = crate::frames::create_frame_from_trait(&msg, 0);
+        let result = sm.handle_frame(frame).unwrap();
+        match result.event {
+            Some(ConnectionEvent::ReceivedMessage(received)) => {
+                assert_eq!(received.msg_type, 42)
+            }
+            other => panic!("expected message, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Flow-control limits for a connection.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Caps on in-flight traffic, enforced by the session layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottleConfig {
+    pub max_bytes_in_flight: u64,
+    pub max_messages_in_flight: u64,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        ThrottleConfig {
+            max_bytes_in_flight: 100 << 20, // 100 MiB, matching osd_client_message_size_cap
+            max_messages_in_flight: 256,
+        }
+    }
+}
+
+/// How often waiting acquirers re-check the bucket.
+const REFILL_TICK: Duration = Duration::from_millis(10);
+
+/// A token bucket: capacity-bounded credit refilled at a steady rate.
+///
+/// Shared by send and receive throttling; tokens typically stand for
+/// bytes.  The bucket starts full.
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: u64,
+    available: AtomicU64,
+    /// Tokens credited per second.
+    refill_rate: u64,
+    last_refill: StdMutex<Instant>,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u64, refill_rate: u64) -> Self {
+        TokenBucket {
+            capacity,
+            available: AtomicU64::new(capacity),
+            refill_rate,
+            last_refill: StdMutex::new(Instant::now()),
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn available(&self) -> u64 {
+        self.available.load(Ordering::Relaxed)
+    }
+
+    /// Takes `n` tokens if the bucket holds that many right now.
+    pub fn try_acquire(&self, n: u64) -> bool {
+        self.refill();
+        self.available
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |available| {
+                available.checked_sub(n)
+            })
+            .is_ok()
+    }
+
+    /// Waits until `n` tokens are available, then takes them.
+    ///
+    /// # Panics
+    ///
+    /// If `n` exceeds the bucket's capacity, which could never succeed.
+    pub async fn acquire(&self, n: u64) {
+        assert!(
+            n <= self.capacity,
+            "cannot acquire {n} tokens from a bucket of {}",
+            self.capacity
+        );
+        if self.try_acquire(n) {
+            return;
+        }
+        let mut tick = tokio::time::interval(REFILL_TICK);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tick.tick().await;
+            if self.try_acquire(n) {
+                return;
+            }
+        }
+    }
+
+    /// Returns `n` tokens, e.g. once a throttled message is acked.
+    pub fn release(&self, n: u64) {
+        self.available
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |available| {
+                Some(available.saturating_add(n).min(self.capacity))
+            })
+            .ok();
+    }
+
+    /// Credits tokens for the time elapsed since the last refill, keeping
+    /// sub-token remainders by only advancing the refill clock for whole
+    /// tokens credited.
+    fn refill(&self) {
+        if self.refill_rate == 0 {
+            return;
+        }
+        let mut last = self.last_refill.lock().unwrap();
+        let elapsed = last.elapsed();
+        let credit = (elapsed.as_nanos() * self.refill_rate as u128 / 1_000_000_000) as u64;
+        if credit == 0 {
+            return;
+        }
+        // In u128 like the credit above: with byte-sized rates the u64
+        // product overflows after a few seconds of idle.
+        *last +=
+            Duration::from_nanos((credit as u128 * 1_000_000_000 / self.refill_rate as u128) as u64);
+        self.release(credit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn try_acquire_drains_and_refills() {
+        let bucket = TokenBucket::new(100, 10);
+        assert!(bucket.try_acquire(60));
+        assert!(bucket.try_acquire(40));
+        assert!(!bucket.try_acquire(1));
+
+        // 10 tokens/s: after two seconds 20 tokens are back.
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(bucket.try_acquire(20));
+        assert!(!bucket.try_acquire(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refill_survives_byte_rates_and_long_idle() {
+        // 1 GB/s with tokens as bytes: 20 idle seconds credit enough
+        // that the refill arithmetic would overflow u64.
+        let bucket = TokenBucket::new(2_000_000_000, 1_000_000_000);
+        assert!(bucket.try_acquire(2_000_000_000));
+        tokio::time::advance(Duration::from_secs(20)).await;
+        assert!(bucket.try_acquire(2_000_000_000));
+        // The refill clock advanced with the credit: no double-crediting
+        // of the same elapsed window.
+        assert!(!bucket.try_acquire(1_000_000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_refill() {
+        let bucket = TokenBucket::new(100, 100);
+        bucket.acquire(100).await;
+        let start = Instant::now();
+        // Needs 50 more tokens at 100/s: roughly half a second.
+        bucket.acquire(50).await;
+        let waited = start.elapsed();
+        assert!(waited >= Duration::from_millis(490), "waited {waited:?}");
+        assert!(waited <= Duration::from_millis(600), "waited {waited:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn release_never_exceeds_capacity() {
+        let bucket = TokenBucket::new(10, 0);
+        bucket.release(100);
+        assert_eq!(bucket.available(), 10);
+        assert!(bucket.try_acquire(10));
+        // Rate zero: tokens only come back via release.
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(!bucket.try_acquire(1));
+    }
+}
@@ -0,0 +1,453 @@
+//! The OSD client: map handling, session management and op dispatch.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crush::PgId;
+use denc::VersionedEncode;
+use monclient::MonClient;
+use msgr2::state_machine::ConnectionConfig;
+use tokio::sync::Mutex;
+
+use crate::deferred::{DeferredOp, DeferredOpQueue};
+use crate::error::{OSDClientError, OSDOpError};
+use crate::ioctx::IoCtx;
+use crate::messages::{MOSDOp, MOSDOpReply};
+use crate::objecter::MapNotifier;
+use crate::operation::{OSDOp, OpCode};
+use crate::osdmap::OSDMap;
+use crate::retry::RetryPolicy;
+use crate::session::OSDSession;
+use crate::tracker::{LatencyHistogram, LatencyStats};
+use crate::types::SnapContext;
+
+/// Tunables for the OSD client.
+#[derive(Debug, Clone)]
+pub struct OSDClientConfig {
+    /// How long to wait for each op's reply.
+    pub op_timeout: Duration,
+    pub max_concurrent_ops: usize,
+    /// When set, transiently failed ops are re-dispatched per the policy;
+    /// see [`crate::retry::should_retry`].
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+impl Default for OSDClientConfig {
+    fn default() -> Self {
+        OSDClientConfig {
+            op_timeout: Duration::from_secs(30),
+            max_concurrent_ops: 1024,
+            retry_policy: None,
+        }
+    }
+}
+
+/// The Rust counterpart of the Objecter: owns the OSDMap, one session per
+/// OSD, and dispatches ops to each PG's primary.
+pub struct OSDClient {
+    mon: Arc<MonClient>,
+    conn_config: ConnectionConfig,
+    config: OSDClientConfig,
+    sessions: Mutex<HashMap<u32, Arc<OSDSession>>>,
+    map_notifier: MapNotifier<OSDMap>,
+    next_tid: AtomicU64,
+    next_cookie: AtomicU64,
+    deferred: DeferredOpQueue,
+    /// Deferred ops currently dispatched; bounded by
+    /// [`OSDClientConfig::max_concurrent_ops`].
+    deferred_in_flight: AtomicUsize,
+}
+
+impl OSDClient {
+    pub fn new(mon: Arc<MonClient>, conn_config: ConnectionConfig, config: OSDClientConfig) -> Self {
+        OSDClient {
+            mon,
+            conn_config,
+            config,
+            sessions: Mutex::new(HashMap::new()),
+            map_notifier: MapNotifier::new(),
+            next_tid: AtomicU64::new(1),
+            next_cookie: AtomicU64::new(1),
+            deferred: DeferredOpQueue::default(),
+            deferred_in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &OSDClientConfig {
+        &self.config
+    }
+
+    /// Fetches the current OSDMap from the monitors and publishes it.
+    pub async fn fetch_osdmap(&self) -> Result<Arc<OSDMap>, OSDClientError> {
+        let result = self
+            .mon
+            .send_command(vec!["{\"prefix\": \"osd getmap\"}".to_string()], None)
+            .await?;
+        if result.code < 0 {
+            return Err(OSDClientError::OsdError(result.code));
+        }
+        let mut raw = result.data.clone();
+        let map = Arc::new(OSDMap::decode_versioned(&mut raw)?);
+        self.map_notifier.publish(map.clone());
+        Ok(map)
+    }
+
+    /// The current OSDMap; [`OSDClient::fetch_osdmap`] must have succeeded
+    /// at least once.
+    pub fn osdmap(&self) -> Result<Arc<OSDMap>, OSDClientError> {
+        self.map_notifier.latest().ok_or(OSDClientError::NotConnected)
+    }
+
+    /// The map notifier, for tasks that want to follow epoch changes.
+    pub fn map_notifier(&self) -> &MapNotifier<OSDMap> {
+        &self.map_notifier
+    }
+
+    /// Waits until the first OSDMap has been delivered, or `timeout`
+    /// elapses.  Returns immediately once a map is known.
+    pub async fn wait_for_osdmap(&self, timeout: Duration) -> Result<Arc<OSDMap>, OSDClientError> {
+        let mut rx = self.map_notifier.watch();
+        let first_map = async {
+            loop {
+                if let Some(map) = rx.borrow_and_update().clone() {
+                    return Ok(map);
+                }
+                if rx.changed().await.is_err() {
+                    return Err(OSDClientError::NotConnected);
+                }
+            }
+        };
+        match tokio::time::timeout(timeout, first_map).await {
+            Ok(result) => result,
+            Err(_) => Err(OSDClientError::Timeout),
+        }
+    }
+
+    /// The epoch of the current OSDMap, without blocking.
+    pub fn current_osdmap_epoch(&self) -> Option<u32> {
+        self.map_notifier.latest().map(|map| map.epoch)
+    }
+
+    /// A push channel carrying the OSDMap epoch: the current epoch
+    /// immediately (0 before the first map arrives), then every newer
+    /// epoch as it is applied.
+    pub fn watch_osdmap(&self) -> tokio::sync::watch::Receiver<u32> {
+        let mut maps = self.map_notifier.subscribe_with_replay();
+        let initial = self.current_osdmap_epoch().unwrap_or(0);
+        let (tx, rx) = tokio::sync::watch::channel(initial);
+        tokio::spawn(async move {
+            while let Some(map) = maps.recv().await {
+                if tx.is_closed() {
+                    break;
+                }
+                // The replayed map repeats `initial`; only real epoch
+                // changes wake the receivers.
+                tx.send_if_modified(|epoch| {
+                    let changed = *epoch != map.epoch;
+                    *epoch = map.epoch;
+                    changed
+                });
+            }
+        });
+        rx
+    }
+
+    /// Looks a pool up by name.
+    pub fn lookup_pool(&self, name: &str) -> Result<u64, OSDClientError> {
+        let map = self.osdmap()?;
+        map.pool_name
+            .iter()
+            .find(|(_, n)| n.as_str() == name)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| OSDClientError::PoolNotFound(name.to_string()))
+    }
+
+    /// Opens an I/O context on the named pool.
+    pub fn ioctx(self: &Arc<Self>, pool_name: &str) -> Result<IoCtx, OSDClientError> {
+        let pool_id = self.lookup_pool(pool_name)?;
+        Ok(IoCtx::new(self.clone(), pool_id, pool_name.to_string()))
+    }
+
+    /// Pings `osd_id`'s session, returning the round-trip time.  Fails
+    /// with [`OSDClientError::NotConnected`] when no session exists and
+    /// one cannot be established.
+    pub async fn check_session_health(&self, osd_id: u32) -> Result<Duration, OSDClientError> {
+        self.measure_osd_latency(osd_id).await
+    }
+
+    /// The round-trip time to `osd_id`, measured with one ping on its
+    /// session (establishing the session first when necessary).
+    pub async fn measure_osd_latency(&self, osd_id: u32) -> Result<Duration, OSDClientError> {
+        let session = self.session_for(osd_id).await?;
+        session.ping(self.next_tid(), self.config.op_timeout).await
+    }
+
+    /// Per-op-type completion latency statistics, merged across every
+    /// open session.
+    pub async fn op_latencies(&self) -> HashMap<OpCode, LatencyStats> {
+        let mut merged: HashMap<OpCode, LatencyHistogram> = HashMap::new();
+        for session in self.sessions.lock().await.values() {
+            for (op_type, histogram) in session.latency_histograms() {
+                merged
+                    .entry(op_type)
+                    .and_modify(|m| m.merge(&histogram))
+                    .or_insert(histogram);
+            }
+        }
+        merged
+            .into_iter()
+            .map(|(op_type, histogram)| (op_type, histogram.stats()))
+            .collect()
+    }
+
+    pub(crate) fn next_tid(&self) -> u64 {
+        self.next_tid.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) fn next_cookie(&self) -> u64 {
+        self.next_cookie.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The session to `osd`, connecting if none exists yet.
+    pub(crate) async fn session_for(&self, osd: u32) -> Result<Arc<OSDSession>, OSDClientError> {
+        let mut sessions = self.sessions.lock().await;
+        if let Some(session) = sessions.get(&osd) {
+            if session.is_ready().await {
+                return Ok(session.clone());
+            }
+            sessions.remove(&osd);
+        }
+        let map = self.osdmap()?;
+        let addrs = map
+            .osd_addrs
+            .get(osd as usize)
+            .ok_or(OSDClientError::NotConnected)?;
+        let addr = addrs
+            .preferred_addr()
+            .ok_or(OSDClientError::NotConnected)?;
+        let session = Arc::new(
+            OSDSession::connect(osd, addr.sockaddr, self.conn_config.clone()).await?,
+        );
+        sessions.insert(osd, session.clone());
+        Ok(session.clone())
+    }
+
+    /// Enqueues `op` for deadline-aware, priority-ordered dispatch
+    /// instead of submitting it immediately.  At most
+    /// [`OSDClientConfig::max_concurrent_ops`] deferred ops run at once;
+    /// each freed slot goes to the highest-priority op still within its
+    /// deadline, and ops whose deadline passes while queued fail with
+    /// [`OSDClientError::Timeout`].  The result arrives on the returned
+    /// receiver.
+    pub fn defer_op(
+        self: &Arc<Self>,
+        op: DeferredOp,
+        priority: u8,
+        deadline: Option<Instant>,
+    ) -> tokio::sync::oneshot::Receiver<Result<MOSDOpReply, OSDClientError>> {
+        let reply_rx = self.deferred.push(op, priority, deadline);
+        self.pump_deferred();
+        reply_rx
+    }
+
+    /// The number of deferred ops waiting for a dispatch slot.
+    pub fn queue_depth(&self) -> usize {
+        self.deferred.len()
+    }
+
+    /// Starts queued ops while dispatch slots are free.  Called after
+    /// every enqueue and every completion, so the queue drains as fast as
+    /// the concurrency limit allows.
+    fn pump_deferred(self: &Arc<Self>) {
+        loop {
+            let claimed = self.deferred_in_flight.fetch_update(
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+                |in_flight| (in_flight < self.config.max_concurrent_ops).then_some(in_flight + 1),
+            );
+            if claimed.is_err() {
+                return;
+            }
+            let Some(queued) = self.deferred.pop_ready(Instant::now()) else {
+                self.deferred_in_flight.fetch_sub(1, Ordering::SeqCst);
+                return;
+            };
+            let client = self.clone();
+            tokio::spawn(async move {
+                let op = queued.op;
+                let result = client
+                    .submit(
+                        op.pool_id,
+                        &op.oid,
+                        op.locator_key.as_deref(),
+                        &op.nspace,
+                        op.ops,
+                        op.flags,
+                        op.snapc,
+                        op.timeout,
+                    )
+                    .await;
+                let _ = queued.reply_tx.send(result);
+                client.deferred_in_flight.fetch_sub(1, Ordering::SeqCst);
+                client.pump_deferred();
+            });
+        }
+    }
+
+    /// Routes `ops` on `oid` to its PG's primary and waits for the reply.
+    /// A non-empty `locator_key` overrides the placement hash; `nspace`
+    /// scopes the object name.  `timeout` overrides the client-wide op
+    /// timeout when set.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn submit(
+        &self,
+        pool_id: u64,
+        oid: &str,
+        locator_key: Option<&str>,
+        nspace: &str,
+        ops: Vec<OSDOp>,
+        flags: u32,
+        snapc: SnapContext,
+        timeout: Option<Duration>,
+    ) -> Result<MOSDOpReply, OSDClientError> {
+        let pg = self
+            .osdmap()?
+            .object_to_pg_with_locator(pool_id, oid, locator_key)?;
+        self.submit_inner(pg, oid, locator_key, nspace, ops, flags, snapc, timeout)
+            .await
+    }
+
+    /// Sends `ops` to the primary of `pg` directly, for PG-scoped ops such
+    /// as listings that are not addressed to one object.
+    pub(crate) async fn submit_to_pg(
+        &self,
+        pg: PgId,
+        ops: Vec<OSDOp>,
+        flags: u32,
+    ) -> Result<MOSDOpReply, OSDClientError> {
+        self.submit_inner(pg, "", None, "", ops, flags, SnapContext::default(), None)
+            .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn submit_inner(
+        &self,
+        pg: PgId,
+        oid: &str,
+        locator_key: Option<&str>,
+        nspace: &str,
+        ops: Vec<OSDOp>,
+        flags: u32,
+        snapc: SnapContext,
+        timeout: Option<Duration>,
+    ) -> Result<MOSDOpReply, OSDClientError> {
+        let mut op = MOSDOp::new(pg, oid, ops);
+        op.locator_key = locator_key.unwrap_or_default().to_string();
+        op.nspace = nspace.to_string();
+        op.flags = flags;
+        op.snapc = snapc;
+        let timeout = timeout.unwrap_or(self.config.op_timeout);
+        match &self.config.retry_policy {
+            Some(policy) => {
+                policy
+                    .run(|| self.dispatch_once(pg, op.clone(), timeout))
+                    .await
+            }
+            None => self.dispatch_once(pg, op, timeout).await,
+        }
+    }
+
+    /// One dispatch attempt: route to the PG's current primary and wait
+    /// for the reply.  Primary and session are resolved per attempt, so a
+    /// retry after a map change or reconnect goes to the right OSD.
+    async fn dispatch_once(
+        &self,
+        pg: PgId,
+        op: MOSDOp,
+        timeout: Duration,
+    ) -> Result<MOSDOpReply, OSDClientError> {
+        let map = self.osdmap()?;
+        let primary = map
+            .pg_primary(pg)?
+            .ok_or(OSDClientError::NoOsdForPg(pg))?;
+        self.dispatch_to_osd(primary, op, Some(timeout)).await
+    }
+
+    /// Sends `op` to `osd` directly, bypassing primary resolution; the
+    /// caller is responsible for picking an OSD that may serve the op
+    /// (e.g. a replica read with `CEPH_OSD_FLAG_BALANCE_READS`).
+    pub(crate) async fn dispatch_to_osd(
+        &self,
+        osd: u32,
+        op: MOSDOp,
+        timeout: Option<Duration>,
+    ) -> Result<MOSDOpReply, OSDClientError> {
+        let timeout = timeout.unwrap_or(self.config.op_timeout);
+        let session = self.session_for(osd).await?;
+        let opcode = op.ops.first().map(|o| o.code);
+        let tid = self.next_tid();
+        let reply = session.submit(op, tid, timeout).await?;
+        if reply.result < 0 {
+            return Err(match opcode {
+                Some(opcode) => OSDOpError::from_errno(reply.result, opcode),
+                None => OSDClientError::OsdError(reply.result),
+            });
+        }
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth::provider::NoneAuthProvider;
+    use monclient::MonClientConfig;
+
+    fn test_client() -> OSDClient {
+        let mon_config = MonClientConfig::new(Vec::new(), "client.admin".parse().unwrap());
+        let conn_config =
+            ConnectionConfig::new(Arc::new(NoneAuthProvider::new("client.admin".parse().unwrap())));
+        OSDClient::new(
+            Arc::new(MonClient::new(mon_config)),
+            conn_config,
+            OSDClientConfig::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn deferred_ops_past_their_deadline_fail_with_timeout() {
+        let client = Arc::new(test_client());
+        let op = DeferredOp::new(1, "obj", vec![crate::operation::OSDOp::stat()], 0);
+        let mut rx = client.defer_op(
+            op,
+            255,
+            Some(Instant::now() - Duration::from_secs(1)),
+        );
+        // The expired op is failed on the spot, never dispatched.
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            Err(OSDClientError::Timeout)
+        ));
+        assert_eq!(client.queue_depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn watch_osdmap_delivers_each_new_epoch() {
+        let map_at = |epoch: u32| {
+            let mut map = OSDMap::default();
+            map.epoch = epoch;
+            Arc::new(map)
+        };
+        let client = test_client();
+        client.map_notifier().publish(map_at(7));
+
+        let mut rx = client.watch_osdmap();
+        assert_eq!(*rx.borrow_and_update(), 7);
+
+        client.map_notifier().publish(map_at(8));
+        rx.changed().await.unwrap();
+        assert_eq!(*rx.borrow_and_update(), 8);
+    }
+}
@@ -0,0 +1,175 @@
+//! Priority-based deferral of ops past the concurrency limit.
+//!
+//! [`crate::client::OSDClient::defer_op`] enqueues ops here instead of
+//! dispatching them immediately; at most
+//! [`crate::client::OSDClientConfig::max_concurrent_ops`] run at once, and
+//! each freed slot goes to the highest-priority op still within its
+//! deadline.
+
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use crate::error::OSDClientError;
+use crate::messages::MOSDOpReply;
+use crate::operation::OSDOp;
+use crate::types::SnapContext;
+
+/// An op captured for later dispatch: the arguments of a submit, owned so
+/// the op can outlive the caller.
+#[derive(Debug, Clone)]
+pub struct DeferredOp {
+    pub pool_id: u64,
+    pub oid: String,
+    /// Overrides the placement hash when set, as with locator keys.
+    pub locator_key: Option<String>,
+    /// The object namespace; empty for the default namespace.
+    pub nspace: String,
+    pub ops: Vec<OSDOp>,
+    /// `CEPH_OSD_FLAG_*` bits for the enclosing message.
+    pub flags: u32,
+    pub snapc: SnapContext,
+    /// Overrides the client-wide op timeout when set.
+    pub timeout: Option<Duration>,
+}
+
+impl DeferredOp {
+    pub fn new(pool_id: u64, oid: &str, ops: Vec<OSDOp>, flags: u32) -> Self {
+        DeferredOp {
+            pool_id,
+            oid: oid.to_string(),
+            locator_key: None,
+            nspace: String::new(),
+            ops,
+            flags,
+            snapc: SnapContext::default(),
+            timeout: None,
+        }
+    }
+}
+
+/// One queued op; ordered by priority, then FIFO within a priority level.
+pub(crate) struct QueuedOp {
+    priority: u8,
+    queue_seq: u64,
+    deadline: Option<Instant>,
+    pub(crate) op: DeferredOp,
+    pub(crate) reply_tx: oneshot::Sender<Result<MOSDOpReply, OSDClientError>>,
+}
+
+impl PartialEq for QueuedOp {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedOp {}
+
+impl PartialOrd for QueuedOp {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedOp {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority
+            .cmp(&other.priority)
+            .then(other.queue_seq.cmp(&self.queue_seq))
+    }
+}
+
+/// The queue of ops waiting for a dispatch slot.
+#[derive(Default)]
+pub(crate) struct DeferredOpQueue {
+    heap: Mutex<BinaryHeap<QueuedOp>>,
+    next_seq: AtomicU64,
+}
+
+impl DeferredOpQueue {
+    /// Enqueues `op`; the returned receiver delivers its eventual result
+    /// (or [`OSDClientError::Timeout`] if the deadline passes first).
+    pub(crate) fn push(
+        &self,
+        op: DeferredOp,
+        priority: u8,
+        deadline: Option<Instant>,
+    ) -> oneshot::Receiver<Result<MOSDOpReply, OSDClientError>> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.heap.lock().unwrap().push(QueuedOp {
+            priority,
+            queue_seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+            deadline,
+            op,
+            reply_tx,
+        });
+        reply_rx
+    }
+
+    /// Removes and returns the highest-priority op whose deadline lies
+    /// past `now`.  Expired ops encountered on the way are failed with
+    /// [`OSDClientError::Timeout`] through their receivers.
+    pub(crate) fn pop_ready(&self, now: Instant) -> Option<QueuedOp> {
+        let mut heap = self.heap.lock().unwrap();
+        while let Some(queued) = heap.pop() {
+            if queued.deadline.is_some_and(|deadline| deadline <= now) {
+                let _ = queued.reply_tx.send(Err(OSDClientError::Timeout));
+                continue;
+            }
+            return Some(queued);
+        }
+        None
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.heap.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop(pool_id: u64) -> DeferredOp {
+        DeferredOp::new(pool_id, "obj", vec![OSDOp::stat()], 0)
+    }
+
+    #[test]
+    fn pops_by_priority_then_fifo() {
+        let queue = DeferredOpQueue::default();
+        let _rx1 = queue.push(noop(1), 64, None);
+        let _rx2 = queue.push(noop(2), 196, None);
+        let _rx3 = queue.push(noop(3), 64, None);
+        let _rx4 = queue.push(noop(4), 196, None);
+        assert_eq!(queue.len(), 4);
+
+        let order: Vec<u64> = std::iter::from_fn(|| {
+            queue.pop_ready(Instant::now()).map(|q| q.op.pool_id)
+        })
+        .collect();
+        // High priority first; equal priorities keep submission order.
+        assert_eq!(order, [2, 4, 1, 3]);
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn expired_ops_are_failed_not_returned() {
+        let queue = DeferredOpQueue::default();
+        let now = Instant::now();
+        let mut expired = queue.push(noop(1), 255, Some(now - Duration::from_secs(1)));
+        let _live = queue.push(noop(2), 0, Some(now + Duration::from_secs(60)));
+
+        // The expired op outranks the live one but is skipped and failed.
+        let popped = queue.pop_ready(now).expect("the live op");
+        assert_eq!(popped.op.pool_id, 2);
+        assert!(matches!(
+            expired.try_recv().unwrap(),
+            Err(OSDClientError::Timeout)
+        ));
+        assert!(queue.pop_ready(now).is_none());
+    }
+}
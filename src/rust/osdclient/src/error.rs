@@ -0,0 +1,114 @@
+//! OSD client errors.
+
+use crush::{CrushError, PgId};
+use denc::RadosError;
+use monclient::MonClientError;
+use thiserror::Error;
+
+use crate::operation::OpCode;
+
+#[derive(Debug, Error)]
+pub enum OSDClientError {
+    #[error("not connected")]
+    NotConnected,
+
+    #[error("operation timed out")]
+    Timeout,
+
+    #[error("pool {0:?} does not exist")]
+    PoolNotFound(String),
+
+    #[error("no OSD is serving pg {0}")]
+    NoOsdForPg(PgId),
+
+    #[error("object not found ({op:?})")]
+    ObjectNotFound { op: OpCode },
+
+    #[error("object already exists ({op:?})")]
+    ObjectAlreadyExists { op: OpCode },
+
+    #[error("cluster is out of space ({op:?})")]
+    OutOfSpace { op: OpCode },
+
+    #[error("I/O error ({op:?})")]
+    IOError { op: OpCode },
+
+    #[error("invalid argument ({op:?})")]
+    InvalidArgument { op: OpCode },
+
+    #[error("osd returned error {0}")]
+    OsdError(i32),
+
+    #[error("class method returned {code}: {message}")]
+    ClassError { code: i32, message: String },
+
+    #[error("watch was cancelled")]
+    WatchCancelled,
+
+    #[error("op batch is empty")]
+    EmptyBatch,
+
+    #[error(transparent)]
+    Crush(#[from] CrushError),
+
+    #[error(transparent)]
+    Mon(#[from] MonClientError),
+
+    #[error(transparent)]
+    Messenger(#[from] msgr2::Error),
+
+    #[error(transparent)]
+    Encoding(#[from] RadosError),
+}
+
+/// Translation of the errno codes OSDs return in `MOSDOpReply::result`.
+pub struct OSDOpError;
+
+impl OSDOpError {
+    /// Maps `code` to a typed error, keeping the failing op for context.
+    /// Codes without a dedicated variant stay raw `OsdError`s.
+    pub fn from_errno(code: i32, op: OpCode) -> OSDClientError {
+        match code {
+            -2 => OSDClientError::ObjectNotFound { op },   // ENOENT
+            -17 => OSDClientError::ObjectAlreadyExists { op }, // EEXIST
+            -28 => OSDClientError::OutOfSpace { op },      // ENOSPC
+            -5 => OSDClientError::IOError { op },          // EIO
+            -22 => OSDClientError::InvalidArgument { op }, // EINVAL
+            other => OSDClientError::OsdError(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errno_mapping() {
+        let op = OpCode::Read;
+        assert!(matches!(
+            OSDOpError::from_errno(-2, op),
+            OSDClientError::ObjectNotFound { op: OpCode::Read }
+        ));
+        assert!(matches!(
+            OSDOpError::from_errno(-17, op),
+            OSDClientError::ObjectAlreadyExists { .. }
+        ));
+        assert!(matches!(
+            OSDOpError::from_errno(-28, op),
+            OSDClientError::OutOfSpace { .. }
+        ));
+        assert!(matches!(
+            OSDOpError::from_errno(-5, op),
+            OSDClientError::IOError { .. }
+        ));
+        assert!(matches!(
+            OSDOpError::from_errno(-22, op),
+            OSDClientError::InvalidArgument { .. }
+        ));
+        assert!(matches!(
+            OSDOpError::from_errno(-108, op),
+            OSDClientError::OsdError(-108)
+        ));
+    }
+}
@@ -0,0 +1,502 @@
+//! Pool I/O contexts, the librados-style entry point for object ops.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use crush::PgId;
+use denc::pg_nls_response::PgNlsResponse;
+use denc::VersionedEncode;
+use tokio::sync::mpsc;
+
+use crate::client::OSDClient;
+use crate::denc_types::{decode_omap_entries, decode_sparse_read_reply, decode_stat_reply};
+use crate::error::OSDClientError;
+use crate::messages::{MOSDOp, MOSDOpReply};
+use crate::operation::{
+    decode_xattrs_reply, message_flags_for, OSDOp, OpBatch, CEPH_OSD_FLAG_BALANCE_READS,
+    CEPH_OSD_FLAG_READ, CEPH_OSD_WATCH_OP_UNWATCH, CEPH_OSD_WATCH_OP_WATCH,
+};
+use crate::osdmap::OSDMap;
+use crate::session::OSDSession;
+use crate::types::{
+    BatchResult, SnapContext, SparseReadResult, Stat2Result, StatResult, WatchNotification,
+    WriteResult,
+};
+
+/// Per-context settings applied to every op issued through an [`IoCtx`].
+#[derive(Debug, Clone, Default)]
+pub struct IoCtxConfig {
+    /// The snap context embedded in each op; `None` sends the empty
+    /// context, as for an unsnapshotted pool.
+    pub snap_context: Option<SnapContext>,
+    /// Extra `CEPH_OSD_FLAG_*` bits OR-ed into every op.
+    pub flags: u32,
+    /// Overrides [`crate::client::OSDClientConfig::op_timeout`] when set.
+    pub default_timeout: Option<Duration>,
+}
+
+/// An I/O context bound to one pool.
+#[derive(Clone)]
+pub struct IoCtx {
+    client: Arc<OSDClient>,
+    pool_id: u64,
+    pool_name: String,
+    /// When set, placement hashes this key instead of each object's name.
+    locator_key: Option<String>,
+    /// The object namespace; empty for the default namespace.
+    namespace: String,
+    config: IoCtxConfig,
+}
+
+impl IoCtx {
+    pub(crate) fn new(client: Arc<OSDClient>, pool_id: u64, pool_name: String) -> Self {
+        IoCtx {
+            client,
+            pool_id,
+            pool_name,
+            locator_key: None,
+            namespace: String::new(),
+            config: IoCtxConfig::default(),
+        }
+    }
+
+    /// A context on `pool_id` with explicit per-context settings.  The
+    /// pool name is resolved from the current OSDMap when one is known.
+    pub fn new_with_config(client: Arc<OSDClient>, pool_id: u64, config: IoCtxConfig) -> Self {
+        let pool_name = client
+            .osdmap()
+            .ok()
+            .and_then(|map| map.pool_name.get(&pool_id).cloned())
+            .unwrap_or_default();
+        IoCtx {
+            client,
+            pool_id,
+            pool_name,
+            locator_key: None,
+            namespace: String::new(),
+            config,
+        }
+    }
+
+    /// Replaces the snap context embedded in subsequent ops.
+    pub fn set_snap_context(&mut self, ctx: SnapContext) {
+        self.config.snap_context = Some(ctx);
+    }
+
+    /// A context on the same pool (and the same [`OSDClient`], so session
+    /// and connection state are shared) but scoped to `ns`: the same
+    /// object name in different namespaces refers to different objects.
+    pub fn clone_with_namespace(&self, ns: &str) -> IoCtx {
+        IoCtx {
+            namespace: ns.to_string(),
+            ..self.clone()
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// A context whose operations are co-located with `key`: every object
+    /// hashes to the PG that `key` would, as with librados locator keys.
+    /// Placement still stays within this pool.
+    pub fn with_locator_key(&self, key: &str) -> IoCtx {
+        IoCtx {
+            locator_key: Some(key.to_string()),
+            ..self.clone()
+        }
+    }
+
+    pub fn pool_id(&self) -> u64 {
+        self.pool_id
+    }
+
+    pub fn pool_name(&self) -> &str {
+        &self.pool_name
+    }
+
+    /// Runs `ops` against `oid` as one transaction, with read or write
+    /// flags derived from the ops themselves.
+    pub async fn operate(
+        &self,
+        oid: &str,
+        ops: Vec<OSDOp>,
+    ) -> Result<MOSDOpReply, OSDClientError> {
+        let flags = message_flags_for(&ops);
+        self.client
+            .submit(
+                self.pool_id,
+                oid,
+                self.locator_key.as_deref(),
+                &self.namespace,
+                ops,
+                flags | self.config.flags,
+                self.config.snap_context.clone().unwrap_or_default(),
+                self.config.default_timeout,
+            )
+            .await
+    }
+
+    /// Replaces the entire object with `data`.
+    pub async fn write_full(&self, oid: &str, data: Bytes) -> Result<WriteResult, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::write_full(data)]).await?;
+        Ok(WriteResult {
+            version: reply.version,
+            new_size: None,
+        })
+    }
+
+    /// Appends `data` to the end of the object, creating it if it does
+    /// not exist.  The OSD resolves the write offset, so the caller never
+    /// needs the current size; when the reply reports the post-append
+    /// size it is returned in [`WriteResult::new_size`].
+    pub async fn append(&self, oid: &str, data: Bytes) -> Result<WriteResult, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::append(data)]).await?;
+        Ok(WriteResult {
+            version: reply.version,
+            new_size: reply.appended_size(),
+        })
+    }
+
+    /// Reads `length` bytes at `offset`; `length == 0` reads the whole
+    /// object.
+    pub async fn read(&self, oid: &str, offset: u64, length: u64) -> Result<Bytes, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::read(offset, length)]).await?;
+        Ok(first_outdata(&reply))
+    }
+
+    /// Reads like [`IoCtx::read`], but routes to the acting-set member
+    /// with the lowest measured ping round trip instead of the primary,
+    /// marking the op `CEPH_OSD_FLAG_BALANCE_READS` so a replica may
+    /// serve it.  Replicas that cannot be pinged are skipped; when none
+    /// can be measured the read falls back to the primary.
+    pub async fn read_from_nearest(
+        &self,
+        oid: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<Bytes, OSDClientError> {
+        let map = self.client.osdmap()?;
+        let pg = map.object_to_pg_with_locator(self.pool_id, oid, self.locator_key.as_deref())?;
+        let acting = map.pg_acting_set(pg)?;
+        let mut measured = HashMap::new();
+        for &osd in &acting {
+            if let Ok(latency) = self.client.measure_osd_latency(osd).await {
+                measured.insert(osd, latency);
+            }
+        }
+        let target = OSDMap::nearest_osd_from_set(&acting, |osd| measured.get(&osd).copied())
+            .or_else(|| acting.first().copied())
+            .ok_or(OSDClientError::NoOsdForPg(pg))?;
+        let mut op = MOSDOp::new(pg, oid, vec![OSDOp::read(offset, length)]);
+        op.locator_key = self.locator_key.clone().unwrap_or_default();
+        op.nspace = self.namespace.clone();
+        op.flags = CEPH_OSD_FLAG_READ | CEPH_OSD_FLAG_BALANCE_READS | self.config.flags;
+        let reply = self
+            .client
+            .dispatch_to_osd(target, op, self.config.default_timeout)
+            .await?;
+        Ok(first_outdata(&reply))
+    }
+
+    /// Reads only the non-hole extents in `[offset, offset + length)`.
+    ///
+    /// The result pairs the extent map with the concatenated extent data;
+    /// an object that is all zeroes in the range yields an empty extent
+    /// list.
+    pub async fn sparse_read(
+        &self,
+        oid: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<SparseReadResult, OSDClientError> {
+        let reply = self
+            .operate(oid, vec![OSDOp::sparse_read(offset, length)])
+            .await?;
+        let mut outdata = first_outdata(&reply);
+        Ok(decode_sparse_read_reply(&mut outdata)?)
+    }
+
+    /// Returns the object's size and mtime.
+    pub async fn stat(&self, oid: &str) -> Result<StatResult, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::stat()]).await?;
+        let mut outdata = first_outdata(&reply);
+        Ok(decode_stat_reply(&mut outdata)?)
+    }
+
+    /// Returns extended stat information for `oid`.
+    ///
+    /// This tree has no `CEPH_OSD_OP_STAT2`, so the call always takes the
+    /// fallback path: a `STAT` op batched with a `GETXATTR` of the
+    /// `version` xattr in one transaction.  `alloc_size` is reported as
+    /// `size` (allocation granularity is not exposed without STAT2), and
+    /// when the version xattr is absent or malformed the object version
+    /// from the reply is used instead.
+    pub async fn stat2(&self, oid: &str) -> Result<Stat2Result, OSDClientError> {
+        let reply = self
+            .operate(oid, vec![OSDOp::stat(), OSDOp::getxattr("version")])
+            .await?;
+        let mut outdata = first_outdata(&reply);
+        let stat = decode_stat_reply(&mut outdata)?;
+        let version = reply
+            .op_results
+            .get(1)
+            .filter(|op| op.rval >= 0 && op.outdata.len() == 8)
+            .map(|op| u64::from_le_bytes(op.outdata[..].try_into().unwrap()))
+            .unwrap_or(reply.version.version);
+        Ok(Stat2Result {
+            size: stat.size,
+            mtime: stat.mtime,
+            alloc_size: stat.size,
+            version,
+        })
+    }
+
+    /// Runs a prepared [`OpBatch`] against `object` as one atomic
+    /// transaction, returning the per-op results in batch order.
+    pub async fn execute_ops(
+        &self,
+        object: &str,
+        batch: OpBatch,
+    ) -> Result<BatchResult, OSDClientError> {
+        if batch.is_empty() {
+            return Err(OSDClientError::EmptyBatch);
+        }
+        let reply = self.operate(object, batch.into_ops()).await?;
+        Ok(BatchResult {
+            op_results: reply.op_results,
+            version: reply.version,
+        })
+    }
+
+    /// Invokes `method` of the RADOS class `class` on `object`, returning
+    /// the method's output bytes.  Errors the method itself returns ride
+    /// in the per-op return value, with any error payload in the op's
+    /// outdata; those surface as [`OSDClientError::ClassError`], while
+    /// failures of the request as a whole keep the usual errno mapping.
+    pub async fn call_class(
+        &self,
+        object: &str,
+        class: &str,
+        method: &str,
+        input: Bytes,
+    ) -> Result<Bytes, OSDClientError> {
+        let reply = self
+            .operate(object, vec![OSDOp::call(class, method, input)])
+            .await?;
+        if let Some(op) = reply.op_results.first() {
+            if op.rval < 0 {
+                return Err(OSDClientError::ClassError {
+                    code: op.rval,
+                    message: String::from_utf8_lossy(&op.outdata).into_owned(),
+                });
+            }
+        }
+        Ok(first_outdata(&reply))
+    }
+
+    /// Removes the object.
+    pub async fn remove(&self, oid: &str) -> Result<WriteResult, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::delete()]).await?;
+        Ok(WriteResult {
+            version: reply.version,
+            new_size: None,
+        })
+    }
+
+    /// Reads omap values: the named `keys`, or every entry when `keys` is
+    /// empty.
+    pub async fn omap_get(
+        &self,
+        oid: &str,
+        keys: &[&str],
+    ) -> Result<HashMap<String, Bytes>, OSDClientError> {
+        let op = if keys.is_empty() {
+            OSDOp::omap_get_vals("", u64::MAX)
+        } else {
+            OSDOp::omap_get_vals_by_keys(keys)
+        };
+        let reply = self.operate(oid, vec![op]).await?;
+        let mut outdata = first_outdata(&reply);
+        Ok(decode_omap_entries(&mut outdata)?)
+    }
+
+    /// Sets (inserting or overwriting) omap entries on `oid`.
+    pub async fn omap_set(
+        &self,
+        oid: &str,
+        entries: &HashMap<String, Bytes>,
+    ) -> Result<WriteResult, OSDClientError> {
+        let reply = self
+            .operate(oid, vec![OSDOp::omap_set_vals(entries)])
+            .await?;
+        Ok(WriteResult {
+            version: reply.version,
+            new_size: None,
+        })
+    }
+
+    /// Sets (inserting or overwriting) the extended attribute `key` on
+    /// `oid`.
+    pub async fn setxattr(
+        &self,
+        oid: &str,
+        key: &str,
+        value: Bytes,
+    ) -> Result<WriteResult, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::setxattr(key, value)]).await?;
+        Ok(WriteResult {
+            version: reply.version,
+            new_size: None,
+        })
+    }
+
+    /// Reads the extended attribute `key` of `oid`.
+    pub async fn getxattr(&self, oid: &str, key: &str) -> Result<Bytes, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::getxattr(key)]).await?;
+        Ok(first_outdata(&reply))
+    }
+
+    /// Reads every extended attribute of `oid`, keyed by attribute name.
+    pub async fn getxattrs(
+        &self,
+        oid: &str,
+    ) -> Result<std::collections::BTreeMap<String, Bytes>, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::getxattrs()]).await?;
+        let mut outdata = first_outdata(&reply);
+        Ok(decode_xattrs_reply(&mut outdata)?)
+    }
+
+    /// Removes every omap entry of `oid`.
+    pub async fn omap_clear(&self, oid: &str) -> Result<WriteResult, OSDClientError> {
+        let reply = self.operate(oid, vec![OSDOp::omap_clear()]).await?;
+        Ok(WriteResult {
+            version: reply.version,
+            new_size: None,
+        })
+    }
+
+    /// Lists every object in the pool, walking each PG in turn.
+    ///
+    /// Each PG is read as a single `PGNLS` batch; listings larger than one
+    /// batch per PG do not yet follow the continuation handle.
+    pub async fn list_objects(&self) -> Result<Vec<String>, OSDClientError> {
+        const MAX_LIST_ENTRIES: u64 = 1024;
+        let map = self.client.osdmap()?;
+        let pool = map
+            .pool(self.pool_id)
+            .ok_or_else(|| OSDClientError::PoolNotFound(self.pool_name.clone()))?;
+        let mut objects = Vec::new();
+        for seed in 0..pool.pg_num {
+            let pg = PgId::new(self.pool_id, seed);
+            let reply = self
+                .client
+                .submit_to_pg(pg, vec![OSDOp::pgnls(MAX_LIST_ENTRIES)], CEPH_OSD_FLAG_READ)
+                .await?;
+            let mut outdata = first_outdata(&reply);
+            if outdata.is_empty() {
+                continue;
+            }
+            let response = PgNlsResponse::decode_versioned(&mut outdata)?;
+            objects.extend(response.entries.into_iter().map(|e| e.hobj.oid));
+        }
+        Ok(objects)
+    }
+
+    /// Sends `payload` to every watcher of `oid`.
+    pub async fn notify(
+        &self,
+        oid: &str,
+        payload: Bytes,
+        timeout_secs: u32,
+    ) -> Result<(), OSDClientError> {
+        self.operate(oid, vec![OSDOp::notify(timeout_secs, payload)])
+            .await?;
+        Ok(())
+    }
+
+    /// Registers a watch on `oid`; notifications arrive on the returned
+    /// handle until [`WatchHandle::unwatch`].
+    pub async fn watch(&self, oid: &str) -> Result<WatchHandle, OSDClientError> {
+        let map = self.client.osdmap()?;
+        let pg = map.object_to_pg_with_locator(self.pool_id, oid, self.locator_key.as_deref())?;
+        let primary = map
+            .pg_primary(pg)?
+            .ok_or(OSDClientError::NoOsdForPg(pg))?;
+        let session = self.client.session_for(primary).await?;
+        let cookie = self.client.next_cookie();
+        let rx = session.register_watch(cookie);
+        let result = self
+            .operate(oid, vec![OSDOp::watch(cookie, CEPH_OSD_WATCH_OP_WATCH)])
+            .await;
+        if let Err(e) = result {
+            session.unregister_watch(cookie);
+            return Err(e);
+        }
+        Ok(WatchHandle {
+            ioctx: self.clone(),
+            session,
+            oid: oid.to_string(),
+            cookie,
+            rx,
+        })
+    }
+}
+
+fn first_outdata(reply: &MOSDOpReply) -> Bytes {
+    reply
+        .op_results
+        .first()
+        .map(|op| op.outdata.clone())
+        .unwrap_or_default()
+}
+
+/// An established watch.  Dropping the handle without calling
+/// [`WatchHandle::unwatch`] leaves the watch registered on the OSD until
+/// it times out.
+pub struct WatchHandle {
+    ioctx: IoCtx,
+    session: Arc<OSDSession>,
+    oid: String,
+    cookie: u64,
+    rx: mpsc::UnboundedReceiver<WatchNotification>,
+}
+
+impl WatchHandle {
+    pub fn cookie(&self) -> u64 {
+        self.cookie
+    }
+
+    /// Waits for the next notification; `None` once the session drops the
+    /// watch.
+    pub async fn next(&mut self) -> Option<WatchNotification> {
+        self.rx.recv().await
+    }
+
+    /// Acknowledges `notification` so the notifier's `notify` completes.
+    pub async fn ack(&self, notification: &WatchNotification) -> Result<(), OSDClientError> {
+        self.ioctx
+            .operate(
+                &self.oid,
+                vec![OSDOp::notify_ack(notification.notify_id, self.cookie)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Unregisters the watch.
+    pub async fn unwatch(mut self) -> Result<(), OSDClientError> {
+        self.rx.close();
+        let result = self
+            .ioctx
+            .operate(
+                &self.oid,
+                vec![OSDOp::watch(self.cookie, CEPH_OSD_WATCH_OP_UNWATCH)],
+            )
+            .await;
+        self.session.unregister_watch(self.cookie);
+        result.map(drop)
+    }
+}
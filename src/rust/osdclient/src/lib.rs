@@ -0,0 +1,29 @@
+//! OSD client.
+//!
+//! The Rust counterpart of the C++ Objecter/librados stack: decodes the
+//! OSDMap, computes placements via the `crush` crate, maintains per-OSD
+//! sessions over `msgr2`, and exposes pool I/O through [`ioctx::IoCtx`].
+
+pub mod client;
+pub mod deferred;
+pub mod denc_types;
+pub mod error;
+pub mod ioctx;
+pub mod messages;
+pub mod objecter;
+pub mod operation;
+pub mod osdmap;
+pub mod pgmap_types;
+pub mod retry;
+pub mod session;
+pub mod tracker;
+pub mod types;
+
+pub use client::{OSDClient, OSDClientConfig};
+pub use deferred::DeferredOp;
+pub use error::{OSDClientError, OSDOpError};
+pub use ioctx::{IoCtx, IoCtxConfig};
+pub use operation::OpBatch;
+pub use retry::{ExponentialBackoff, RetryPolicy};
+pub use types::{BatchResult, SnapContext};
+pub use osdmap::OSDMap;
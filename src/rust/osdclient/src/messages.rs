@@ -0,0 +1,289 @@
+//! OSD message payloads (`MOSDOp`, `MOSDOpReply`, `MWatchNotify`).
+
+use bytes::{Bytes, BytesMut};
+use crush::PgId;
+use denc::types::{EVersion, UTime};
+use denc::{Denc, RadosError};
+use msgr2::Message;
+
+use crate::operation::OSDOp;
+use crate::types::SnapContext;
+
+/// `CEPH_MSG_OSD_OP`
+pub const CEPH_MSG_OSD_OP: u16 = 42;
+/// `CEPH_MSG_OSD_OPREPLY`
+pub const CEPH_MSG_OSD_OPREPLY: u16 = 43;
+/// `CEPH_MSG_WATCH_NOTIFY`
+pub const CEPH_MSG_WATCH_NOTIFY: u16 = 44;
+
+/// A batch of ops against one object, addressed to the PG's primary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MOSDOp {
+    pub pgid: PgId,
+    pub oid: String,
+    /// The object locator key; empty unless the caller pinned placement
+    /// to another object's PG.
+    pub locator_key: String,
+    /// The object namespace; empty for the default namespace.
+    pub nspace: String,
+    pub flags: u32,
+    pub mtime: UTime,
+    /// The snap context for writes; empty when the pool is unsnapshotted.
+    pub snapc: SnapContext,
+    pub ops: Vec<OSDOp>,
+}
+
+impl MOSDOp {
+    pub fn new(pgid: PgId, oid: impl Into<String>, ops: Vec<OSDOp>) -> Self {
+        MOSDOp {
+            pgid,
+            oid: oid.into(),
+            locator_key: String::new(),
+            nspace: String::new(),
+            flags: 0,
+            mtime: UTime::default(),
+            snapc: SnapContext::default(),
+            ops,
+        }
+    }
+
+    pub fn encode_front(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.pgid.encode(&mut buf);
+        self.oid.encode(&mut buf);
+        self.locator_key.encode(&mut buf);
+        self.nspace.encode(&mut buf);
+        self.flags.encode(&mut buf);
+        self.mtime.encode(&mut buf);
+        self.snapc.encode(&mut buf);
+        (self.ops.len() as u16).encode(&mut buf);
+        for op in &self.ops {
+            op.encode(&mut buf);
+        }
+        buf.freeze()
+    }
+
+    pub fn decode_front(front: &mut Bytes) -> Result<Self, RadosError> {
+        let pgid = PgId::decode(front)?;
+        let oid = String::decode(front)?;
+        let locator_key = String::decode(front)?;
+        let nspace = String::decode(front)?;
+        let flags = u32::decode(front)?;
+        let mtime = UTime::decode(front)?;
+        let snapc = SnapContext::decode(front)?;
+        let num_ops = u16::decode(front)? as usize;
+        let mut ops = Vec::with_capacity(num_ops);
+        for _ in 0..num_ops {
+            ops.push(OSDOp::decode(front)?);
+        }
+        Ok(MOSDOp {
+            pgid,
+            oid,
+            locator_key,
+            nspace,
+            flags,
+            mtime,
+            snapc,
+            ops,
+        })
+    }
+
+    pub fn into_message(self, tid: u64) -> Message {
+        let mut msg = Message::new(CEPH_MSG_OSD_OP, self.encode_front());
+        msg.tid = tid;
+        msg
+    }
+}
+
+/// Per-op result within an [`MOSDOpReply`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpResult {
+    pub rval: i32,
+    pub outdata: Bytes,
+}
+
+/// The reply to an `MOSDOp`: overall result plus per-op return values and
+/// output payloads.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MOSDOpReply {
+    pub result: i32,
+    pub flags: u32,
+    pub version: EVersion,
+    pub op_results: Vec<OpResult>,
+}
+
+impl MOSDOpReply {
+    pub fn encode_front(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.result.encode(&mut buf);
+        self.flags.encode(&mut buf);
+        self.version.encode(&mut buf);
+        (self.op_results.len() as u16).encode(&mut buf);
+        for op in &self.op_results {
+            op.rval.encode(&mut buf);
+            op.outdata.encode(&mut buf);
+        }
+        buf.freeze()
+    }
+
+    pub fn decode_front(front: &mut Bytes) -> Result<Self, RadosError> {
+        let result = i32::decode(front)?;
+        let flags = u32::decode(front)?;
+        let version = EVersion::decode(front)?;
+        let num_ops = u16::decode(front)? as usize;
+        let mut op_results = Vec::with_capacity(num_ops);
+        for _ in 0..num_ops {
+            op_results.push(OpResult {
+                rval: i32::decode(front)?,
+                outdata: Bytes::decode(front)?,
+            });
+        }
+        Ok(MOSDOpReply {
+            result,
+            flags,
+            version,
+            op_results,
+        })
+    }
+
+    /// The object size reported by an `APPEND` op, when the reply carries
+    /// one: a successful first op result whose outdata is a single `u64`.
+    pub fn appended_size(&self) -> Option<u64> {
+        self.op_results
+            .first()
+            .filter(|op| op.rval >= 0 && op.outdata.len() == 8)
+            .map(|op| u64::from_le_bytes(op.outdata[..].try_into().unwrap()))
+    }
+}
+
+/// Notify event kinds carried by `MWatchNotify` (`CEPH_WATCH_EVENT_*`).
+pub const CEPH_WATCH_EVENT_NOTIFY: u8 = 1;
+pub const CEPH_WATCH_EVENT_NOTIFY_COMPLETE: u8 = 2;
+pub const CEPH_WATCH_EVENT_DISCONNECT: u8 = 3;
+
+/// A notification pushed from the OSD to a watcher.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MWatchNotify {
+    pub cookie: u64,
+    pub notify_id: u64,
+    pub opcode: u8,
+    pub notifier_gid: u64,
+    pub timestamp: UTime,
+    pub payload: Bytes,
+}
+
+impl MWatchNotify {
+    pub fn encode_front(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.cookie.encode(&mut buf);
+        self.notify_id.encode(&mut buf);
+        self.opcode.encode(&mut buf);
+        self.notifier_gid.encode(&mut buf);
+        self.timestamp.encode(&mut buf);
+        self.payload.encode(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn decode_front(front: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(MWatchNotify {
+            cookie: u64::decode(front)?,
+            notify_id: u64::decode(front)?,
+            opcode: u8::decode(front)?,
+            notifier_gid: u64::decode(front)?,
+            timestamp: UTime::decode(front)?,
+            payload: Bytes::decode(front)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::OSDOp;
+
+    #[test]
+    fn mosdop_round_trip() {
+        let mut op = MOSDOp::new(
+            PgId::new(3, 0x1f),
+            "rbd_header.10ab",
+            vec![OSDOp::stat(), OSDOp::read(0, 4096)],
+        );
+        op.nspace = "backup".to_string();
+        op.snapc = SnapContext {
+            seq: 8,
+            snaps: vec![8, 5, 2],
+        };
+        let mut front = op.encode_front();
+        assert_eq!(MOSDOp::decode_front(&mut front).unwrap(), op);
+
+        // The namespace is part of the object's identity on the wire.
+        let mut other = op.clone();
+        other.nspace = String::new();
+        assert_ne!(other.encode_front(), op.encode_front());
+
+        // So is the snap context: writes under different contexts must
+        // not compare equal on the wire.
+        let mut other = op.clone();
+        other.snapc = SnapContext::default();
+        assert_ne!(other.encode_front(), op.encode_front());
+    }
+
+    #[test]
+    fn reply_round_trip() {
+        let reply = MOSDOpReply {
+            result: 0,
+            flags: 0,
+            version: EVersion {
+                version: 9,
+                epoch: 4,
+            },
+            op_results: vec![
+                OpResult {
+                    rval: 0,
+                    outdata: Bytes::from_static(b"data"),
+                },
+                OpResult {
+                    rval: -2,
+                    outdata: Bytes::new(),
+                },
+            ],
+        };
+        let mut front = reply.encode_front();
+        assert_eq!(MOSDOpReply::decode_front(&mut front).unwrap(), reply);
+    }
+
+    #[test]
+    fn append_reply_carries_the_new_size() {
+        let mut reply = MOSDOpReply {
+            op_results: vec![OpResult {
+                rval: 0,
+                outdata: Bytes::copy_from_slice(&4096u64.to_le_bytes()),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(reply.appended_size(), Some(4096));
+
+        // Older OSDs omit the size; a failed op never reports one.
+        reply.op_results[0].outdata = Bytes::new();
+        assert_eq!(reply.appended_size(), None);
+        reply.op_results[0] = OpResult {
+            rval: -28,
+            outdata: Bytes::copy_from_slice(&[0u8; 8]),
+        };
+        assert_eq!(reply.appended_size(), None);
+    }
+
+    #[test]
+    fn watch_notify_round_trip() {
+        let notify = MWatchNotify {
+            cookie: 0xc00c1e,
+            notify_id: 12,
+            opcode: CEPH_WATCH_EVENT_NOTIFY,
+            notifier_gid: 4444,
+            timestamp: UTime::new(1_700_000_000, 0),
+            payload: Bytes::from_static(b"refresh"),
+        };
+        let mut front = notify.encode_front();
+        assert_eq!(MWatchNotify::decode_front(&mut front).unwrap(), notify);
+    }
+}
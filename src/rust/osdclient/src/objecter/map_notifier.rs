@@ -0,0 +1,295 @@
+//! Fan-out of new cluster maps to interested tasks.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, watch};
+
+/// Anything with a monotonically increasing epoch.
+pub trait MapLike: Send + Sync {
+    fn epoch(&self) -> u32;
+}
+
+impl MapLike for crate::osdmap::OSDMap {
+    fn epoch(&self) -> u32 {
+        self.epoch
+    }
+}
+
+struct Subscriber<M> {
+    id: u64,
+    tx: mpsc::UnboundedSender<Arc<M>>,
+}
+
+struct NotifierInner<M> {
+    latest: Option<Arc<M>>,
+    subscribers: Vec<Subscriber<M>>,
+    next_id: u64,
+}
+
+/// Publishes each new map epoch to every subscriber.
+///
+/// Stale maps (epoch not newer than the latest published) are dropped, so
+/// subscribers observe a strictly increasing epoch sequence.
+pub struct MapNotifier<M: MapLike> {
+    inner: Arc<Mutex<NotifierInner<M>>>,
+    /// Mirrors `latest` for tasks that only want to wait for the first (or
+    /// a newer) map rather than consume every epoch.
+    watch_tx: watch::Sender<Option<Arc<M>>>,
+}
+
+impl<M: MapLike> Default for MapNotifier<M> {
+    fn default() -> Self {
+        MapNotifier {
+            inner: Arc::new(Mutex::new(NotifierInner {
+                latest: None,
+                subscribers: Vec::new(),
+                next_id: 0,
+            })),
+            watch_tx: watch::channel(None).0,
+        }
+    }
+}
+
+/// One subscription to a [`MapNotifier`].  Dropping the handle
+/// unsubscribes immediately, so the notifier never queues maps for a
+/// receiver nobody is draining.
+pub struct SubscriptionHandle<M: MapLike> {
+    id: u64,
+    inner: Arc<Mutex<NotifierInner<M>>>,
+    receiver: mpsc::UnboundedReceiver<Arc<M>>,
+}
+
+impl<M: MapLike> SubscriptionHandle<M> {
+    /// The next published map; `None` once the notifier is gone.
+    pub async fn recv(&mut self) -> Option<Arc<M>> {
+        self.receiver.recv().await
+    }
+}
+
+impl<M: MapLike> Drop for SubscriptionHandle<M> {
+    fn drop(&mut self) {
+        self.inner
+            .lock()
+            .unwrap()
+            .subscribers
+            .retain(|s| s.id != self.id);
+    }
+}
+
+impl<M: MapLike> MapNotifier<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most recently published map.
+    pub fn latest(&self) -> Option<Arc<M>> {
+        self.inner.lock().unwrap().latest.clone()
+    }
+
+    /// Subscribes to future maps only.
+    pub fn subscribe(&self) -> SubscriptionHandle<M> {
+        self.subscribe_inner(false)
+    }
+
+    /// A watch on the latest map; starts out `None` until the first
+    /// publish.
+    pub fn watch(&self) -> watch::Receiver<Option<Arc<M>>> {
+        self.watch_tx.subscribe()
+    }
+
+    /// Subscribes, immediately replaying the latest map if there is one.
+    pub fn subscribe_with_replay(&self) -> SubscriptionHandle<M> {
+        self.subscribe_inner(true)
+    }
+
+    fn subscribe_inner(&self, replay: bool) -> SubscriptionHandle<M> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut inner = self.inner.lock().unwrap();
+        if replay {
+            if let Some(latest) = &inner.latest {
+                let _ = tx.send(latest.clone());
+            }
+        }
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.subscribers.push(Subscriber { id, tx });
+        SubscriptionHandle {
+            id,
+            inner: self.inner.clone(),
+            receiver: rx,
+        }
+    }
+
+    /// How many subscriptions are still live.  Handles unsubscribe on
+    /// drop, so this never counts receivers nobody is draining.
+    pub fn active_subscriber_count(&self) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        inner.subscribers.retain(|s| !s.tx.is_closed());
+        inner.subscribers.len()
+    }
+
+    /// Publishes `map` if its epoch is newer than the latest; returns
+    /// whether it was accepted.  Closed subscribers are pruned.
+    pub fn publish(&self, map: Arc<M>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(latest) = &inner.latest {
+            if map.epoch() <= latest.epoch() {
+                return false;
+            }
+        }
+        inner.latest = Some(map.clone());
+        inner.subscribers.retain(|s| s.tx.send(map.clone()).is_ok());
+        self.watch_tx.send_replace(Some(map));
+        true
+    }
+
+    /// Starts a batch: maps staged with [`BatchPublisher::publish`] are
+    /// held back until [`BatchPublisher::commit`] delivers them all under
+    /// one lock, so a subscriber sees either none or all of the batch.
+    pub fn begin_batch(&self) -> BatchPublisher<'_, M> {
+        BatchPublisher {
+            notifier: self,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// Stages maps for an atomic multi-map publish; see
+/// [`MapNotifier::begin_batch`].  Dropping an uncommitted batch discards
+/// the staged maps.
+pub struct BatchPublisher<'a, M: MapLike> {
+    notifier: &'a MapNotifier<M>,
+    pending: Vec<Arc<M>>,
+}
+
+impl<M: MapLike> BatchPublisher<'_, M> {
+    /// Stages `map` for delivery on commit.
+    pub fn publish(&mut self, map: Arc<M>) {
+        self.pending.push(map);
+    }
+
+    /// Delivers every staged map in order, atomically with respect to
+    /// subscription: nobody subscribes between the first and last map.
+    /// Stale epochs are dropped as in [`MapNotifier::publish`]; returns
+    /// how many maps were accepted.
+    pub fn commit(self) -> usize {
+        let mut inner = self.notifier.inner.lock().unwrap();
+        let mut accepted = Vec::with_capacity(self.pending.len());
+        for map in self.pending {
+            if let Some(latest) = &inner.latest {
+                if map.epoch() <= latest.epoch() {
+                    continue;
+                }
+            }
+            inner.latest = Some(map.clone());
+            accepted.push(map);
+        }
+        if accepted.is_empty() {
+            return 0;
+        }
+        inner.subscribers.retain(|s| {
+            accepted.iter().all(|map| s.tx.send(map.clone()).is_ok())
+        });
+        self.notifier
+            .watch_tx
+            .send_replace(Some(accepted.last().unwrap().clone()));
+        accepted.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Epoch(u32);
+
+    impl MapLike for Epoch {
+        fn epoch(&self) -> u32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn stale_epochs_are_dropped() {
+        let notifier = MapNotifier::new();
+        assert!(notifier.publish(Arc::new(Epoch(5))));
+        assert!(!notifier.publish(Arc::new(Epoch(5))));
+        assert!(!notifier.publish(Arc::new(Epoch(4))));
+        assert!(notifier.publish(Arc::new(Epoch(6))));
+        assert_eq!(notifier.latest().unwrap().epoch(), 6);
+    }
+
+    #[tokio::test]
+    async fn replay_delivers_latest_then_updates() {
+        let notifier = MapNotifier::new();
+        notifier.publish(Arc::new(Epoch(3)));
+        let mut rx = notifier.subscribe_with_replay();
+        assert_eq!(rx.recv().await.unwrap().epoch(), 3);
+        notifier.publish(Arc::new(Epoch(4)));
+        assert_eq!(rx.recv().await.unwrap().epoch(), 4);
+    }
+
+    #[tokio::test]
+    async fn watch_resolves_once_a_map_arrives() {
+        let notifier = MapNotifier::new();
+        let mut rx = notifier.watch();
+        assert!(rx.borrow().is_none());
+        notifier.publish(Arc::new(Epoch(1)));
+        rx.changed().await.unwrap();
+        assert_eq!(rx.borrow().as_ref().unwrap().epoch(), 1);
+    }
+
+    #[tokio::test]
+    async fn plain_subscribe_skips_the_current_map() {
+        let notifier = MapNotifier::new();
+        notifier.publish(Arc::new(Epoch(3)));
+        let mut rx = notifier.subscribe();
+        notifier.publish(Arc::new(Epoch(4)));
+        assert_eq!(rx.recv().await.unwrap().epoch(), 4);
+    }
+
+    #[tokio::test]
+    async fn a_batch_is_delivered_all_at_once() {
+        let notifier = MapNotifier::new();
+        notifier.publish(Arc::new(Epoch(2)));
+        let mut rx = notifier.subscribe();
+
+        let mut batch = notifier.begin_batch();
+        batch.publish(Arc::new(Epoch(3)));
+        batch.publish(Arc::new(Epoch(1))); // stale, dropped
+        batch.publish(Arc::new(Epoch(4)));
+        // Nothing is visible until commit.
+        assert_eq!(notifier.latest().unwrap().epoch(), 2);
+        assert_eq!(batch.commit(), 2);
+
+        assert_eq!(rx.recv().await.unwrap().epoch(), 3);
+        assert_eq!(rx.recv().await.unwrap().epoch(), 4);
+        assert_eq!(notifier.latest().unwrap().epoch(), 4);
+    }
+
+    #[test]
+    fn an_uncommitted_batch_publishes_nothing() {
+        let notifier = MapNotifier::new();
+        let mut batch = notifier.begin_batch();
+        batch.publish(Arc::new(Epoch(9)));
+        drop(batch);
+        assert!(notifier.latest().is_none());
+    }
+
+    #[tokio::test]
+    async fn dropped_handles_unsubscribe_immediately() {
+        let notifier = MapNotifier::new();
+        let first = notifier.subscribe();
+        let second = notifier.subscribe_with_replay();
+        assert_eq!(notifier.active_subscriber_count(), 2);
+
+        drop(first);
+        assert_eq!(notifier.active_subscriber_count(), 1);
+        // A dropped handle no longer accumulates lag on publish.
+        notifier.publish(Arc::new(Epoch(1)));
+        assert_eq!(notifier.active_subscriber_count(), 1);
+
+        drop(second);
+        assert_eq!(notifier.active_subscriber_count(), 0);
+    }
+}
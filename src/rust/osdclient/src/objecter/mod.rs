@@ -0,0 +1,5 @@
+//! Objecter support: map distribution to interested subscribers.
+
+pub mod map_notifier;
+
+pub use map_notifier::{MapLike, MapNotifier};
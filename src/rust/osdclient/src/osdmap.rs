@@ -0,0 +1,1356 @@
+//! The OSDMap: cluster membership, pools and placement state.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use bytes::{Bytes, BytesMut};
+use crush::hash::crush_hash_rjenkins1_2;
+use crush::placement::PoolParams;
+use crush::{crush_do_rule, pg_upmap_lookup, CrushMap, PgId};
+use denc::entity_addr::EntityAddrvec;
+use denc::features::CEPH_FEATURE_SERVER_LUMINOUS;
+use denc::types::{FsId, UTime};
+use denc::{Denc, RadosError, VersionedEncode};
+
+use crate::error::OSDClientError;
+
+/// OSD state bits (`CEPH_OSD_*` in `include/rados.h`).
+pub const CEPH_OSD_EXISTS: u32 = 1 << 0;
+pub const CEPH_OSD_UP: u32 = 1 << 1;
+
+/// `osd_weight` values are 16.16 fixed point; this is weight 1.0.
+pub const CEPH_OSD_IN: u32 = 0x10000;
+pub const CEPH_OSD_OUT: u32 = 0;
+
+/// One snapshot of a pool (`pool_snap_info_t`).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PoolSnapInfo {
+    pub snap_id: u64,
+    pub stamp: UTime,
+    pub name: String,
+}
+
+impl Denc for PoolSnapInfo {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.snap_id.encode(buf);
+        self.stamp.encode(buf);
+        self.name.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(PoolSnapInfo {
+            snap_id: u64::decode(buf)?,
+            stamp: UTime::decode(buf)?,
+            name: String::decode(buf)?,
+        })
+    }
+}
+
+/// Parameters of a Bloom-filter hit set (`BloomHitSet::Params`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BloomHitSetParams {
+    /// Target false positive probability in parts per million.
+    pub fpp_micro: u32,
+    /// Target filter size in bytes.
+    pub target_size: u64,
+    pub seed: u32,
+}
+
+impl BloomHitSetParams {
+    /// The target false-positive probability as a fraction.
+    pub fn fpp(&self) -> f64 {
+        f64::from(self.fpp_micro) / 1_000_000.0
+    }
+
+    /// The number of hash functions an optimally parameterised filter
+    /// uses: `k = log2(1 / fpp)`, rounded up.
+    fn num_hashes(&self) -> f64 {
+        (-self.fpp().log2()).ceil().max(1.0)
+    }
+
+    /// How many items a filter of `target_size` bytes can hold while
+    /// staying at the target false-positive probability, from the
+    /// standard sizing formula `m = -n * ln(fpp) / (ln 2)^2` solved
+    /// for `n`.
+    pub fn expected_capacity(&self) -> u64 {
+        if self.fpp_micro == 0 || self.target_size == 0 {
+            return 0;
+        }
+        let m = (self.target_size * 8) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        (m * ln2 * ln2 / -self.fpp().ln()) as u64
+    }
+
+    /// The false-positive probability the filter actually exhibits after
+    /// `items` insertions: `(1 - e^(-k * n / m))^k`.
+    pub fn actual_fpp(&self, items: u64) -> f64 {
+        if self.target_size == 0 {
+            return 1.0;
+        }
+        let m = (self.target_size * 8) as f64;
+        let k = self.num_hashes();
+        (1.0 - (-k * items as f64 / m).exp()).powf(k)
+    }
+}
+
+/// Per-pool configuration (`pg_pool_t`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgPool {
+    pub id: u64,
+    pub pool_type: u8,
+    pub size: u32,
+    pub min_size: u32,
+    pub pg_num: u32,
+    pub pg_num_target: u32,
+    pub crush_rule: u32,
+    pub object_hash: u8,
+    pub flags: u64,
+    pub snaps: BTreeMap<u64, PoolSnapInfo>,
+    pub expected_num_objects: u64,
+}
+
+impl Default for PgPool {
+    fn default() -> Self {
+        PgPool {
+            id: 0,
+            pool_type: 1, // replicated
+            size: 3,
+            min_size: 2,
+            pg_num: 32,
+            pg_num_target: 32,
+            crush_rule: 0,
+            object_hash: 2, // rjenkins
+            flags: 0,
+            snaps: BTreeMap::new(),
+            expected_num_objects: 0,
+        }
+    }
+}
+
+/// Set on pools managed by the pg_autoscaler (`pg_pool_t::FLAG_*`).
+pub const CEPH_POOL_FLAG_AUTOSCALE: u64 = 1 << 15;
+
+impl PgPool {
+    /// The most data one PG should be expected to carry; see
+    /// [`PgPool::target_pg_num_from_expected`].
+    pub const TARGET_PG_BYTES: u64 = 100 << 30;
+
+    /// Whether the autoscaler is still moving this pool towards its
+    /// target PG count.
+    pub fn is_autoscaling(&self) -> bool {
+        self.pg_num != self.pg_num_target
+    }
+
+    /// The PG count placement should plan for: `pg_num`, or
+    /// `pg_num_target` while an autoscale is pending.
+    pub fn effective_pg_num(&self) -> u32 {
+        if !self.is_autoscaling() {
+            return self.pg_num;
+        }
+        tracing::warn!(
+            "pool {} is autoscaling: pg_num {} -> target {}",
+            self.id,
+            self.pg_num,
+            self.pg_num_target
+        );
+        self.pg_num_target
+    }
+
+    /// The pool's snapshots ordered by creation stamp rather than snap
+    /// id; the two differ once snap ids are reused after deletions.
+    pub fn snapshots_chronological(&self) -> impl Iterator<Item = (u64, &PoolSnapInfo)> {
+        let mut snaps: Vec<_> = self.snaps.iter().map(|(id, snap)| (*id, snap)).collect();
+        snaps.sort_by_key(|(_, snap)| snap.stamp);
+        snaps.into_iter()
+    }
+
+    pub fn snap_by_name(&self, name: &str) -> Option<(u64, &PoolSnapInfo)> {
+        self.snaps
+            .iter()
+            .find(|(_, snap)| snap.name == name)
+            .map(|(id, snap)| (*id, snap))
+    }
+
+    /// The `expected_num_objects` creation hint (Luminous).  Zero means
+    /// the administrator gave no estimate.
+    pub fn expected_num_objects(&self) -> u64 {
+        self.expected_num_objects
+    }
+
+    /// The PG count this pool should aim for, given its
+    /// `expected_num_objects` hint.
+    ///
+    /// Each PG should hold about `target_objects_per_pg` objects and no
+    /// more than [`PgPool::TARGET_PG_BYTES`] of data (estimated as
+    /// `expected_num_objects * avg_object_size_bytes`); whichever limit
+    /// demands the finer split wins.  The result is rounded up to the
+    /// next power of two, since PG counts are kept at powers of two to
+    /// make splits cheap.  Without a hint there is nothing to plan with
+    /// and the current `pg_num` is returned unchanged.
+    pub fn target_pg_num_from_expected(
+        &self,
+        avg_object_size_bytes: u64,
+        target_objects_per_pg: u32,
+    ) -> u32 {
+        if self.expected_num_objects == 0 {
+            return self.pg_num;
+        }
+        let by_objects =
+            self.expected_num_objects.div_ceil(target_objects_per_pg.max(1) as u64);
+        let by_bytes = self
+            .expected_num_objects
+            .saturating_mul(avg_object_size_bytes)
+            .div_ceil(Self::TARGET_PG_BYTES);
+        let wanted = by_objects.max(by_bytes).max(1);
+        u32::try_from(wanted)
+            .map(u32::next_power_of_two)
+            .unwrap_or(u32::MAX)
+    }
+}
+
+impl PoolParams for PgPool {
+    fn pool_id(&self) -> u64 {
+        self.id
+    }
+
+    fn pg_num(&self) -> u32 {
+        self.pg_num
+    }
+
+    fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn crush_rule(&self) -> u32 {
+        self.crush_rule
+    }
+}
+
+impl VersionedEncode for PgPool {
+    const VERSION: u8 = 1;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.id.encode(buf);
+        self.pool_type.encode(buf);
+        self.size.encode(buf);
+        self.min_size.encode(buf);
+        self.pg_num.encode(buf);
+        self.pg_num_target.encode(buf);
+        self.crush_rule.encode(buf);
+        self.object_hash.encode(buf);
+        self.flags.encode(buf);
+        (self.snaps.len() as u32).encode(buf);
+        for snap in self.snaps.values() {
+            snap.encode(buf);
+        }
+        self.expected_num_objects.encode(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        let id = u64::decode(buf)?;
+        let pool_type = u8::decode(buf)?;
+        let size = u32::decode(buf)?;
+        let min_size = u32::decode(buf)?;
+        let pg_num = u32::decode(buf)?;
+        let pg_num_target = u32::decode(buf)?;
+        let crush_rule = u32::decode(buf)?;
+        let object_hash = u8::decode(buf)?;
+        let flags = u64::decode(buf)?;
+        let num_snaps = u32::decode(buf)? as usize;
+        let mut snaps = BTreeMap::new();
+        for _ in 0..num_snaps {
+            let snap = PoolSnapInfo::decode(buf)?;
+            snaps.insert(snap.snap_id, snap);
+        }
+        Ok(PgPool {
+            id,
+            pool_type,
+            size,
+            min_size,
+            pg_num,
+            pg_num_target,
+            crush_rule,
+            object_hash,
+            flags,
+            snaps,
+            expected_num_objects: u64::decode(buf)?,
+        })
+    }
+}
+
+/// Liveness history of one OSD (`osd_info_t`), all fields epochs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OsdInfo {
+    pub up_from: u32,
+    pub up_thru: u32,
+    pub down_at: u32,
+    pub last_clean_begin: u32,
+    pub last_clean_end: u32,
+}
+
+/// Epoch sentinel stored in every [`OsdInfo`] field when the OSD has been
+/// destroyed (`ceph osd destroy`) but its id not yet reused.
+pub const OSD_EPOCH_DESTROYED: u32 = u32::MAX;
+
+impl OsdInfo {
+    /// Whether this slot no longer describes a live OSD: either every
+    /// field carries the destroyed sentinel, or the slot was zeroed when
+    /// the id was reclaimed.
+    pub fn is_destroyed(&self) -> bool {
+        let fields = [
+            self.up_from,
+            self.up_thru,
+            self.down_at,
+            self.last_clean_begin,
+            self.last_clean_end,
+        ];
+        fields.iter().all(|&e| e == 0) || fields.iter().all(|&e| e == OSD_EPOCH_DESTROYED)
+    }
+
+    /// The span of the last closed clean interval, in epochs.  `None`
+    /// until one closes; the map records epochs, not wall-clock times.
+    pub fn last_clean_interval(&self) -> Option<u32> {
+        if !self.was_ever_clean() {
+            return None;
+        }
+        self.last_clean_end.checked_sub(self.last_clean_begin)
+    }
+
+    pub fn was_ever_clean(&self) -> bool {
+        self.last_clean_end != 0
+    }
+}
+
+impl Denc for OsdInfo {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.up_from.encode(buf);
+        self.up_thru.encode(buf);
+        self.down_at.encode(buf);
+        self.last_clean_begin.encode(buf);
+        self.last_clean_end.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(OsdInfo {
+            up_from: u32::decode(buf)?,
+            up_thru: u32::decode(buf)?,
+            down_at: u32::decode(buf)?,
+            last_clean_begin: u32::decode(buf)?,
+            last_clean_end: u32::decode(buf)?,
+        })
+    }
+}
+
+/// Extended OSD state (`osd_xinfo_t`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OsdXInfo {
+    pub down_stamp: UTime,
+    /// Probability this OSD is laggy, as a 32-bit fixed-point fraction.
+    pub laggy_probability: u32,
+    pub laggy_interval: u32,
+    pub features: u64,
+    pub dead_epoch: u32,
+}
+
+impl OsdXInfo {
+    /// Whether the OSD last reported a feature set predating luminous.
+    /// False for a zeroed slot: no features were ever reported.
+    pub fn is_pre_luminous(&self) -> bool {
+        self.features != 0 && self.features & CEPH_FEATURE_SERVER_LUMINOUS == 0
+    }
+
+    /// Whether the OSD had already been confirmed dead as of `epoch`.
+    /// A zero `dead_epoch` means it was never confirmed dead.
+    pub fn was_dead_at_epoch(&self, epoch: u32) -> bool {
+        self.dead_epoch != 0 && self.dead_epoch <= epoch
+    }
+
+    /// Whether the recorded laggy probability reaches
+    /// `probability_threshold` (a fraction in `0.0..=1.0`; the stored
+    /// value is fixed-point with `u32::MAX` meaning certainty).
+    pub fn was_laggy_at(&self, probability_threshold: f32) -> bool {
+        self.laggy_probability as f64 / u32::MAX as f64 >= probability_threshold as f64
+    }
+
+    /// Whether the dead record is too old to act on: the OSD was
+    /// confirmed dead more than `stale_threshold` epochs before
+    /// `current_epoch`.  False when it was never confirmed dead.
+    pub fn is_stale(&self, current_epoch: u32, stale_threshold: u32) -> bool {
+        self.dead_epoch != 0 && self.dead_epoch.saturating_add(stale_threshold) < current_epoch
+    }
+}
+
+impl Denc for OsdXInfo {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.down_stamp.encode(buf);
+        self.laggy_probability.encode(buf);
+        self.laggy_interval.encode(buf);
+        self.features.encode(buf);
+        self.dead_epoch.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(OsdXInfo {
+            down_stamp: UTime::decode(buf)?,
+            laggy_probability: u32::decode(buf)?,
+            laggy_interval: u32::decode(buf)?,
+            features: u64::decode(buf)?,
+            dead_epoch: u32::decode(buf)?,
+        })
+    }
+}
+
+/// A compact set of EC shard ids, stored as a 128-bit bitmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShardIdSet {
+    pub words: [u64; 2],
+}
+
+impl ShardIdSet {
+    pub const MAX_SHARDS: u8 = 128;
+
+    pub fn insert(&mut self, shard: u8) {
+        self.words[(shard / 64) as usize] |= 1u64 << (shard % 64);
+    }
+
+    pub fn contains(&self, shard: u8) -> bool {
+        self.words[(shard / 64) as usize] & (1u64 << (shard % 64)) != 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..Self::MAX_SHARDS).filter(|&s| self.contains(s))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words == [0, 0]
+    }
+
+    pub fn union(&self, other: &ShardIdSet) -> ShardIdSet {
+        self.zip_words(other, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, other: &ShardIdSet) -> ShardIdSet {
+        self.zip_words(other, |a, b| a & b)
+    }
+
+    /// The shards in `self` but not in `other`.
+    pub fn difference(&self, other: &ShardIdSet) -> ShardIdSet {
+        self.zip_words(other, |a, b| a & !b)
+    }
+
+    pub fn symmetric_difference(&self, other: &ShardIdSet) -> ShardIdSet {
+        self.zip_words(other, |a, b| a ^ b)
+    }
+
+    fn zip_words(&self, other: &ShardIdSet, op: impl Fn(u64, u64) -> u64) -> ShardIdSet {
+        ShardIdSet {
+            words: [
+                op(self.words[0], other.words[0]),
+                op(self.words[1], other.words[1]),
+            ],
+        }
+    }
+}
+
+/// The OSD map (`OSDMap`).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OSDMap {
+    pub epoch: u32,
+    pub fsid: FsId,
+    pub created: UTime,
+    pub modified: UTime,
+    pub flags: u64,
+    pub pools: BTreeMap<u64, PgPool>,
+    pub pool_name: BTreeMap<u64, String>,
+    /// Per-OSD state bits, weight (16.16), addresses and history, indexed
+    /// by OSD id; all four vectors have the same length.
+    pub osd_state: Vec<u32>,
+    pub osd_weight: Vec<u32>,
+    pub osd_addrs: Vec<EntityAddrvec>,
+    pub osd_info: Vec<OsdInfo>,
+    pub osd_xinfo: Vec<OsdXInfo>,
+    /// Temporary placement overrides installed during peering.
+    pub pg_temp: BTreeMap<PgId, Vec<u32>>,
+    /// Explicit full-mapping overrides (`pg_upmap`).
+    pub pg_upmap: BTreeMap<PgId, Vec<u32>>,
+    /// Per-OSD swap overrides (`pg_upmap_items`), each pair `(from, to)`.
+    pub pg_upmap_items: BTreeMap<PgId, Vec<(u32, u32)>>,
+    /// The raw (still encoded) CRUSH map.
+    pub crush_raw: Bytes,
+    name_to_pool_id: NameIndex,
+    crush: CrushCache,
+}
+
+/// The decoded CRUSH map, parsed from `crush_raw` on first use so each
+/// placement query does not re-decode it.  Like [`NameIndex`] it is
+/// derived state: ignored by comparisons and dropped on clone.
+#[derive(Debug, Default)]
+struct CrushCache(OnceLock<CrushMap>);
+
+impl Clone for CrushCache {
+    fn clone(&self) -> Self {
+        CrushCache::default()
+    }
+}
+
+impl PartialEq for CrushCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// Lazily built reverse index from pool name to pool id, so repeated
+/// [`OSDMap::pool_by_name`] lookups are O(1).  Derived from `pool_name`,
+/// so it is ignored by comparisons and rebuilt after a clone.
+#[derive(Debug, Default)]
+struct NameIndex(OnceLock<HashMap<String, u64>>);
+
+impl Clone for NameIndex {
+    fn clone(&self) -> Self {
+        NameIndex::default()
+    }
+}
+
+impl PartialEq for NameIndex {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl OSDMap {
+    pub fn is_up(&self, osd: u32) -> bool {
+        self.osd_state
+            .get(osd as usize)
+            .is_some_and(|s| s & CEPH_OSD_UP != 0)
+    }
+
+    pub fn is_in(&self, osd: u32) -> bool {
+        self.osd_weight.get(osd as usize).is_some_and(|w| *w > 0)
+    }
+
+    /// One past the highest OSD id this map describes, i.e. the length of
+    /// the per-OSD arrays.
+    pub fn max_osd(&self) -> u32 {
+        self.osd_state.len().max(self.osd_weight.len()) as u32
+    }
+
+    /// Every OSD id the map covers, whatever its state.
+    pub fn all_osd_ids(&self) -> impl Iterator<Item = u32> {
+        0..self.max_osd()
+    }
+
+    /// The OSD ids that are both up and in.
+    pub fn active_osd_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.all_osd_ids()
+            .filter(|&osd| self.is_up(osd) && self.is_in(osd))
+    }
+
+    /// How many OSDs are both up and in.
+    pub fn num_active_osds(&self) -> usize {
+        self.active_osd_ids().count()
+    }
+
+    pub fn pool(&self, id: u64) -> Option<&PgPool> {
+        self.pools.get(&id)
+    }
+
+    pub fn pool_by_id(&self, id: u64) -> Option<&PgPool> {
+        self.pool(id)
+    }
+
+    /// Looks a pool up by name (case-sensitive, like the cluster itself).
+    /// The reverse index is built on first use.
+    pub fn pool_by_name(&self, name: &str) -> Option<(u64, &PgPool)> {
+        let index = self.name_to_pool_id.0.get_or_init(|| {
+            self.pool_name
+                .iter()
+                .map(|(id, name)| (name.clone(), *id))
+                .collect()
+        });
+        let id = *index.get(name)?;
+        Some((id, self.pools.get(&id)?))
+    }
+
+    /// Hashes an object name to its PG within `pool`.
+    pub fn object_to_pg(&self, pool_id: u64, name: &str) -> Result<PgId, OSDClientError> {
+        self.object_to_pg_with_locator(pool_id, name, None)
+    }
+
+    /// Like [`OSDMap::object_to_pg`], but a non-empty locator key replaces
+    /// the object name for placement, co-locating the object with
+    /// whatever the key names.
+    pub fn object_to_pg_with_locator(
+        &self,
+        pool_id: u64,
+        name: &str,
+        locator_key: Option<&str>,
+    ) -> Result<PgId, OSDClientError> {
+        let pool = self
+            .pools
+            .get(&pool_id)
+            .ok_or_else(|| OSDClientError::PoolNotFound(pool_id.to_string()))?;
+        let placement_name = match locator_key {
+            Some(key) if !key.is_empty() => key,
+            _ => name,
+        };
+        let hash = str_hash_rjenkins(placement_name);
+        Ok(PgId::new(pool_id, hash % pool.pg_num))
+    }
+
+    /// Compares this map (the older epoch) with `other` (the newer one)
+    /// and summarizes what changed.
+    pub fn diff(&self, other: &OSDMap) -> OSDMapDiff {
+        let old_osds = self.max_osd() as usize;
+        let new_osds = other.max_osd() as usize;
+        let added_osds = (old_osds..new_osds).map(|osd| osd as u32).collect();
+        let removed_osds = (new_osds..old_osds).map(|osd| osd as u32).collect();
+        let changed_weights = (0..old_osds.min(new_osds))
+            .filter_map(|osd| {
+                let old = *self.osd_weight.get(osd)?;
+                let new = *other.osd_weight.get(osd)?;
+                (old != new).then(|| {
+                    (
+                        osd as u32,
+                        old as f32 / CEPH_OSD_IN as f32,
+                        new as f32 / CEPH_OSD_IN as f32,
+                    )
+                })
+            })
+            .collect();
+        let added_pools = other
+            .pool_name
+            .iter()
+            .filter(|(id, _)| !self.pools.contains_key(id))
+            .map(|(id, name)| (*id, name.clone()))
+            .collect();
+        let removed_pools = self
+            .pool_name
+            .iter()
+            .filter(|(id, _)| !other.pools.contains_key(id))
+            .map(|(id, name)| (*id, name.clone()))
+            .collect();
+        let pg_temp_changes = self
+            .pg_temp
+            .iter()
+            .filter(|(pg, acting)| other.pg_temp.get(pg) != Some(acting))
+            .count()
+            + other
+                .pg_temp
+                .keys()
+                .filter(|pg| !self.pg_temp.contains_key(pg))
+                .count();
+        OSDMapDiff {
+            added_osds,
+            removed_osds,
+            changed_weights,
+            added_pools,
+            removed_pools,
+            pg_temp_changes,
+        }
+    }
+
+    /// The OSDs currently acting for `pg`, primary first.
+    ///
+    /// `pg_temp` overrides take precedence over the CRUSH computation;
+    /// `pg_upmap` / `pg_upmap_items` entries are applied to the CRUSH
+    /// result.
+    /// The decoded CRUSH map, parsed from [`OSDMap::crush_raw`] on first
+    /// use and cached for the lifetime of this map epoch.
+    pub fn crush_map(&self) -> Result<&CrushMap, OSDClientError> {
+        if let Some(map) = self.crush.0.get() {
+            return Ok(map);
+        }
+        if self.crush_raw.is_empty() {
+            return Err(OSDClientError::Crush(crush::CrushError::Integrity(
+                "the OSDMap carries no CRUSH map".to_string(),
+            )));
+        }
+        let decoded = crush::decode_crush_map(&mut self.crush_raw.clone())?;
+        Ok(self.crush.0.get_or_init(|| decoded))
+    }
+
+    pub fn pg_acting_set(&self, pg: PgId) -> Result<Vec<u32>, OSDClientError> {
+        if let Some(acting) = self.pg_temp.get(&pg) {
+            return Ok(acting.clone());
+        }
+        self.pg_crush_acting_set(pg)
+    }
+
+    /// The acting set CRUSH (plus upmap overrides and up/in filtering)
+    /// would assign to `pg`, ignoring any `pg_temp` override.
+    fn pg_crush_acting_set(&self, pg: PgId) -> Result<Vec<u32>, OSDClientError> {
+        let pool = self
+            .pools
+            .get(&pg.pool)
+            .ok_or_else(|| OSDClientError::PoolNotFound(pg.pool.to_string()))?;
+        crush::validate_pg_id(pg, pool)?;
+        let crush_map = self.crush_map()?;
+        let x = crush_hash_rjenkins1_2(pg.ps(), pg.pool as u32);
+        let osds = crush_do_rule(crush_map, pool.crush_rule, x, pool.size)?;
+        // The CRUSH map can name devices this epoch's OSD arrays do not
+        // cover yet; they cannot serve the PG either way.
+        let max_osd = self.max_osd();
+        let crush_result: Vec<u32> = osds
+            .into_iter()
+            .filter(|&osd| osd >= 0 && (osd as u32) < max_osd)
+            .map(|osd| osd as u32)
+            .collect();
+        let mapped = pg_upmap_lookup(pg, &crush_result, &self.pg_upmap, &self.pg_upmap_items)
+            .unwrap_or(crush_result);
+        Ok(mapped
+            .into_iter()
+            .filter(|&osd| self.is_up(osd) && self.is_in(osd))
+            .collect())
+    }
+
+    /// The primary OSD for `pg`, if any OSD is acting.
+    pub fn pg_primary(&self, pg: PgId) -> Result<Option<u32>, OSDClientError> {
+        Ok(self.pg_acting_set(pg)?.first().copied())
+    }
+
+    /// Picks the OSD in `osds` with the smallest latency as reported by
+    /// `latency_fn`; OSDs the function cannot measure (`None`) are
+    /// skipped.  Returns `None` when nothing is measurable.
+    pub fn nearest_osd_from_set<F>(osds: &[u32], latency_fn: F) -> Option<u32>
+    where
+        F: Fn(u32) -> Option<std::time::Duration>,
+    {
+        osds.iter()
+            .filter_map(|&osd| latency_fn(osd).map(|latency| (latency, osd)))
+            .min_by_key(|&(latency, _)| latency)
+            .map(|(_, osd)| osd)
+    }
+
+    /// Drops `pg_temp` entries that no longer override anything: once
+    /// peering has moved a PG back to its CRUSH mapping, the stored list
+    /// matches what CRUSH computes and the entry is dead weight.  Entries
+    /// whose mapping cannot be computed (pool deleted, CRUSH error) are
+    /// kept.  Returns how many entries were removed.
+    pub fn cleanup_pg_temp(&mut self) -> usize {
+        let stale: Vec<PgId> = self
+            .pg_temp
+            .iter()
+            .filter(|(&pg, stored)| {
+                self.pg_crush_acting_set(pg)
+                    .is_ok_and(|computed| computed == **stored)
+            })
+            .map(|(&pg, _)| pg)
+            .collect();
+        for pg in &stale {
+            self.pg_temp.remove(pg);
+        }
+        stale.len()
+    }
+}
+
+/// Ceph's string hash (rjenkins flavor), used for object placement.
+pub fn str_hash_rjenkins(s: &str) -> u32 {
+    let mut hash = 0u32;
+    for chunk in s.as_bytes().chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        hash = crush_hash_rjenkins1_2(hash, u32::from_le_bytes(word));
+    }
+    hash
+}
+
+/// What changed between two OSDMap epochs; see [`OSDMap::diff`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OSDMapDiff {
+    pub added_osds: Vec<u32>,
+    pub removed_osds: Vec<u32>,
+    /// `(osd, old weight, new weight)`, weights normalized to `[0, 1]`.
+    pub changed_weights: Vec<(u32, f32, f32)>,
+    pub added_pools: Vec<(u64, String)>,
+    pub removed_pools: Vec<(u64, String)>,
+    pub pg_temp_changes: usize,
+}
+
+impl std::fmt::Display for OSDMapDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for osd in &self.added_osds {
+            writeln!(f, "osd.{osd} added")?;
+        }
+        for osd in &self.removed_osds {
+            writeln!(f, "osd.{osd} removed")?;
+        }
+        for (osd, old, new) in &self.changed_weights {
+            writeln!(f, "osd.{osd} weight {old:.4} -> {new:.4}")?;
+        }
+        for (id, name) in &self.added_pools {
+            writeln!(f, "pool {id} ({name}) added")?;
+        }
+        for (id, name) in &self.removed_pools {
+            writeln!(f, "pool {id} ({name}) removed")?;
+        }
+        if self.pg_temp_changes > 0 {
+            writeln!(f, "{} pg_temp mapping(s) changed", self.pg_temp_changes)?;
+        }
+        Ok(())
+    }
+}
+
+impl VersionedEncode for OSDMap {
+    const VERSION: u8 = 1;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.epoch.encode(buf);
+        self.fsid.encode(buf);
+        self.created.encode(buf);
+        self.modified.encode(buf);
+        self.flags.encode(buf);
+        (self.pools.len() as u32).encode(buf);
+        for (id, pool) in &self.pools {
+            id.encode(buf);
+            pool.encode_versioned(buf);
+        }
+        (self.pool_name.len() as u32).encode(buf);
+        for (id, name) in &self.pool_name {
+            id.encode(buf);
+            name.encode(buf);
+        }
+        self.osd_state.encode(buf);
+        self.osd_weight.encode(buf);
+        (self.osd_addrs.len() as u32).encode(buf);
+        for addrs in &self.osd_addrs {
+            addrs.encode_versioned(buf);
+        }
+        self.osd_info.encode(buf);
+        self.osd_xinfo.encode(buf);
+        (self.pg_temp.len() as u32).encode(buf);
+        for (pg, acting) in &self.pg_temp {
+            pg.encode(buf);
+            acting.encode(buf);
+        }
+        self.pg_upmap.encode(buf);
+        self.pg_upmap_items.encode(buf);
+        self.crush_raw.encode(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        let epoch = u32::decode(buf)?;
+        let fsid = FsId::decode(buf)?;
+        let created = UTime::decode(buf)?;
+        let modified = UTime::decode(buf)?;
+        let flags = u64::decode(buf)?;
+        let num_pools = u32::decode(buf)? as usize;
+        let mut pools = BTreeMap::new();
+        for _ in 0..num_pools {
+            let id = u64::decode(buf)?;
+            pools.insert(id, PgPool::decode_versioned(buf)?);
+        }
+        let num_names = u32::decode(buf)? as usize;
+        let mut pool_name = BTreeMap::new();
+        for _ in 0..num_names {
+            let id = u64::decode(buf)?;
+            pool_name.insert(id, String::decode(buf)?);
+        }
+        let osd_state = Vec::<u32>::decode(buf)?;
+        let osd_weight = Vec::<u32>::decode(buf)?;
+        let num_addrs = u32::decode(buf)? as usize;
+        let mut osd_addrs = Vec::with_capacity(num_addrs);
+        for _ in 0..num_addrs {
+            osd_addrs.push(EntityAddrvec::decode_versioned(buf)?);
+        }
+        let osd_info = Vec::<OsdInfo>::decode(buf)?;
+        let osd_xinfo = Vec::<OsdXInfo>::decode(buf)?;
+        let num_pg_temp = u32::decode(buf)? as usize;
+        let mut pg_temp = BTreeMap::new();
+        for _ in 0..num_pg_temp {
+            let pg = PgId::decode(buf)?;
+            pg_temp.insert(pg, Vec::<u32>::decode(buf)?);
+        }
+        let pg_upmap = BTreeMap::decode(buf)?;
+        let pg_upmap_items = BTreeMap::decode(buf)?;
+        Ok(OSDMap {
+            epoch,
+            fsid,
+            created,
+            modified,
+            flags,
+            pools,
+            pool_name,
+            osd_state,
+            osd_weight,
+            osd_addrs,
+            osd_info,
+            osd_xinfo,
+            pg_temp,
+            pg_upmap,
+            pg_upmap_items,
+            crush_raw: Bytes::decode(buf)?,
+            name_to_pool_id: NameIndex::default(),
+            crush: CrushCache::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testutil {
+    use super::*;
+    use crush::types::CRUSH_BUCKET_STRAW2;
+
+    /// Encodes a minimal straw2 CRUSH map for `num_osds` devices.
+    fn encode_test_crush(num_osds: u32) -> Bytes {
+        // There is no CRUSH encoder yet; build the binary form by hand,
+        // mirroring decode_crush_map's expectations.
+        let mut buf = BytesMut::new();
+        crush::types::CRUSH_MAGIC.encode(&mut buf);
+        1i32.encode(&mut buf); // max_buckets
+        1u32.encode(&mut buf); // max_rules
+        (num_osds as i32).encode(&mut buf); // max_devices
+                                            // bucket -1
+        (CRUSH_BUCKET_STRAW2 as u32).encode(&mut buf);
+        (-1i32).encode(&mut buf);
+        10u16.encode(&mut buf); // type "root"
+        CRUSH_BUCKET_STRAW2.encode(&mut buf);
+        0u8.encode(&mut buf); // hash
+        (0x10000 * num_osds).encode(&mut buf);
+        num_osds.encode(&mut buf);
+        for osd in 0..num_osds as i32 {
+            osd.encode(&mut buf);
+        }
+        for _ in 0..num_osds {
+            0x10000u32.encode(&mut buf);
+        }
+        // rule 0: take -1, chooseleaf firstn 0, emit
+        1u32.encode(&mut buf); // present
+        3u32.encode(&mut buf); // steps
+        0u8.encode(&mut buf); // ruleset
+        1u8.encode(&mut buf); // type
+        1u8.encode(&mut buf); // min_size
+        10u8.encode(&mut buf); // max_size
+        for (op, arg1) in [(1u32, -1i32), (6, 0), (4, 0)] {
+            op.encode(&mut buf);
+            arg1.encode(&mut buf);
+            0i32.encode(&mut buf);
+        }
+        // name maps
+        2u32.encode(&mut buf);
+        0i32.encode(&mut buf);
+        "osd".to_string().encode(&mut buf);
+        10i32.encode(&mut buf);
+        "root".to_string().encode(&mut buf);
+        1u32.encode(&mut buf);
+        (-1i32).encode(&mut buf);
+        "default".to_string().encode(&mut buf);
+        1u32.encode(&mut buf);
+        0i32.encode(&mut buf);
+        "replicated_rule".to_string().encode(&mut buf);
+        buf.freeze()
+    }
+
+    /// A map with `num_osds` up+in OSDs and one replicated pool `1`
+    /// named `rbd`.
+    pub fn test_osdmap(num_osds: u32) -> OSDMap {
+        let pool = PgPool {
+            id: 1,
+            ..Default::default()
+        };
+        let mut map = OSDMap {
+            epoch: 10,
+            fsid: FsId(*b"0123456789abcdef"),
+            pools: BTreeMap::from([(1, pool)]),
+            pool_name: BTreeMap::from([(1, "rbd".to_string())]),
+            osd_state: vec![CEPH_OSD_EXISTS | CEPH_OSD_UP; num_osds as usize],
+            osd_weight: vec![CEPH_OSD_IN; num_osds as usize],
+            osd_addrs: vec![EntityAddrvec::default(); num_osds as usize],
+            osd_info: vec![OsdInfo::default(); num_osds as usize],
+            osd_xinfo: vec![OsdXInfo::default(); num_osds as usize],
+            crush_raw: encode_test_crush(num_osds),
+            ..Default::default()
+        };
+        map.modified = UTime::new(100, 0);
+        let _ = crush::decode_crush_map(&mut map.crush_raw.clone()).expect("test crush decodes");
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testutil::test_osdmap;
+    use super::*;
+
+    #[test]
+    fn osdmap_round_trip() {
+        let map = test_osdmap(4);
+        let mut buf = BytesMut::new();
+        map.encode_versioned(&mut buf);
+        let mut raw = buf.freeze();
+        assert_eq!(OSDMap::decode_versioned(&mut raw).unwrap(), map);
+    }
+
+    #[test]
+    fn pool_lookup_by_name_and_id() {
+        let mut map = test_osdmap(4);
+        map.pools.insert(0, PgPool::default());
+        map.pool_name.insert(0, "device_health_metrics".to_string());
+
+        let (id, pool) = map.pool_by_name("rbd").unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(pool.id, 1);
+        assert_eq!(map.pool_by_id(1).unwrap().id, 1);
+
+        // Pool id 0 is a valid pool, not a sentinel.
+        assert_eq!(map.pool_by_name("device_health_metrics").unwrap().0, 0);
+        assert!(map.pool_by_id(0).is_some());
+
+        assert!(map.pool_by_name("no-such-pool").is_none());
+        assert!(map.pool_by_id(42).is_none());
+        // Pool names are case-sensitive.
+        assert!(map.pool_by_name("RBD").is_none());
+    }
+
+    #[test]
+    fn effective_pg_num_tracks_autoscaling() {
+        let settled = PgPool::default();
+        assert!(!settled.is_autoscaling());
+        assert_eq!(settled.effective_pg_num(), settled.pg_num);
+
+        let scaling = PgPool {
+            pg_num: 32,
+            pg_num_target: 128,
+            flags: CEPH_POOL_FLAG_AUTOSCALE,
+            ..Default::default()
+        };
+        assert!(scaling.is_autoscaling());
+        assert_eq!(scaling.effective_pg_num(), 128);
+    }
+
+    #[test]
+    fn target_pg_num_follows_the_expected_object_hint() {
+        // No hint: nothing to plan with, keep the current pg_num.
+        let unhinted = PgPool::default();
+        assert_eq!(unhinted.expected_num_objects(), 0);
+        assert_eq!(unhinted.target_pg_num_from_expected(1 << 20, 100), 32);
+
+        // Object count drives the split: 10000 objects at 100 per PG
+        // wants 100 PGs, rounded up to 128.
+        let by_objects = PgPool {
+            expected_num_objects: 10_000,
+            ..Default::default()
+        };
+        assert_eq!(by_objects.target_pg_num_from_expected(1 << 20, 100), 128);
+
+        // Large objects make the byte limit dominate: 200 objects of
+        // 10 GiB is 2000 GiB, or 20 PGs at 100 GiB each, rounded to 32.
+        let by_bytes = PgPool {
+            expected_num_objects: 200,
+            ..Default::default()
+        };
+        assert_eq!(by_bytes.target_pg_num_from_expected(10 << 30, 100), 32);
+
+        // A degenerate objects-per-PG target of zero is treated as one.
+        assert_eq!(by_bytes.target_pg_num_from_expected(0, 0), 256);
+    }
+
+    #[test]
+    fn object_maps_to_stable_pg_and_acting_set() {
+        let map = test_osdmap(4);
+        let pg = map.object_to_pg(1, "rbd_header.10ab").unwrap();
+        assert_eq!(pg, map.object_to_pg(1, "rbd_header.10ab").unwrap());
+        // A locator key pins placement to the locator object's PG.
+        assert_eq!(
+            map.object_to_pg_with_locator(1, "some-other-object", Some("rbd_header.10ab"))
+                .unwrap(),
+            pg
+        );
+        assert_eq!(
+            map.object_to_pg_with_locator(1, "rbd_header.10ab", Some(""))
+                .unwrap(),
+            pg
+        );
+        assert!(pg.ps() < 32);
+        let acting = map.pg_acting_set(pg).unwrap();
+        assert!(!acting.is_empty());
+        assert!(acting.iter().all(|&osd| osd < 4));
+    }
+
+    #[test]
+    fn pg_temp_overrides_crush() {
+        let mut map = test_osdmap(4);
+        let pg = map.object_to_pg(1, "foo").unwrap();
+        map.pg_temp.insert(pg, vec![3, 1]);
+        assert_eq!(map.pg_acting_set(pg).unwrap(), vec![3, 1]);
+        assert_eq!(map.pg_primary(pg).unwrap(), Some(3));
+    }
+
+    #[test]
+    fn nearest_osd_prefers_the_lowest_latency() {
+        use std::time::Duration;
+        let latencies = |osd: u32| match osd {
+            0 => Some(Duration::from_millis(5)),
+            1 => Some(Duration::from_millis(2)),
+            2 => None, // unreachable replica
+            _ => Some(Duration::from_millis(9)),
+        };
+        assert_eq!(OSDMap::nearest_osd_from_set(&[0, 1, 2], latencies), Some(1));
+        assert_eq!(OSDMap::nearest_osd_from_set(&[2, 0], latencies), Some(0));
+        assert_eq!(OSDMap::nearest_osd_from_set(&[2], latencies), None);
+        assert_eq!(OSDMap::nearest_osd_from_set(&[], latencies), None);
+    }
+
+    #[test]
+    fn xinfo_dead_and_laggy_predicates() {
+        let xinfo = OsdXInfo {
+            laggy_probability: u32::MAX / 2 + 1,
+            dead_epoch: 100,
+            ..Default::default()
+        };
+
+        assert!(!xinfo.was_dead_at_epoch(99));
+        assert!(xinfo.was_dead_at_epoch(100));
+        assert!(xinfo.was_dead_at_epoch(101));
+
+        assert!(xinfo.was_laggy_at(0.25));
+        assert!(xinfo.was_laggy_at(0.5));
+        assert!(!xinfo.was_laggy_at(0.75));
+
+        // Stale strictly beyond dead_epoch + threshold.
+        assert!(!xinfo.is_stale(110, 10));
+        assert!(xinfo.is_stale(111, 10));
+
+        // A never-dead record is neither dead nor stale, and a zeroed
+        // slot is never laggy at a positive threshold.
+        let fresh = OsdXInfo::default();
+        assert!(!fresh.was_dead_at_epoch(u32::MAX));
+        assert!(!fresh.is_stale(u32::MAX, 0));
+        assert!(!fresh.was_laggy_at(f32::MIN_POSITIVE));
+    }
+
+    #[test]
+    fn cleanup_drops_pg_temp_entries_matching_crush() {
+        let mut map = test_osdmap(4);
+        let pg = map.object_to_pg(1, "foo").unwrap();
+        let crush_acting = map.pg_acting_set(pg).unwrap();
+
+        // One entry that still overrides, one that has converged back to
+        // the CRUSH mapping, and one for a pool that no longer exists.
+        let diverged = PgId::new(1, (pg.ps() + 1) % 32);
+        map.pg_temp.insert(diverged, vec![3, 1, 0, 2]);
+        map.pg_temp.insert(pg, crush_acting.clone());
+        map.pg_temp.insert(PgId::new(99, 0), vec![0]);
+
+        assert_eq!(map.cleanup_pg_temp(), 1);
+        assert!(!map.pg_temp.contains_key(&pg));
+        assert!(map.pg_temp.contains_key(&diverged));
+        assert!(map.pg_temp.contains_key(&PgId::new(99, 0)));
+        assert_eq!(map.pg_acting_set(pg).unwrap(), crush_acting);
+
+        // A second pass finds nothing new.
+        assert_eq!(map.cleanup_pg_temp(), 0);
+    }
+
+    #[test]
+    fn pg_upmap_overrides_crush() {
+        let mut map = test_osdmap(4);
+        let pg = map.object_to_pg(1, "foo").unwrap();
+        let crush_acting = map.pg_acting_set(pg).unwrap();
+
+        map.pg_upmap.insert(pg, vec![3, 0]);
+        assert_eq!(map.pg_acting_set(pg).unwrap(), vec![3, 0]);
+        map.pg_upmap.remove(&pg);
+
+        let from = crush_acting[0];
+        let to = (0..4).find(|osd| !crush_acting.contains(osd)).unwrap();
+        map.pg_upmap_items.insert(pg, vec![(from, to)]);
+        let swapped = map.pg_acting_set(pg).unwrap();
+        assert_eq!(swapped[0], to);
+        assert_eq!(swapped[1..], crush_acting[1..]);
+        map.pg_upmap_items.remove(&pg);
+
+        // No override: the CRUSH result stands.
+        assert_eq!(map.pg_acting_set(pg).unwrap(), crush_acting);
+    }
+
+    #[test]
+    fn diff_reports_weight_and_membership_changes() {
+        let old = test_osdmap(4);
+
+        let mut new = test_osdmap(4);
+        new.osd_weight[2] = CEPH_OSD_IN / 2;
+        let diff = old.diff(&new);
+        assert!(diff.added_osds.is_empty());
+        assert!(diff.removed_osds.is_empty());
+        assert_eq!(diff.changed_weights, vec![(2, 1.0, 0.5)]);
+        assert!(diff.added_pools.is_empty());
+        assert!(diff.removed_pools.is_empty());
+        assert_eq!(diff.pg_temp_changes, 0);
+        assert_eq!(diff.to_string(), "osd.2 weight 1.0000 -> 0.5000\n");
+
+        let mut grown = test_osdmap(6);
+        grown.pool_name.insert(2, "cephfs_data".to_string());
+        grown
+            .pools
+            .insert(2, PgPool { id: 2, ..Default::default() });
+        grown.pg_temp.insert(PgId::new(1, 0), vec![1, 0]);
+        let diff = old.diff(&grown);
+        assert_eq!(diff.added_osds, vec![4, 5]);
+        assert_eq!(diff.added_pools, vec![(2, "cephfs_data".to_string())]);
+        assert_eq!(diff.pg_temp_changes, 1);
+
+        let shrunk = grown.diff(&old);
+        assert_eq!(shrunk.removed_osds, vec![4, 5]);
+        assert_eq!(shrunk.removed_pools, vec![(2, "cephfs_data".to_string())]);
+    }
+
+    #[test]
+    fn crush_map_is_decoded_once_and_cached() {
+        let map = test_osdmap(4);
+        let first = map.crush_map().unwrap() as *const _;
+        let second = map.crush_map().unwrap() as *const _;
+        assert_eq!(first, second);
+
+        let empty = OSDMap::default();
+        match empty.crush_map() {
+            Err(OSDClientError::Crush(e)) => {
+                assert!(e.to_string().contains("no CRUSH map"));
+            }
+            other => panic!("expected a crush error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn snapshots_sort_by_stamp_not_id() {
+        let empty = PgPool::default();
+        assert_eq!(empty.snapshots_chronological().count(), 0);
+        assert!(empty.snap_by_name("nightly").is_none());
+
+        let snap = |id: u64, secs: u32, name: &str| PoolSnapInfo {
+            snap_id: id,
+            stamp: UTime::new(secs, 0),
+            name: name.to_string(),
+        };
+        let pool = PgPool {
+            // Snap 3 was taken before snap 2: id order is not time order.
+            snaps: BTreeMap::from([
+                (1, snap(1, 100, "first")),
+                (2, snap(2, 300, "third")),
+                (3, snap(3, 200, "second")),
+            ]),
+            ..Default::default()
+        };
+        let order: Vec<u64> = pool.snapshots_chronological().map(|(id, _)| id).collect();
+        assert_eq!(order, [1, 3, 2]);
+
+        let (id, info) = pool.snap_by_name("second").unwrap();
+        assert_eq!(id, 3);
+        assert_eq!(info.stamp, UTime::new(200, 0));
+        assert!(pool.snap_by_name("Second").is_none());
+    }
+
+    #[test]
+    fn osd_info_state_predicates() {
+        let fresh = OsdInfo::default();
+        assert!(fresh.is_destroyed());
+        assert!(!fresh.was_ever_clean());
+        assert_eq!(fresh.last_clean_interval(), None);
+
+        let healthy = OsdInfo {
+            up_from: 5,
+            up_thru: 40,
+            down_at: 0,
+            last_clean_begin: 10,
+            last_clean_end: 30,
+        };
+        assert!(!healthy.is_destroyed());
+        assert!(healthy.was_ever_clean());
+        assert_eq!(healthy.last_clean_interval(), Some(20));
+
+        let destroyed = OsdInfo {
+            up_from: OSD_EPOCH_DESTROYED,
+            up_thru: OSD_EPOCH_DESTROYED,
+            down_at: OSD_EPOCH_DESTROYED,
+            last_clean_begin: OSD_EPOCH_DESTROYED,
+            last_clean_end: OSD_EPOCH_DESTROYED,
+        };
+        assert!(destroyed.is_destroyed());
+
+        let mut xinfo = OsdXInfo::default();
+        assert!(!xinfo.is_pre_luminous());
+        xinfo.features = 1 << 5; // some ancient feature set
+        assert!(xinfo.is_pre_luminous());
+        xinfo.features |= CEPH_FEATURE_SERVER_LUMINOUS;
+        assert!(!xinfo.is_pre_luminous());
+    }
+
+    #[test]
+    fn shard_id_set_basics() {
+        let mut set = ShardIdSet::default();
+        set.insert(0);
+        set.insert(65);
+        assert!(set.contains(0));
+        assert!(set.contains(65));
+        assert!(!set.contains(1));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 65]);
+        assert_eq!(set.len(), 2);
+        assert!(!set.is_empty());
+    }
+
+    #[test]
+    fn shard_id_set_algebra() {
+        // Patterns straddling the word boundary exercise both words.
+        let a = ShardIdSet {
+            words: [0b1011, 1 << 1],
+        };
+        let b = ShardIdSet {
+            words: [0b0110, 1 << 1 | 1 << 2],
+        };
+
+        assert_eq!(a.union(&b).words, [0b1111, 0b110]);
+        assert_eq!(a.intersection(&b).words, [0b0010, 0b010]);
+        assert_eq!(a.difference(&b).words, [0b1001, 0]);
+        assert_eq!(b.difference(&a).words, [0b0100, 0b100]);
+        assert_eq!(a.symmetric_difference(&b).words, [0b1101, 0b100]);
+
+        assert_eq!(a.len(), 4);
+        assert_eq!(a.union(&b).len(), 6);
+        assert!(a.intersection(&ShardIdSet::default()).is_empty());
+    }
+    #[test]
+    fn bloom_params_capacity_and_fpp() {
+        // 1 KiB filter at 1% fpp: n = 8192 * (ln 2)^2 / -ln(0.01) = 854.
+        let params = BloomHitSetParams {
+            fpp_micro: 10_000,
+            target_size: 1024,
+            seed: 0,
+        };
+        assert_eq!(params.expected_capacity(), 854);
+
+        // Filled to capacity the filter shows roughly the target fpp.
+        let at_capacity = params.actual_fpp(params.expected_capacity());
+        assert!((at_capacity - params.fpp()).abs() < 0.005, "{at_capacity}");
+        // Empty filters never false-positive; overfilled ones degrade.
+        assert_eq!(params.actual_fpp(0), 0.0);
+        assert!(params.actual_fpp(10_000) > params.fpp());
+
+        // Degenerate parameters hold nothing.
+        assert_eq!(BloomHitSetParams::default().expected_capacity(), 0);
+    }
+
+    #[test]
+    fn osd_count_accessors() {
+        let mut map = test_osdmap(4);
+        assert_eq!(map.max_osd(), 4);
+        assert_eq!(map.all_osd_ids().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(map.num_active_osds(), 4);
+
+        // Mark osd.1 down and weight osd.3 out.
+        map.osd_state[1] = CEPH_OSD_EXISTS;
+        map.osd_weight[3] = 0;
+        assert_eq!(map.active_osd_ids().collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(map.num_active_osds(), 2);
+        // The map still covers all four ids.
+        assert_eq!(map.max_osd(), 4);
+
+        assert_eq!(OSDMap::default().max_osd(), 0);
+        assert_eq!(OSDMap::default().num_active_osds(), 0);
+    }
+
+}
@@ -0,0 +1,129 @@
+//! PGMap statistics types (`pg_stat_t` and friends).
+
+use bytes::{Bytes, BytesMut};
+use denc::{Denc, RadosError, VersionedEncode};
+
+/// Aggregated object/byte counters for a set of PGs (`object_stat_sum_t`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PGStatSum {
+    pub num_bytes: i64,
+    pub num_objects: i64,
+    pub num_object_clones: i64,
+    pub num_object_copies: i64,
+    pub num_objects_degraded: i64,
+    pub num_rd: i64,
+    pub num_rd_kb: i64,
+    pub num_wr: i64,
+    pub num_wr_kb: i64,
+}
+
+impl VersionedEncode for PGStatSum {
+    const VERSION: u8 = 1;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.num_bytes.encode(buf);
+        self.num_objects.encode(buf);
+        self.num_object_clones.encode(buf);
+        self.num_object_copies.encode(buf);
+        self.num_objects_degraded.encode(buf);
+        self.num_rd.encode(buf);
+        self.num_rd_kb.encode(buf);
+        self.num_wr.encode(buf);
+        self.num_wr_kb.encode(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        Ok(PGStatSum {
+            num_bytes: i64::decode(buf)?,
+            num_objects: i64::decode(buf)?,
+            num_object_clones: i64::decode(buf)?,
+            num_object_copies: i64::decode(buf)?,
+            num_objects_degraded: i64::decode(buf)?,
+            num_rd: i64::decode(buf)?,
+            num_rd_kb: i64::decode(buf)?,
+            num_wr: i64::decode(buf)?,
+            num_wr_kb: i64::decode(buf)?,
+        })
+    }
+}
+
+/// Per-pool statistics (`pool_stat_t`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStat {
+    pub pool_id: u64,
+    pub stat_sum: PGStatSum,
+}
+
+impl VersionedEncode for PoolStat {
+    const VERSION: u8 = 1;
+    const COMPAT: u8 = 1;
+
+    fn encode_payload(&self, buf: &mut BytesMut) {
+        self.pool_id.encode(buf);
+        self.stat_sum.encode_versioned(buf);
+    }
+
+    fn decode_payload(buf: &mut Bytes, _version: u8) -> Result<Self, RadosError> {
+        Ok(PoolStat {
+            pool_id: u64::decode(buf)?,
+            stat_sum: PGStatSum::decode_versioned(buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_sum() -> PGStatSum {
+        PGStatSum {
+            num_bytes: 1,
+            num_objects: 2,
+            num_object_clones: 3,
+            num_object_copies: 4,
+            num_objects_degraded: 5,
+            num_rd: 6,
+            num_rd_kb: 7,
+            num_wr: 8,
+            num_wr_kb: 9,
+        }
+    }
+
+    #[test]
+    fn stat_sum_round_trip() {
+        let sum = sample_sum();
+        let mut buf = BytesMut::new();
+        sum.encode_versioned(&mut buf);
+        let mut raw = buf.freeze();
+        assert_eq!(PGStatSum::decode_versioned(&mut raw).unwrap(), sum);
+        assert!(raw.is_empty());
+    }
+
+    #[test]
+    fn stat_sum_wire_layout() {
+        // Version header (v1, compat 1, 72 payload bytes) followed by the
+        // nine counters as little-endian i64s, in declaration order.
+        let mut buf = BytesMut::new();
+        sample_sum().encode_versioned(&mut buf);
+        let raw = buf.freeze();
+        assert_eq!(&raw[..6], &[1, 1, 72, 0, 0, 0]);
+        for (i, expected) in (1i64..=9).enumerate() {
+            let start = 6 + i * 8;
+            let field = i64::from_le_bytes(raw[start..start + 8].try_into().unwrap());
+            assert_eq!(field, expected, "field {i}");
+        }
+    }
+
+    #[test]
+    fn pool_stat_round_trip() {
+        let stat = PoolStat {
+            pool_id: 42,
+            stat_sum: sample_sum(),
+        };
+        let mut buf = BytesMut::new();
+        stat.encode_versioned(&mut buf);
+        let mut raw = buf.freeze();
+        assert_eq!(PoolStat::decode_versioned(&mut raw).unwrap(), stat);
+    }
+}
@@ -0,0 +1,178 @@
+//! Automatic re-dispatch of ops that failed transiently.
+
+use std::future::Future;
+use std::time::Duration;
+
+use crate::error::OSDClientError;
+
+/// Exponential backoff between attempts: `initial`, doubling each retry,
+/// capped at `max`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        ExponentialBackoff {
+            initial: Duration::from_millis(50),
+            max: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ExponentialBackoff {
+    /// The delay before the retry following attempt number `attempt`
+    /// (counting from 1).
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(16) as u32;
+        (self.initial * (1 << doublings)).min(self.max)
+    }
+}
+
+/// How often, and with what spacing, transiently failed ops are
+/// re-dispatched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total dispatch attempts, including the first.
+    pub max_attempts: usize,
+    pub backoff: ExponentialBackoff,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: ExponentialBackoff::default(),
+        }
+    }
+}
+
+/// Whether `error` is worth retrying.  Connection-level failures and
+/// `EAGAIN`-style responses may succeed on re-dispatch (possibly against
+/// a reconnected session); logical errors such as a missing object or a
+/// bad argument never will.
+pub fn should_retry(error: &OSDClientError) -> bool {
+    match error {
+        OSDClientError::NotConnected | OSDClientError::Timeout => true,
+        OSDClientError::Messenger(_) => true,
+        // EAGAIN, ENOTCONN, ETIMEDOUT.
+        OSDClientError::OsdError(code) => matches!(*code, -11 | -107 | -110),
+        _ => false,
+    }
+}
+
+impl RetryPolicy {
+    /// Runs `dispatch` until it succeeds, fails non-transiently, or
+    /// `max_attempts` dispatches have been made, sleeping per the backoff
+    /// between attempts.
+    pub async fn run<T, F, Fut>(&self, mut dispatch: F) -> Result<T, OSDClientError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, OSDClientError>>,
+    {
+        let max_attempts = self.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match dispatch().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < max_attempts && should_retry(&e) => {
+                    tokio::time::sleep(self.backoff.delay(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::operation::OpCode;
+
+    fn failing_dispatch(
+        calls: &Arc<AtomicUsize>,
+        error: fn() -> OSDClientError,
+    ) -> impl FnMut() -> std::future::Ready<Result<(), OSDClientError>> + '_ {
+        move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(Err(error()))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_errors_use_every_attempt() {
+        let policy = RetryPolicy::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let err = policy
+            .run(failing_dispatch(&calls, || OSDClientError::Timeout))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OSDClientError::Timeout));
+        assert_eq!(calls.load(Ordering::SeqCst), policy.max_attempts);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_errors_bypass_the_loop() {
+        let policy = RetryPolicy::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let err = policy
+            .run(failing_dispatch(&calls, || OSDClientError::ObjectNotFound {
+                op: OpCode::Read,
+            }))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OSDClientError::ObjectNotFound { .. }));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_late_success_stops_retrying() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            ..Default::default()
+        };
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let value = policy
+            .run(move || {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                std::future::ready(if n < 1 {
+                    Err(OSDClientError::NotConnected)
+                } else {
+                    Ok(42)
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let backoff = ExponentialBackoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(350),
+        };
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(200));
+        assert_eq!(backoff.delay(3), Duration::from_millis(350));
+        assert_eq!(backoff.delay(60), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn retryability_classification() {
+        assert!(should_retry(&OSDClientError::NotConnected));
+        assert!(should_retry(&OSDClientError::OsdError(-11))); // EAGAIN
+        assert!(!should_retry(&OSDClientError::OsdError(-1)));
+        assert!(!should_retry(&OSDClientError::InvalidArgument {
+            op: OpCode::Write
+        }));
+    }
+}
@@ -0,0 +1,232 @@
+//! Per-OSD sessions: one msgr2 connection plus reply/notify routing.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use crush::PgId;
+use tokio::time::Instant;
+
+use msgr2::protocol::MessageHandler;
+use msgr2::state_machine::ConnectionConfig;
+use msgr2::{Connection, Message};
+use tokio::sync::mpsc;
+
+use crate::error::OSDClientError;
+use crate::messages::{MOSDOp, MOSDOpReply, MWatchNotify, CEPH_MSG_OSD_OPREPLY, CEPH_MSG_WATCH_NOTIFY};
+use crate::operation::{OSDOp, OpCode};
+use crate::tracker::{InflightTracker, LatencyHistogram};
+use crate::types::WatchNotification;
+
+/// The object a [`OSDSession::ping`] liveness probe stats.  It need not
+/// exist: a `-ENOENT` reply proves the OSD is alive just as well.
+const HEALTH_CHECK_OBJECT: &str = "__health_check__";
+
+/// A session with one OSD.
+///
+/// Replies are matched to submitters by tid through the
+/// [`InflightTracker`]; watch notifications are routed by cookie to the
+/// subscribed watcher.
+pub struct OSDSession {
+    osd: u32,
+    addr: SocketAddr,
+    config: ConnectionConfig,
+    connection: StdMutex<Arc<Connection>>,
+    tracker: Arc<InflightTracker>,
+    watch_subs: Arc<StdMutex<HashMap<u64, mpsc::UnboundedSender<WatchNotification>>>>,
+}
+
+impl OSDSession {
+    /// Connects to OSD `osd` at `addr` and installs the routing handler.
+    pub async fn connect(
+        osd: u32,
+        addr: SocketAddr,
+        config: ConnectionConfig,
+    ) -> Result<OSDSession, OSDClientError> {
+        let connection = Arc::new(Connection::connect(addr, config.clone()).await?);
+        let tracker = Arc::new(InflightTracker::new());
+        let watch_subs: Arc<StdMutex<HashMap<u64, mpsc::UnboundedSender<WatchNotification>>>> =
+            Arc::new(StdMutex::new(HashMap::new()));
+        connection.set_handler(Self::make_handler(tracker.clone(), watch_subs.clone()));
+        Ok(OSDSession {
+            osd,
+            addr,
+            config,
+            connection: StdMutex::new(connection),
+            tracker,
+            watch_subs,
+        })
+    }
+
+    fn make_handler(
+        tracker: Arc<InflightTracker>,
+        watch_subs: Arc<StdMutex<HashMap<u64, mpsc::UnboundedSender<WatchNotification>>>>,
+    ) -> MessageHandler {
+        Arc::new(move |msg: Message| {
+            let tracker = tracker.clone();
+            let watch_subs = watch_subs.clone();
+            Box::pin(async move {
+                match msg.msg_type {
+                    CEPH_MSG_OSD_OPREPLY => {
+                        let mut front = msg.front.clone();
+                        let reply = MOSDOpReply::decode_front(&mut front)?;
+                        if !tracker.complete(msg.tid, reply) {
+                            tracing::debug!("dropping reply for unknown tid {}", msg.tid);
+                        }
+                    }
+                    CEPH_MSG_WATCH_NOTIFY => {
+                        let mut front = msg.front.clone();
+                        let notify = MWatchNotify::decode_front(&mut front)?;
+                        let sender = watch_subs.lock().unwrap().get(&notify.cookie).cloned();
+                        match sender {
+                            Some(sender) => {
+                                let _ = sender.send(WatchNotification {
+                                    timestamp: notify.timestamp,
+                                    notifier_id: notify.notifier_gid,
+                                    notify_id: notify.notify_id,
+                                    payload: notify.payload,
+                                });
+                            }
+                            None => {
+                                tracing::debug!(
+                                    "notify for unknown watch cookie {:#x}",
+                                    notify.cookie
+                                );
+                            }
+                        }
+                    }
+                    other => {
+                        tracing::debug!("ignoring unhandled message type {other}");
+                    }
+                }
+                Ok(())
+            })
+        })
+    }
+
+    pub fn osd(&self) -> u32 {
+        self.osd
+    }
+
+    /// A snapshot of this session's per-op-type latency histograms.
+    pub(crate) fn latency_histograms(&self) -> HashMap<OpCode, LatencyHistogram> {
+        self.tracker.latency_histograms()
+    }
+
+    pub async fn is_ready(&self) -> bool {
+        self.connection().is_ready().await
+    }
+
+    fn connection(&self) -> Arc<Connection> {
+        self.connection.lock().unwrap().clone()
+    }
+
+    /// Parks every in-flight op for replay by [`OSDSession::reconnect`];
+    /// call when the connection is found dead.  Returns how many ops were
+    /// parked.
+    pub fn on_disconnect(&self) -> usize {
+        self.tracker.abort_all_for_replay()
+    }
+
+    /// Re-establishes the connection and resends the ops parked by
+    /// [`OSDSession::on_disconnect`] in tid order.  Ops whose submitter's
+    /// timeout has already expired are failed instead of replayed.
+    /// Returns how many ops were resent.
+    pub async fn reconnect(&self) -> Result<usize, OSDClientError> {
+        let connection = Arc::new(Connection::connect(self.addr, self.config.clone()).await?);
+        connection.set_handler(Self::make_handler(self.tracker.clone(), self.watch_subs.clone()));
+        *self.connection.lock().unwrap() = connection.clone();
+        let replay = self.tracker.take_replayable(Instant::now());
+        let resent = replay.len();
+        for (tid, op) in replay {
+            connection.send_message(op.into_message(tid)).await?;
+        }
+        Ok(resent)
+    }
+
+    /// Submits `op` under `tid` and waits up to `timeout` for its reply.
+    pub async fn submit(
+        &self,
+        op: MOSDOp,
+        tid: u64,
+        timeout: Duration,
+    ) -> Result<MOSDOpReply, OSDClientError> {
+        let op_type = op.ops.first().map(|op| op.code);
+        let sent_at = Instant::now();
+        let receiver = self.tracker.register(tid, op.clone(), sent_at + timeout);
+        if let Err(e) = self.connection().send_message(op.into_message(tid)).await {
+            self.tracker.cancel(tid);
+            return Err(e.into());
+        }
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(reply)) => {
+                if let Some(op_type) = op_type {
+                    self.tracker.record_completion(op_type, sent_at.elapsed());
+                }
+                Ok(reply)
+            }
+            Ok(Err(_)) => Err(OSDClientError::NotConnected),
+            Err(_) => {
+                self.tracker.cancel(tid);
+                Err(OSDClientError::Timeout)
+            }
+        }
+    }
+
+    /// Probes the OSD with a `STAT` on the liveness-probe object and
+    /// returns the round-trip time.  `tid` must be unique on this
+    /// session; [`crate::OSDClient::check_session_health`] supplies one.
+    pub async fn ping(&self, tid: u64, timeout: Duration) -> Result<Duration, OSDClientError> {
+        let op = MOSDOp::new(PgId::new(0, 0), HEALTH_CHECK_OBJECT, vec![OSDOp::stat()]);
+        measure_rtt(self.submit(op, tid, timeout), timeout).await
+    }
+
+    /// Routes notifications for `cookie` to the returned channel until
+    /// [`OSDSession::unregister_watch`].
+    pub fn register_watch(&self, cookie: u64) -> mpsc::UnboundedReceiver<WatchNotification> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.watch_subs.lock().unwrap().insert(cookie, tx);
+        rx
+    }
+
+    pub fn unregister_watch(&self, cookie: u64) {
+        self.watch_subs.lock().unwrap().remove(&cookie);
+    }
+}
+
+/// Awaits `probe` and reports how long it took, mapping both an elapsed
+/// `timeout` and a probe error of [`OSDClientError::Timeout`] to a
+/// timeout.  An op-level error (for example `-ENOENT` on the probe
+/// object) still counts as a successful liveness check.
+async fn measure_rtt(
+    probe: impl Future<Output = Result<MOSDOpReply, OSDClientError>>,
+    timeout: Duration,
+) -> Result<Duration, OSDClientError> {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, probe).await {
+        Ok(Ok(_)) | Ok(Err(OSDClientError::ObjectNotFound { .. })) => Ok(start.elapsed()),
+        Ok(Err(OSDClientError::Timeout)) | Err(_) => Err(OSDClientError::Timeout),
+        Ok(Err(e)) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn rtt_measurement_times_out() {
+        let hung = std::future::pending();
+        let result = measure_rtt(hung, Duration::from_secs(5)).await;
+        assert!(matches!(result, Err(OSDClientError::Timeout)));
+
+        let quick = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(MOSDOpReply::default())
+        };
+        let rtt = measure_rtt(quick, Duration::from_secs(5)).await.unwrap();
+        assert!(rtt >= Duration::from_millis(10));
+    }
+}
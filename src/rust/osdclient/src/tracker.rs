@@ -0,0 +1,306 @@
+//! In-flight op tracking: matching `MOSDOpReply` tids to waiters.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::messages::{MOSDOp, MOSDOpReply};
+use crate::operation::OpCode;
+
+/// Power-of-two latency buckets: bucket `i` counts completions that took
+/// `[2^i, 2^(i+1))` microseconds.  40 buckets reach past twelve days,
+/// beyond any plausible op timeout.
+const LATENCY_BUCKETS: usize = 40;
+
+/// An HDR-style histogram of completion latencies for one op type.
+#[derive(Clone)]
+pub(crate) struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS],
+    count: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            buckets: [0; LATENCY_BUCKETS],
+            count: 0,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    fn bucket_index(latency: Duration) -> usize {
+        let micros = latency.as_micros().max(1) as u64;
+        (micros.ilog2() as usize).min(LATENCY_BUCKETS - 1)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        self.buckets[Self::bucket_index(latency)] += 1;
+        self.count += 1;
+    }
+
+    pub(crate) fn merge(&mut self, other: &LatencyHistogram) {
+        for (mine, theirs) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *mine += theirs;
+        }
+        self.count += other.count;
+    }
+
+    /// The upper bound of the bucket holding the `q`-quantile sample;
+    /// `None` before anything was recorded.
+    fn percentile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let target = ((self.count as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket;
+            if seen >= target {
+                return Some(Duration::from_micros(1 << (i + 1)));
+            }
+        }
+        None
+    }
+
+    pub(crate) fn stats(&self) -> LatencyStats {
+        LatencyStats {
+            count: self.count,
+            p50: self.percentile(0.50),
+            p99: self.percentile(0.99),
+        }
+    }
+}
+
+/// A summary of one op type's completion latencies.  The percentiles are
+/// bucket upper bounds, so they overestimate by at most a factor of two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub count: u64,
+    pub p50: Option<Duration>,
+    pub p99: Option<Duration>,
+}
+
+struct InflightOp {
+    sender: oneshot::Sender<MOSDOpReply>,
+    sent_at: Instant,
+    /// The op itself, kept so it can be replayed after a reconnect.
+    op: MOSDOp,
+    /// When the submitter's timeout expires; replaying past this point
+    /// is pointless, the waiter is already gone.
+    deadline: Instant,
+}
+
+/// Maps each outstanding tid to the task awaiting its reply.
+#[derive(Default)]
+pub struct InflightTracker {
+    ops: Mutex<HashMap<u64, InflightOp>>,
+    /// Ops parked by [`InflightTracker::abort_all_for_replay`] awaiting a
+    /// reconnect.
+    pending_replay: Mutex<Vec<(u64, InflightOp)>>,
+    latencies: Mutex<HashMap<OpCode, LatencyHistogram>>,
+}
+
+impl InflightTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `tid`; the returned receiver resolves when the reply
+    /// arrives.  `op` and `deadline` are kept for replay after a
+    /// reconnect.
+    pub fn register(&self, tid: u64, op: MOSDOp, deadline: Instant) -> oneshot::Receiver<MOSDOpReply> {
+        let (sender, receiver) = oneshot::channel();
+        self.ops.lock().unwrap().insert(
+            tid,
+            InflightOp {
+                sender,
+                sent_at: Instant::now(),
+                op,
+                deadline,
+            },
+        );
+        receiver
+    }
+
+    /// Parks every in-flight op for replay, in tid order; call when the
+    /// connection is found dead.  Returns how many ops were parked.
+    pub fn abort_all_for_replay(&self) -> usize {
+        let mut parked: Vec<(u64, InflightOp)> = self.ops.lock().unwrap().drain().collect();
+        parked.sort_by_key(|(tid, _)| *tid);
+        let mut pending = self.pending_replay.lock().unwrap();
+        pending.extend(parked);
+        pending.len()
+    }
+
+    /// Takes the parked ops back for resending: ops whose deadline has
+    /// passed are failed (their waiters resolve with an error), the rest
+    /// are re-registered and returned in tid order for the wire.
+    pub fn take_replayable(&self, now: Instant) -> Vec<(u64, MOSDOp)> {
+        let parked = std::mem::take(&mut *self.pending_replay.lock().unwrap());
+        let mut replay = Vec::with_capacity(parked.len());
+        let mut ops = self.ops.lock().unwrap();
+        for (tid, op) in parked {
+            if op.deadline <= now {
+                // Dropping the entry drops the sender, failing the waiter.
+                continue;
+            }
+            replay.push((tid, op.op.clone()));
+            ops.insert(tid, op);
+        }
+        replay
+    }
+
+    /// Delivers `reply` to the waiter for `tid`; returns false for unknown
+    /// (e.g. already timed-out) tids.
+    pub fn complete(&self, tid: u64, reply: MOSDOpReply) -> bool {
+        match self.ops.lock().unwrap().remove(&tid) {
+            Some(op) => op.sender.send(reply).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Drops the waiter for `tid`, if still registered.
+    pub fn cancel(&self, tid: u64) {
+        self.ops.lock().unwrap().remove(&tid);
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.lock().unwrap().is_empty()
+    }
+
+    /// Records one completed op of `op_type` in its latency histogram.
+    pub fn record_completion(&self, op_type: OpCode, latency: Duration) {
+        self.latencies
+            .lock()
+            .unwrap()
+            .entry(op_type)
+            .or_default()
+            .record(latency);
+    }
+
+    /// The 99th-percentile completion latency of `op_type`; `None` until
+    /// one completes.
+    pub fn p99_latency(&self, op_type: OpCode) -> Option<Duration> {
+        self.latencies
+            .lock()
+            .unwrap()
+            .get(&op_type)
+            .and_then(|hist| hist.percentile(0.99))
+    }
+
+    /// A snapshot of the per-op-type histograms, for merging across
+    /// sessions.
+    pub(crate) fn latency_histograms(&self) -> HashMap<OpCode, LatencyHistogram> {
+        self.latencies.lock().unwrap().clone()
+    }
+
+    /// How long the oldest outstanding op has been in flight.
+    pub fn oldest_age(&self) -> Option<Duration> {
+        self.ops
+            .lock()
+            .unwrap()
+            .values()
+            .map(|op| op.sent_at.elapsed())
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::OSDOp;
+    use crush::PgId;
+
+    fn stat_op(oid: &str) -> MOSDOp {
+        MOSDOp::new(PgId::new(0, 0), oid, vec![OSDOp::stat()])
+    }
+
+    fn far_deadline() -> Instant {
+        Instant::now() + Duration::from_secs(3600)
+    }
+
+    #[tokio::test]
+    async fn complete_resolves_the_waiter() {
+        let tracker = InflightTracker::new();
+        let rx = tracker.register(7, stat_op("a"), far_deadline());
+        assert_eq!(tracker.len(), 1);
+        assert!(tracker.complete(7, MOSDOpReply::default()));
+        assert_eq!(rx.await.unwrap(), MOSDOpReply::default());
+        assert!(tracker.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_and_cancelled_tids_are_ignored() {
+        let tracker = InflightTracker::new();
+        assert!(!tracker.complete(1, MOSDOpReply::default()));
+        let _rx = tracker.register(2, stat_op("a"), far_deadline());
+        tracker.cancel(2);
+        assert!(!tracker.complete(2, MOSDOpReply::default()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_resends_unexpired_ops_in_order() {
+        let tracker = InflightTracker::new();
+        // Five ops in flight when the connection breaks; the first two
+        // were submitted with a 1s timeout, the rest with 60s.
+        let short = Instant::now() + Duration::from_secs(1);
+        let long = Instant::now() + Duration::from_secs(60);
+        let mut waiters = Vec::new();
+        for (tid, deadline) in [(1, short), (2, short), (3, long), (4, long), (5, long)] {
+            waiters.push(tracker.register(tid, stat_op(&format!("obj{tid}")), deadline));
+        }
+
+        assert_eq!(tracker.abort_all_for_replay(), 5);
+        assert!(tracker.is_empty());
+
+        // By the time the reconnect lands, the short-timeout ops have
+        // expired: they are failed, the other three are re-registered.
+        tokio::time::advance(Duration::from_secs(5)).await;
+        let replay = tracker.take_replayable(Instant::now());
+        let tids: Vec<u64> = replay.iter().map(|(tid, _)| *tid).collect();
+        assert_eq!(tids, [3, 4, 5]);
+        assert_eq!(replay[0].1.oid, "obj3");
+        assert_eq!(tracker.len(), 3);
+
+        let mut waiters = waiters.into_iter();
+        assert!(waiters.next().unwrap().await.is_err());
+        assert!(waiters.next().unwrap().await.is_err());
+        // A replayed op's reply still reaches the original waiter.
+        assert!(tracker.complete(3, MOSDOpReply::default()));
+        assert_eq!(waiters.next().unwrap().await.unwrap(), MOSDOpReply::default());
+    }
+
+    #[test]
+    fn latencies_are_recorded_per_op_type() {
+        let tracker = InflightTracker::new();
+        assert!(tracker.p99_latency(OpCode::Read).is_none());
+
+        for micros in [100, 200, 400, 100_000] {
+            tracker.record_completion(OpCode::Read, Duration::from_micros(micros));
+        }
+        tracker.record_completion(OpCode::Write, Duration::from_millis(5));
+
+        // The slowest read lands in the [65536, 131072) µs bucket; its
+        // upper bound is the reported p99.
+        assert_eq!(
+            tracker.p99_latency(OpCode::Read),
+            Some(Duration::from_micros(131_072))
+        );
+        let stats = tracker.latency_histograms()[&OpCode::Read].stats();
+        assert_eq!(stats.count, 4);
+        // p50 is the second sample's bucket upper bound.
+        assert_eq!(stats.p50, Some(Duration::from_micros(256)));
+
+        let writes = tracker.latency_histograms()[&OpCode::Write].stats();
+        assert_eq!(writes.count, 1);
+        assert_eq!(writes.p99, Some(Duration::from_micros(8192)));
+    }
+}
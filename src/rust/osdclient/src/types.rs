@@ -0,0 +1,89 @@
+//! Result types returned by `IoCtx` operations.
+
+use bytes::{Bytes, BytesMut};
+use denc::types::{EVersion, UTime};
+use denc::{Denc, RadosError};
+
+/// The snapshot context a client sends with its writes: the newest snap
+/// sequence it has seen plus the IDs of the snaps that exist, newest
+/// first.  The OSD uses it to decide when an object must be cloned.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SnapContext {
+    pub seq: u64,
+    pub snaps: Vec<u64>,
+}
+
+impl Denc for SnapContext {
+    fn encode(&self, buf: &mut BytesMut) {
+        self.seq.encode(buf);
+        self.snaps.encode(buf);
+    }
+
+    fn decode(buf: &mut Bytes) -> Result<Self, RadosError> {
+        Ok(SnapContext {
+            seq: u64::decode(buf)?,
+            snaps: Vec::decode(buf)?,
+        })
+    }
+}
+
+/// The outcome of a mutating operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteResult {
+    /// The object version after the write.
+    pub version: EVersion,
+    /// The object size after the write, when the op reports one (only
+    /// `APPEND` does).
+    pub new_size: Option<u64>,
+}
+
+/// The outcome of a `STAT` operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatResult {
+    pub size: u64,
+    pub mtime: UTime,
+}
+
+/// The outcome of a `stat2`: basic stat plus allocation size and the
+/// object's last-modify version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stat2Result {
+    pub size: u64,
+    pub mtime: UTime,
+    /// Bytes allocated on disk.  Without a dedicated STAT2 op this equals
+    /// `size`; see [`crate::ioctx::IoCtx::stat2`].
+    pub alloc_size: u64,
+    pub version: u64,
+}
+
+/// One non-hole extent of a sparse object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SparseExtent {
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// The outcome of a `SPARSE_READ`: the extent map plus the concatenated
+/// data of all extents, in extent order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SparseReadResult {
+    pub extents: Vec<SparseExtent>,
+    pub data: Bytes,
+}
+
+/// One notification delivered to a watcher.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchNotification {
+    pub timestamp: UTime,
+    pub notifier_id: u64,
+    pub notify_id: u64,
+    pub payload: Bytes,
+}
+
+/// Per-op results of one [`crate::operation::OpBatch`] execution, indexed
+/// by the order in which the ops were added.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BatchResult {
+    pub op_results: Vec<crate::messages::OpResult>,
+    pub version: EVersion,
+}
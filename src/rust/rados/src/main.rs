@@ -0,0 +1,944 @@
+//! `rados`: object-level access to a RADOS cluster.
+//!
+//! The Rust counterpart of `src/tools/rados`, built on the `monclient` and
+//! `osdclient` crates.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use auth::{EntityName, Keyring};
+use bytes::Bytes;
+use cephconfig::CephConfig;
+use clap::{Parser, Subcommand, ValueEnum};
+use denc::entity_addr::EntityAddr;
+use monclient::{MonClient, MonClientConfig};
+use msgr2::ConnectionConfig;
+use osdclient::{IoCtx, OSDClient, OSDClientConfig};
+
+/// The default msgr2 monitor port.
+const DEFAULT_MON_PORT: u16 = 3300;
+
+#[derive(Parser)]
+#[command(
+    name = "rados",
+    about = "rados object storage utility",
+    after_help = "Connection settings are resolved in order: command-line \
+                  argument, then environment variable, then ceph.conf."
+)]
+struct Cli {
+    /// Path to ceph.conf.
+    #[arg(short = 'c', long, env = "CEPH_CONF")]
+    conf: Option<PathBuf>,
+
+    /// Cluster name, used to derive the default conf path
+    /// (/etc/ceph/<cluster>.conf).
+    #[arg(long, env = "CEPH_CLUSTER", default_value = "ceph")]
+    cluster: String,
+
+    /// Comma-separated monitor addresses, overriding the config file.
+    #[arg(short = 'm', long, env = "CEPH_MON_HOST")]
+    mon_host: Option<String>,
+
+    /// Path to the keyring, overriding the config file.
+    #[arg(short = 'k', long, env = "CEPH_KEYRING")]
+    keyring: Option<PathBuf>,
+
+    /// Client name to authenticate as.
+    #[arg(short = 'n', long, default_value = "client.admin")]
+    name: String,
+
+    /// Pool to operate on.
+    #[arg(short = 'p', long)]
+    pool: Option<String>,
+
+    /// Output format for read commands.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    format: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Plain,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write a file (or stdin, with `-`) to an object.
+    Put { object: String, infile: String },
+    /// Read an object to a file (or stdout, with `-`).
+    Get { object: String, outfile: String },
+    /// Show an object's size and mtime.
+    Stat { object: String },
+    /// List the objects in the pool.
+    Ls,
+    /// Show cluster and per-pool utilization.
+    Df,
+    /// Remove an object.
+    Rm { object: String },
+    /// Write every regular file in a directory as an object named after it.
+    Import {
+        directory: PathBuf,
+        /// Concurrent transfers.
+        #[arg(long, default_value_t = 4)]
+        parallelism: usize,
+        /// Skip objects that already exist with the same size.
+        #[arg(long)]
+        skip_existing: bool,
+    },
+    /// Download every object in the pool to a file named after it.
+    Export {
+        directory: PathBuf,
+        /// Concurrent transfers.
+        #[arg(long, default_value_t = 4)]
+        parallelism: usize,
+        /// Skip files that already exist with the same size.
+        #[arg(long)]
+        skip_existing: bool,
+    },
+    /// Watch an object, printing each notification as it arrives.
+    Watch {
+        object: String,
+        /// Exit after the first notification.
+        #[arg(long)]
+        notify_once: bool,
+    },
+    /// Send a notification to an object's watchers.
+    Notify { object: String, message: String },
+    /// Set an extended attribute on an object.
+    Setxattr {
+        object: String,
+        key: String,
+        value: String,
+        /// Interpret the value as hex-encoded binary.
+        #[arg(long)]
+        hex_value: bool,
+    },
+    /// Print an extended attribute of an object.
+    Getxattr {
+        object: String,
+        key: String,
+        /// Write the raw bytes to stdout instead of requiring UTF-8.
+        #[arg(long)]
+        binary: bool,
+    },
+    /// List an object's extended attribute names, one per line.
+    Listxattrs { object: String },
+    /// Pool management.
+    #[command(subcommand)]
+    Pool(PoolCommand),
+    /// Authentication key inspection.
+    #[command(subcommand)]
+    Auth(AuthCommand),
+}
+
+#[derive(Subcommand)]
+enum AuthCommand {
+    /// List the CephX entities and their capabilities.
+    Ls,
+    /// Show one entity.
+    Get { entity: String },
+}
+
+#[derive(Subcommand)]
+enum PoolCommand {
+    /// Create a pool.
+    Create {
+        name: String,
+        #[arg(long, default_value_t = 32)]
+        pg_num: u32,
+        /// Block until the pool shows up in the OSDMap.
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Delete a pool and everything in it.
+    Delete {
+        name: String,
+        /// Required: pool deletion is irreversible.
+        #[arg(long)]
+        yes_i_really_really_mean_it: bool,
+        /// Block until the pool is gone from the OSDMap.
+        #[arg(long)]
+        wait: bool,
+    },
+    /// List the pools.
+    Ls,
+}
+
+/// Parses the `mon_host` format: comma-separated `host:port` entries, with
+/// the msgr2 port assumed when none is given.
+fn parse_mon_host(raw: &str) -> Result<Vec<EntityAddr>> {
+    let mut addrs = Vec::new();
+    for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let addr = EntityAddr::from_str(part)
+            .or_else(|_| EntityAddr::from_str(&format!("{part}:{DEFAULT_MON_PORT}")))
+            .with_context(|| format!("invalid monitor address {part:?}"))?;
+        addrs.push(addr);
+    }
+    if addrs.is_empty() {
+        bail!("no monitor addresses in {raw:?}");
+    }
+    Ok(addrs)
+}
+
+/// The conf path implied by the cluster name.
+fn default_conf_path(cluster: &str) -> PathBuf {
+    PathBuf::from(format!("/etc/ceph/{cluster}.conf"))
+}
+
+fn load_config(cli: &Cli) -> Result<CephConfig> {
+    if let Some(path) = &cli.conf {
+        return Ok(CephConfig::from_file(path)?);
+    }
+    let default = default_conf_path(&cli.cluster);
+    if default.exists() {
+        return Ok(CephConfig::from_file(default)?);
+    }
+    Ok(CephConfig::default())
+}
+
+async fn connect(cli: &Cli) -> Result<(Arc<MonClient>, Arc<OSDClient>)> {
+    let config = load_config(cli)?;
+    let entity = EntityName::from_str(&cli.name)?;
+    let section = entity.to_string();
+
+    let mon_host = match &cli.mon_host {
+        Some(raw) => raw.clone(),
+        None => config
+            .get_raw(&section, "mon_host")
+            .context("no monitors: pass --mon-host or set mon_host in ceph.conf")?
+            .to_string(),
+    };
+    let mon_addrs = parse_mon_host(&mon_host)?;
+
+    let keyring_path = cli
+        .keyring
+        .clone()
+        .or_else(|| config.get_raw(&section, "keyring").map(PathBuf::from));
+    let keyring = match keyring_path {
+        Some(path) => Some(Keyring::from_file(&path)?),
+        None => None,
+    };
+
+    let mut mon_config = MonClientConfig::new(mon_addrs, entity);
+    mon_config.keyring = keyring;
+
+    let mon = Arc::new(MonClient::new(mon_config.clone()));
+    mon.connect().await?;
+
+    let fsid = mon.get_fsid().await?;
+    tracing::debug!("connected to cluster {fsid:?}");
+
+    let osd = Arc::new(OSDClient::new(
+        mon.clone(),
+        ConnectionConfig::new(mon_config.auth_provider()),
+        OSDClientConfig::default(),
+    ));
+    osd.fetch_osdmap().await?;
+    Ok((mon, osd))
+}
+
+fn require_pool(cli: &Cli) -> Result<&str> {
+    cli.pool
+        .as_deref()
+        .context("this command requires a pool: pass --pool")
+}
+
+async fn open_ioctx(cli: &Cli) -> Result<IoCtx> {
+    let pool = require_pool(cli)?;
+    let (_mon, osd) = connect(cli).await?;
+    Ok(osd.ioctx(pool)?)
+}
+
+fn read_input(infile: &str) -> Result<Bytes> {
+    if infile == "-" {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        Ok(Bytes::from(data))
+    } else {
+        Ok(Bytes::from(
+            std::fs::read(infile).with_context(|| format!("cannot read {infile}"))?,
+        ))
+    }
+}
+
+fn write_output(outfile: &str, data: &[u8]) -> Result<()> {
+    if outfile == "-" {
+        std::io::stdout().write_all(data)?;
+    } else {
+        std::fs::write(outfile, data).with_context(|| format!("cannot write {outfile}"))?;
+    }
+    Ok(())
+}
+
+fn format_mtime(mtime: &denc::types::UTime) -> String {
+    format!("{}.{:09}", mtime.sec, mtime.nsec)
+}
+
+fn format_stat(object: &str, stat: &osdclient::types::StatResult, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Plain => format!(
+            "{object} mtime {}, size {}",
+            format_mtime(&stat.mtime),
+            stat.size
+        ),
+        OutputFormat::Json => serde_json::json!({
+            "name": object,
+            "size": stat.size,
+            "mtime": format_mtime(&stat.mtime),
+        })
+        .to_string(),
+    }
+}
+
+fn format_df(df: &monclient::DfResult, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Plain => {
+            let mut out = format!(
+                "total {} used {} avail {}\n{:<16} {:>4} {:>12} {:>10}",
+                df.stats.total_bytes,
+                df.stats.total_used_bytes,
+                df.stats.total_avail_bytes,
+                "POOL",
+                "ID",
+                "STORED",
+                "OBJECTS"
+            );
+            for pool in &df.pools {
+                out.push_str(&format!(
+                    "\n{:<16} {:>4} {:>12} {:>10}",
+                    pool.name, pool.id, pool.stats.stored, pool.stats.objects
+                ));
+            }
+            out
+        }
+        OutputFormat::Json => serde_json::json!({
+            "stats": {
+                "total_bytes": df.stats.total_bytes,
+                "total_used_bytes": df.stats.total_used_bytes,
+                "total_avail_bytes": df.stats.total_avail_bytes,
+            },
+            "pools": df.pools.iter().map(|pool| serde_json::json!({
+                "name": pool.name,
+                "id": pool.id,
+                "stored": pool.stats.stored,
+                "objects": pool.stats.objects,
+            })).collect::<Vec<_>>(),
+        })
+        .to_string(),
+    }
+}
+
+fn format_ls(objects: &[String], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Plain => objects.join("\n"),
+        OutputFormat::Json => serde_json::json!(objects).to_string(),
+    }
+}
+
+fn format_auth_entries(entries: &[monclient::AuthEntry], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Plain => {
+            let mut out = Vec::new();
+            for entry in entries {
+                out.push(entry.entity.clone());
+                let mut caps: Vec<_> = entry.caps.iter().collect();
+                caps.sort();
+                for (service, cap) in caps {
+                    out.push(format!("\tcaps: [{service}] {cap}"));
+                }
+            }
+            out.join("\n")
+        }
+        OutputFormat::Json => serde_json::json!(entries
+            .iter()
+            .map(|entry| serde_json::json!({
+                "entity": entry.entity,
+                "key_type": entry.key_type,
+                "caps": entry.caps,
+            }))
+            .collect::<Vec<_>>())
+        .to_string(),
+    }
+}
+
+/// Decodes a hex string (optionally with a `0x` prefix) into bytes.
+fn parse_hex(raw: &str) -> Result<Vec<u8>> {
+    let raw = raw.strip_prefix("0x").unwrap_or(raw);
+    if !raw.len().is_multiple_of(2) {
+        bail!("hex value has an odd number of digits");
+    }
+    (0..raw.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&raw[i..i + 2], 16)
+                .with_context(|| format!("invalid hex digits {:?}", &raw[i..i + 2]))
+        })
+        .collect()
+}
+
+/// The errno to report for `err` in JSON mode.
+fn error_code(err: &anyhow::Error) -> i32 {
+    use osdclient::OSDClientError;
+    match err.downcast_ref::<OSDClientError>() {
+        Some(OSDClientError::OsdError(code)) => *code,
+        Some(OSDClientError::ObjectNotFound { .. }) => -2,
+        Some(OSDClientError::ObjectAlreadyExists { .. }) => -17,
+        Some(OSDClientError::OutOfSpace { .. }) => -28,
+        Some(OSDClientError::IOError { .. }) => -5,
+        Some(OSDClientError::InvalidArgument { .. }) => -22,
+        Some(OSDClientError::PoolNotFound(_)) => -2,
+        Some(OSDClientError::Timeout) => -110,
+        Some(OSDClientError::NotConnected) => -107,
+        _ => -1,
+    }
+}
+
+fn format_error(err: &anyhow::Error, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Plain => format!("error: {err:#}"),
+        OutputFormat::Json => serde_json::json!({
+            "error": err.to_string(),
+            "code": error_code(err),
+        })
+        .to_string(),
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    match &cli.command {
+        Command::Put { object, infile } => {
+            let ioctx = open_ioctx(&cli).await?;
+            let data = read_input(infile)?;
+            ioctx.write_full(object, data).await?;
+        }
+        Command::Get { object, outfile } => {
+            let ioctx = open_ioctx(&cli).await?;
+            let data = ioctx.read(object, 0, 0).await?;
+            write_output(outfile, &data)?;
+        }
+        Command::Stat { object } => {
+            let ioctx = open_ioctx(&cli).await?;
+            let stat = ioctx.stat(object).await?;
+            let name = format!("{}/{}", ioctx.pool_name(), object);
+            println!("{}", format_stat(&name, &stat, cli.format));
+        }
+        Command::Ls => {
+            let ioctx = open_ioctx(&cli).await?;
+            let objects = ioctx.list_objects().await?;
+            println!("{}", format_ls(&objects, cli.format));
+        }
+        Command::Df => {
+            let (mon, _osd) = connect(&cli).await?;
+            let df = mon.get_df().await?;
+            println!("{}", format_df(&df, cli.format));
+        }
+        Command::Rm { object } => {
+            let ioctx = open_ioctx(&cli).await?;
+            ioctx.remove(object).await?;
+        }
+        Command::Import {
+            directory,
+            parallelism,
+            skip_existing,
+        } => {
+            let ioctx = open_ioctx(&cli).await?;
+            import_dir(&ioctx, directory, *parallelism, *skip_existing).await?;
+        }
+        Command::Export {
+            directory,
+            parallelism,
+            skip_existing,
+        } => {
+            let ioctx = open_ioctx(&cli).await?;
+            export_dir(&ioctx, directory, *parallelism, *skip_existing).await?;
+        }
+        Command::Watch {
+            object,
+            notify_once,
+        } => {
+            let ioctx = open_ioctx(&cli).await?;
+            let mut handle = ioctx.watch(object).await?;
+            eprintln!(
+                "watching {}/{} (cookie {:#x}); press ^C to stop",
+                ioctx.pool_name(),
+                object,
+                handle.cookie()
+            );
+            while let Some(notification) = handle.next().await {
+                let hex: String = notification
+                    .payload
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect();
+                println!(
+                    "{}.{:09} notifier {} notify_id {} payload {}",
+                    notification.timestamp.sec,
+                    notification.timestamp.nsec,
+                    notification.notifier_id,
+                    notification.notify_id,
+                    hex
+                );
+                handle.ack(&notification).await?;
+                if *notify_once {
+                    break;
+                }
+            }
+            handle.unwatch().await?;
+        }
+        Command::Notify { object, message } => {
+            let ioctx = open_ioctx(&cli).await?;
+            ioctx
+                .notify(object, Bytes::from(message.clone()), 30)
+                .await?;
+        }
+        Command::Setxattr {
+            object,
+            key,
+            value,
+            hex_value,
+        } => {
+            let ioctx = open_ioctx(&cli).await?;
+            let value = if *hex_value {
+                Bytes::from(parse_hex(value)?)
+            } else {
+                Bytes::from(value.clone())
+            };
+            ioctx.setxattr(object, key, value).await?;
+        }
+        Command::Getxattr {
+            object,
+            key,
+            binary,
+        } => {
+            let ioctx = open_ioctx(&cli).await?;
+            let value = ioctx.getxattr(object, key).await?;
+            if *binary {
+                std::io::stdout().write_all(&value)?;
+            } else {
+                let text = std::str::from_utf8(&value)
+                    .context("value is not UTF-8; pass --binary to dump it raw")?;
+                println!("{text}");
+            }
+        }
+        Command::Listxattrs { object } => {
+            let ioctx = open_ioctx(&cli).await?;
+            let xattrs = ioctx.getxattrs(object).await?;
+            let names: Vec<String> = xattrs.into_keys().collect();
+            println!("{}", format_ls(&names, cli.format));
+        }
+        Command::Pool(pool_cmd) => {
+            let (mon, osd) = connect(&cli).await?;
+            match pool_cmd {
+                PoolCommand::Create { name, pg_num, wait } => {
+                    mon.create_pool(name, *pg_num).await?;
+                    if *wait {
+                        wait_for_pool(&osd, name, true).await?;
+                    }
+                }
+                PoolCommand::Delete {
+                    name,
+                    yes_i_really_really_mean_it,
+                    wait,
+                } => {
+                    if !yes_i_really_really_mean_it {
+                        bail!(
+                            "deleting pool {name} is irreversible; \
+                             pass --yes-i-really-really-mean-it to confirm"
+                        );
+                    }
+                    mon.delete_pool(name).await?;
+                    if *wait {
+                        wait_for_pool(&osd, name, false).await?;
+                    }
+                }
+                PoolCommand::Ls => {
+                    let pools = mon.list_pools().await?;
+                    println!("{}", format_ls(&pools, cli.format));
+                }
+            }
+        }
+        Command::Auth(auth_cmd) => {
+            let (mon, _osd) = connect(&cli).await?;
+            let entries = match auth_cmd {
+                AuthCommand::Ls => mon.auth_list().await?,
+                AuthCommand::Get { entity } => vec![mon.auth_get(entity).await?],
+            };
+            println!("{}", format_auth_entries(&entries, cli.format));
+        }
+    }
+    Ok(())
+}
+
+/// Waits for one transfer task and reports overall progress to stderr.
+async fn finish_transfer(
+    set: &mut tokio::task::JoinSet<Result<(String, bool)>>,
+    done: &mut usize,
+    total: usize,
+) -> Result<()> {
+    if let Some(joined) = set.join_next().await {
+        let (name, skipped) = joined.context("transfer task panicked")??;
+        *done += 1;
+        eprintln!(
+            "{:3}% {}{}",
+            *done * 100 / total,
+            name,
+            if skipped { " (skipped)" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+/// Writes every regular file in `directory` as an object named after the
+/// file, running up to `parallelism` transfers at once.
+async fn import_dir(
+    ioctx: &IoCtx,
+    directory: &std::path::Path,
+    parallelism: usize,
+    skip_existing: bool,
+) -> Result<()> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory)
+        .with_context(|| format!("cannot read {}", directory.display()))?
+    {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| anyhow::anyhow!("file name {name:?} is not valid UTF-8"))?;
+        files.push((name, entry.path()));
+    }
+    files.sort();
+
+    let total = files.len();
+    let mut done = 0;
+    let mut set = tokio::task::JoinSet::new();
+    for (name, path) in files {
+        if set.len() >= parallelism.max(1) {
+            finish_transfer(&mut set, &mut done, total).await?;
+        }
+        let ioctx = ioctx.clone();
+        set.spawn(async move {
+            let data = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("cannot read {}", path.display()))?;
+            if skip_existing {
+                if let Ok(stat) = ioctx.stat(&name).await {
+                    if stat.size == data.len() as u64 {
+                        return Ok((name, true));
+                    }
+                }
+            }
+            ioctx.write_full(&name, Bytes::from(data)).await?;
+            Ok((name, false))
+        });
+    }
+    while !set.is_empty() {
+        finish_transfer(&mut set, &mut done, total).await?;
+    }
+    Ok(())
+}
+
+/// Downloads every object in the pool to `directory`, one file per object,
+/// running up to `parallelism` transfers at once.
+async fn export_dir(
+    ioctx: &IoCtx,
+    directory: &std::path::Path,
+    parallelism: usize,
+    skip_existing: bool,
+) -> Result<()> {
+    std::fs::create_dir_all(directory)
+        .with_context(|| format!("cannot create {}", directory.display()))?;
+    let mut objects = ioctx.list_objects().await?;
+    objects.sort();
+
+    let total = objects.len();
+    let mut done = 0;
+    let mut set = tokio::task::JoinSet::new();
+    for object in objects {
+        if set.len() >= parallelism.max(1) {
+            finish_transfer(&mut set, &mut done, total).await?;
+        }
+        let ioctx = ioctx.clone();
+        let path = directory.join(&object);
+        set.spawn(async move {
+            if skip_existing {
+                if let (Ok(meta), Ok(stat)) =
+                    (tokio::fs::metadata(&path).await, ioctx.stat(&object).await)
+                {
+                    if meta.is_file() && meta.len() == stat.size {
+                        return Ok((object, true));
+                    }
+                }
+            }
+            let data = ioctx.read(&object, 0, 0).await?;
+            tokio::fs::write(&path, &data)
+                .await
+                .with_context(|| format!("cannot write {}", path.display()))?;
+            Ok((object, false))
+        });
+    }
+    while !set.is_empty() {
+        finish_transfer(&mut set, &mut done, total).await?;
+    }
+    Ok(())
+}
+
+/// Polls the OSDMap until `pool` is present (or absent, per `exists`).
+async fn wait_for_pool(osd: &OSDClient, pool: &str, exists: bool) -> Result<()> {
+    const ATTEMPTS: usize = 30;
+    for _ in 0..ATTEMPTS {
+        let map = osd.fetch_osdmap().await?;
+        if map.pool_name.values().any(|name| name == pool) == exists {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    bail!("timed out waiting for pool {pool} to be {}", if exists { "created" } else { "deleted" });
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "warn".into()),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+    let cli = Cli::parse();
+    let format = cli.format;
+    if let Err(err) = run(cli).await {
+        eprintln!("{}", format_error(&err, format));
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use denc::types::UTime;
+    use osdclient::types::StatResult;
+
+    #[test]
+    fn stat_json_output() {
+        let stat = StatResult {
+            size: 1234,
+            mtime: UTime::new(1_700_000_000, 5),
+        };
+        let out = format_stat("rbd/foo", &stat, OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["name"], "rbd/foo");
+        assert_eq!(value["size"], 1234);
+        assert_eq!(value["mtime"], "1700000000.000000005");
+    }
+
+    #[test]
+    fn ls_json_output_is_an_array() {
+        let objects = vec!["a".to_string(), "b".to_string()];
+        let out = format_ls(&objects, OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value, serde_json::json!(["a", "b"]));
+        assert_eq!(format_ls(&objects, OutputFormat::Plain), "a\nb");
+    }
+
+    #[test]
+    fn df_json_output() {
+        let df = monclient::DfResult {
+            stats: monclient::types::DfStats {
+                total_bytes: 100,
+                total_used_bytes: 40,
+                total_avail_bytes: 60,
+            },
+            pools: vec![monclient::types::PoolDf {
+                name: "rbd".to_string(),
+                id: 1,
+                stats: monclient::types::PoolDfStats {
+                    stored: 10,
+                    objects: 3,
+                    ..Default::default()
+                },
+            }],
+        };
+        let out = format_df(&df, OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["stats"]["total_bytes"], 100);
+        assert_eq!(value["pools"][0]["name"], "rbd");
+    }
+
+    #[test]
+    fn cluster_name_drives_the_default_conf_path() {
+        assert_eq!(
+            default_conf_path("ceph"),
+            PathBuf::from("/etc/ceph/ceph.conf")
+        );
+        assert_eq!(
+            default_conf_path("backup"),
+            PathBuf::from("/etc/ceph/backup.conf")
+        );
+        let cli = Cli::try_parse_from(["rados", "--cluster", "backup", "df"]).unwrap();
+        assert_eq!(cli.cluster, "backup");
+    }
+
+    #[test]
+    fn pool_subcommands_parse() {
+        let cli = Cli::try_parse_from(["rados", "pool", "create", "rbd", "--pg-num", "64"]).unwrap();
+        match cli.command {
+            Command::Pool(PoolCommand::Create { name, pg_num, wait }) => {
+                assert_eq!(name, "rbd");
+                assert_eq!(pg_num, 64);
+                assert!(!wait);
+            }
+            _ => panic!("expected pool create"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "rados",
+            "pool",
+            "delete",
+            "rbd",
+            "--yes-i-really-really-mean-it",
+            "--wait",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Pool(PoolCommand::Delete {
+                yes_i_really_really_mean_it,
+                wait,
+                ..
+            }) => {
+                assert!(yes_i_really_really_mean_it);
+                assert!(wait);
+            }
+            _ => panic!("expected pool delete"),
+        }
+
+        let cli = Cli::try_parse_from(["rados", "pool", "ls"]).unwrap();
+        assert!(matches!(cli.command, Command::Pool(PoolCommand::Ls)));
+    }
+
+    #[test]
+    fn auth_ls_output() {
+        let entries = vec![monclient::AuthEntry {
+            entity: "client.admin".to_string(),
+            key_type: 1,
+            caps: [
+                ("osd".to_string(), "allow *".to_string()),
+                ("mon".to_string(), "allow *".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        }];
+        assert_eq!(
+            format_auth_entries(&entries, OutputFormat::Plain),
+            "client.admin\n\tcaps: [mon] allow *\n\tcaps: [osd] allow *"
+        );
+        let out = format_auth_entries(&entries, OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value[0]["entity"], "client.admin");
+        assert_eq!(value[0]["key_type"], 1);
+        assert_eq!(value[0]["caps"]["mon"], "allow *");
+
+        let cli = Cli::try_parse_from(["rados", "auth", "get", "osd.0"]).unwrap();
+        match cli.command {
+            Command::Auth(AuthCommand::Get { entity }) => assert_eq!(entity, "osd.0"),
+            _ => panic!("expected auth get"),
+        }
+    }
+
+    #[test]
+    fn hex_values_parse_or_fail_loudly() {
+        assert_eq!(parse_hex("0001ff").unwrap(), vec![0x00, 0x01, 0xff]);
+        assert_eq!(parse_hex("0xDEAD").unwrap(), vec![0xde, 0xad]);
+        assert_eq!(parse_hex("").unwrap(), Vec::<u8>::new());
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn xattr_subcommands_parse() {
+        let cli =
+            Cli::try_parse_from(["rados", "setxattr", "foo", "user.k", "01ff", "--hex-value"])
+                .unwrap();
+        match cli.command {
+            Command::Setxattr {
+                object,
+                key,
+                value,
+                hex_value,
+            } => {
+                assert_eq!(object, "foo");
+                assert_eq!(key, "user.k");
+                assert_eq!(value, "01ff");
+                assert!(hex_value);
+            }
+            _ => panic!("expected setxattr"),
+        }
+
+        let cli = Cli::try_parse_from(["rados", "getxattr", "foo", "user.k"]).unwrap();
+        match cli.command {
+            Command::Getxattr { binary, .. } => assert!(!binary),
+            _ => panic!("expected getxattr"),
+        }
+
+        assert!(Cli::try_parse_from(["rados", "listxattrs"]).is_err());
+    }
+
+    #[test]
+    fn import_export_subcommands_parse() {
+        let cli = Cli::try_parse_from(["rados", "import", "/tmp/dump"]).unwrap();
+        match cli.command {
+            Command::Import {
+                directory,
+                parallelism,
+                skip_existing,
+            } => {
+                assert_eq!(directory, PathBuf::from("/tmp/dump"));
+                assert_eq!(parallelism, 4);
+                assert!(!skip_existing);
+            }
+            _ => panic!("expected import"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "rados",
+            "export",
+            "/tmp/dump",
+            "--parallelism",
+            "8",
+            "--skip-existing",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Export {
+                parallelism,
+                skip_existing,
+                ..
+            } => {
+                assert_eq!(parallelism, 8);
+                assert!(skip_existing);
+            }
+            _ => panic!("expected export"),
+        }
+    }
+
+    #[test]
+    fn errors_carry_an_errno_in_json() {
+        let err = anyhow::Error::from(osdclient::OSDClientError::OsdError(-2));
+        let out = format_error(&err, OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["code"], -2);
+        assert!(value["error"].is_string());
+    }
+}